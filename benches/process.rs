@@ -0,0 +1,110 @@
+//! Throughput of the hot process functions: `cargo bench`, or `cargo bench -- base64` to
+//! run the cases whose name contains a filter.
+
+use std::{env, fs, hint::black_box, io};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rcli::{
+    process_csv, process_encode_stream, process_stream_encrypt, process_text_sign, Base64Format,
+    CsvDialect, OutputFormat, TextSignFormat,
+};
+
+const DATA_LEN: usize = 8 * 1024 * 1024;
+const CSV_ROWS: usize = 50_000;
+
+fn bench_process(c: &mut Criterion) {
+    let dir = env::temp_dir().join("rcli-bench");
+    fs::create_dir_all(&dir).unwrap();
+    let data: Vec<u8> = (0..DATA_LEN).map(|i| (i * 31 % 251) as u8).collect();
+    let data_path = dir.join("data.bin");
+    fs::write(&data_path, &data).unwrap();
+    let data_path = data_path.to_str().unwrap();
+    let csv_path = dir.join("data.csv");
+    let mut csv = String::from("id,name,score,active\n");
+    for i in 0..CSV_ROWS {
+        csv.push_str(&format!("{},name{},{}.5,{}\n", i, i, i % 100, i % 2 == 0));
+    }
+    fs::write(&csv_path, &csv).unwrap();
+    let csv_path = csv_path.to_str().unwrap();
+    let json_path = dir.join("data.json").to_string_lossy().into_owned();
+
+    let mut group = c.benchmark_group("csv");
+    group.throughput(Throughput::Bytes(csv.len() as u64));
+    group.bench_function("convert json", |b| {
+        let dialect = CsvDialect::default();
+        b.iter(|| {
+            process_csv(
+                csv_path,
+                json_path.clone(),
+                OutputFormat::Json,
+                &dialect,
+                None,
+                None,
+                None,
+                1,
+                None,
+            )
+            .unwrap()
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("stream");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("base64 encode", |b| {
+        b.iter(|| {
+            process_encode_stream(
+                &mut data.as_slice(),
+                &mut io::sink(),
+                Base64Format::Standard,
+                None,
+            )
+            .unwrap()
+        })
+    });
+    group.bench_function("blake3 sign", |b| {
+        b.iter(|| {
+            black_box(
+                process_text_sign(
+                    data_path,
+                    "fixtures/blake3.txt",
+                    TextSignFormat::Blake3,
+                    &[],
+                )
+                .unwrap(),
+            )
+        })
+    });
+    group.bench_function("ed25519 sign", |b| {
+        b.iter(|| {
+            black_box(
+                process_text_sign(
+                    data_path,
+                    "fixtures/ed25519.sk",
+                    TextSignFormat::Ed25519,
+                    &[],
+                )
+                .unwrap(),
+            )
+        })
+    });
+    group.bench_function("chacha20 stream encrypt", |b| {
+        b.iter(|| {
+            process_stream_encrypt(
+                &mut data.as_slice(),
+                &mut io::sink(),
+                "fixtures/chacha20poly1305.txt",
+            )
+            .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    // every case reads or encrypts megabytes per iteration, fewer samples keep a run short
+    config = Criterion::default().sample_size(20);
+    targets = bench_process
+}
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+use clap::Parser;
+
+use super::http::generate_token;
+use crate::{process_api_serve, process_api_stdio, CmdExector, ExecContext, Secret};
+
+/// One JSON-RPC 2.0 request per line, one response line back
+#[derive(Debug, Parser)]
+#[command(after_help = "Methods:
+  auth         {token}, required first on --port
+  encode       {data, format?}
+  decode       {data, format?}
+  hash         {data | input, algorithm?}
+  sign         {data, key, format?}
+  verify       {data, key, signature, format?}
+  jwt.sign     {sub, aud, exp}
+  jwt.verify   {token, secrets?}
+  csv.convert  {input, output, format?}")]
+pub struct ServeApiOpts {
+    /// Read requests from stdin and answer on stdout
+    #[arg(long, conflicts_with = "port", required_unless_present = "port")]
+    pub stdio: bool,
+
+    /// Listen on this localhost TCP port instead
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Token TCP clients must send in an auth request first; a random one is printed at
+    /// startup if omitted
+    #[arg(long, env = "RCLI_API_TOKEN", requires = "port")]
+    pub token: Option<Secret>,
+}
+
+impl CmdExector for ServeApiOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        match self.port {
+            Some(port) => {
+                let token = match &self.token {
+                    Some(token) => token.clone(),
+                    None => {
+                        let token = generate_token()?;
+                        writeln!(ctx.stderr(), "API token: {}", token.expose_str())?;
+                        token
+                    }
+                };
+                process_api_serve(port, token).await?
+            }
+            None => {
+                let stdout = ctx.stdout();
+                tokio::task::spawn_blocking(move || process_api_stdio(stdout)).await??
+            }
+        }
+        Ok(())
+    }
+}
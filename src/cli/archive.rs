@@ -0,0 +1,198 @@
+use std::{
+    fmt::{self, Formatter},
+    path::Path,
+    str::FromStr,
+};
+
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{process_archive_create, process_archive_extract, CmdExector, ExecContext};
+
+use super::verify_file_exists;
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum ArchiveSubCommand {
+    #[command(about = "Pack a directory or file into a tar, tar.gz or zip archive")]
+    Create(ArchiveCreateOpts),
+    #[command(about = "Unpack a tar, tar.gz or zip archive")]
+    Extract(ArchiveExtractOpts),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+#[derive(Debug, Parser)]
+pub struct ArchiveCreateOpts {
+    /// Directory or file to pack, stored under its own name
+    #[arg(short, long, value_parser = verify_file_exists)]
+    pub input: String,
+    /// Archive to write, `-` for stdout with tar formats
+    // a String like the global -o it shadows, clap shares the value between the two
+    #[arg(short, long)]
+    pub output: String,
+    /// tar, tar.gz or zip; guessed from the output name when left out
+    #[arg(long, value_parser = parse_archive_format)]
+    pub format: Option<ArchiveFormat>,
+}
+
+#[derive(Debug, Parser)]
+pub struct ArchiveExtractOpts {
+    /// Archive to unpack, `-` for stdin with tar formats
+    #[arg(short, long, value_parser = verify_file_exists)]
+    pub input: String,
+    /// Directory to unpack into, created if missing
+    #[arg(short, long, default_value = ".")]
+    pub output: String,
+    /// tar, tar.gz or zip; guessed from the input name when left out
+    #[arg(long, value_parser = parse_archive_format)]
+    pub format: Option<ArchiveFormat>,
+}
+
+fn parse_archive_format(format: &str) -> Result<ArchiveFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl ArchiveFormat {
+    /// The format a file name's extension stands for
+    pub fn from_path(path: &str) -> Option<Self> {
+        let name = path.to_ascii_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+fn archive_format(format: Option<ArchiveFormat>, path: &str) -> anyhow::Result<ArchiveFormat> {
+    format
+        .or_else(|| ArchiveFormat::from_path(path))
+        .ok_or_else(|| anyhow::anyhow!("Can't tell the archive format of {}, pass --format", path))
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tar" => Ok(ArchiveFormat::Tar),
+            "tar.gz" | "tgz" => Ok(ArchiveFormat::TarGz),
+            "zip" => Ok(ArchiveFormat::Zip),
+            _ => Err(anyhow::anyhow!("Invalid archive format: {}", s)),
+        }
+    }
+}
+
+impl From<ArchiveFormat> for &'static str {
+    fn from(format: ArchiveFormat) -> Self {
+        match format {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for ArchiveCreateOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let format = archive_format(self.format, &self.output)?;
+        // progress and the summary go to stderr, stdout may be the archive itself
+        let summary = process_archive_create(
+            Path::new(&self.input),
+            &self.output,
+            format,
+            |rel, done, total| {
+                if !ctx.quiet {
+                    let _ = writeln!(ctx.stderr(), "[{}/{}] {}", done, total, rel.display());
+                }
+            },
+        )?;
+        if !ctx.quiet {
+            writeln!(
+                ctx.stderr(),
+                "Packed {} entries, {} bytes, into {}",
+                summary.entries,
+                summary.bytes,
+                self.output
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for ArchiveExtractOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let format = archive_format(self.format, &self.input)?;
+        let summary = process_archive_extract(
+            &self.input,
+            self.output.as_ref(),
+            format,
+            |rel, done, total| {
+                if ctx.quiet {
+                    return;
+                }
+                let _ = match total {
+                    Some(total) => writeln!(ctx.stderr(), "[{}/{}] {}", done, total, rel.display()),
+                    None => writeln!(ctx.stderr(), "[{}] {}", done, rel.display()),
+                };
+            },
+        )?;
+        if !ctx.quiet {
+            writeln!(
+                ctx.stderr(),
+                "Unpacked {} entries, {} bytes, into {}",
+                summary.entries,
+                summary.bytes,
+                self.output
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_format_from_path() {
+        assert_eq!(
+            ArchiveFormat::from_path("out.tar.gz"),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::from_path("OUT.TGZ"),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::from_path("out.tar"),
+            Some(ArchiveFormat::Tar)
+        );
+        assert_eq!(
+            ArchiveFormat::from_path("out.zip"),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(ArchiveFormat::from_path("-"), None);
+        assert!(archive_format(None, "-").is_err());
+        assert_eq!(
+            archive_format(Some(ArchiveFormat::Tar), "-").unwrap(),
+            ArchiveFormat::Tar
+        );
+    }
+}
@@ -3,39 +3,78 @@ use std::{fmt::Display, str::FromStr};
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 
-use crate::{process_decode, process_encode, CmdExector};
+use crate::{
+    get_async_reader, hexdump, process_decode, process_decode_stream_async, process_encode,
+    process_encode_stream_async, process_inspect, CmdExector, ExecContext, Input, OutputTarget,
+};
 
 use super::verify_file_exists;
 
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExector)]
 pub enum Base64SubCommand {
-    #[command(name = "encode", about = "Encode base64")]
+    #[command(
+        name = "encode",
+        about = "Encode base64 (or hex, base32, base58, ascii85 via --format)"
+    )]
     Encode(Base64EncodeOpts),
-    #[command(name = "decode", about = "Decode base64")]
+    #[command(
+        name = "decode",
+        about = "Decode base64 (or hex, base32, base58, ascii85 via --format)"
+    )]
     Decode(Base64DecodeOpts),
+    #[command(
+        name = "inspect",
+        about = "Inspect a base64 blob: alphabet, padding, content type and hexdump"
+    )]
+    Inspect(Base64InspectOpts),
 }
 
 #[derive(Debug, Parser)]
 pub struct Base64EncodeOpts {
     #[arg(short, long,value_parser=verify_file_exists,default_value="-")]
     pub input: String,
+    /// Encode this text instead of reading -i
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
     #[arg(long,value_parser=parse_base64_format, default_value = "standard")]
     pub format: Base64Format,
+    /// Wrap encoded lines at N columns (76 if no value, for MIME)
+    #[arg(long, num_args = 0..=1, default_missing_value = "76")]
+    pub wrap: Option<usize>,
 }
 
 #[derive(Debug, Parser)]
 pub struct Base64DecodeOpts {
     #[arg(short, long,value_parser=verify_file_exists,default_value="-" )]
     pub input: String,
+    /// Decode this text instead of reading -i
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
     #[arg(long,value_parser=parse_base64_format, default_value = "standard")]
     pub format: Base64Format,
 }
 
+#[derive(Debug, Parser)]
+pub struct Base64InspectOpts {
+    #[arg(short, long, value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+    /// Inspect this text instead of reading -i
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+    /// Number of decoded bytes to show in the hexdump preview
+    #[arg(short = 'n', long, default_value_t = 64)]
+    pub preview: usize,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Base64Format {
     Standard,
     UrlSafe,
+    Hex,
+    Base32,
+    Base58,
+    Ascii85,
 }
 
 fn parse_base64_format(format: &str) -> Result<Base64Format, anyhow::Error> {
@@ -49,6 +88,10 @@ impl FromStr for Base64Format {
         match s {
             "standard" => Ok(Base64Format::Standard),
             "urlsafe" => Ok(Base64Format::UrlSafe),
+            "hex" => Ok(Base64Format::Hex),
+            "base32" => Ok(Base64Format::Base32),
+            "base58" => Ok(Base64Format::Base58),
+            "ascii85" | "base85" => Ok(Base64Format::Ascii85),
             _ => Err(anyhow::anyhow!("Invalid format: {}", s)),
         }
     }
@@ -59,10 +102,21 @@ impl From<Base64Format> for &'static str {
         match format {
             Base64Format::Standard => "standard",
             Base64Format::UrlSafe => "urlsafe",
+            Base64Format::Hex => "hex",
+            Base64Format::Base32 => "base32",
+            Base64Format::Base58 => "base58",
+            Base64Format::Ascii85 => "ascii85",
         }
     }
 }
 
+impl Base64Format {
+    /// base64 alphabets can be processed in fixed-size blocks without buffering the input
+    pub fn is_streamable(&self) -> bool {
+        matches!(self, Base64Format::Standard | Base64Format::UrlSafe)
+    }
+}
+
 impl Display for Base64Format {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", Into::<&str>::into(*self))
@@ -70,17 +124,59 @@ impl Display for Base64Format {
 }
 
 impl CmdExector for Base64EncodeOpts {
-    async fn execute(&self) -> anyhow::Result<()> {
-        let encode = process_encode(&self.input, self.format)?;
-        println!("{}", encode);
-        Ok(())
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        if self.format.is_streamable() && ctx.output.is_streamable() {
+            let mut reader = get_async_reader(input).await?;
+            let mut writer = ctx.async_writer().await?;
+            process_encode_stream_async(&mut reader, &mut writer, self.format, self.wrap).await?;
+            // wrapped output already ends with a newline
+            if ctx.output == OutputTarget::Stdout && self.wrap.is_none() {
+                writeln!(ctx.stdout())?;
+            }
+            return Ok(());
+        }
+        ctx.write_text(&process_encode(input, self.format)?)
     }
 }
 
 impl CmdExector for Base64DecodeOpts {
-    async fn execute(&self) -> anyhow::Result<()> {
-        let decode = process_decode(&self.input, self.format)?;
-        println!("{}", decode);
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        // terminal output goes through write_bytes, which catches binary data
+        let to_terminal = ctx.output == OutputTarget::Stdout && ctx.stdout_is_terminal();
+        if self.format.is_streamable() && ctx.output.is_streamable() && !to_terminal {
+            let mut reader = get_async_reader(input).await?;
+            let mut writer = ctx.async_writer().await?;
+            process_decode_stream_async(&mut reader, &mut writer, self.format).await?;
+            return Ok(());
+        }
+        // decoded data might be binary, so write the raw bytes instead of a string
+        ctx.write_bytes(&process_decode(input, self.format)?)
+    }
+}
+
+impl CmdExector for Base64InspectOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        let inspection = process_inspect(input, self.preview)?;
+        let mut out = ctx.stdout();
+        writeln!(out, "alphabet:     {}", inspection.alphabet)?;
+        writeln!(out, "padding:      {}", inspection.padding)?;
+        writeln!(out, "encoded len:  {}", inspection.encoded_len)?;
+        if let Some(e) = &inspection.error {
+            writeln!(out, "decode error: {}", e)?;
+            return Ok(());
+        }
+        if let Some(len) = inspection.decoded_len {
+            writeln!(out, "decoded len:  {}", len)?;
+        }
+        if let Some(content_type) = inspection.content_type {
+            writeln!(out, "content type: {}", content_type)?;
+        }
+        if !inspection.preview.is_empty() {
+            write!(out, "{}", hexdump(&inspection.preview, 0))?;
+        }
         Ok(())
     }
 }
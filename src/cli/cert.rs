@@ -0,0 +1,100 @@
+use std::{fs, path::Path};
+
+use chrono::Utc;
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{
+    process_cert_generate, process_cert_inspect, write_key_file, CmdExector, CommandOutput,
+    ExecContext,
+};
+
+use super::verify_file_exists;
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum CertSubCommand {
+    #[command(
+        about = "Generate a self-signed certificate and key, e.g. for http serve --tls-cert"
+    )]
+    Gen(CertGenOpts),
+    #[command(about = "Show the subject, names, validity and fingerprint of a certificate")]
+    Inspect(CertInspectOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct CertGenOpts {
+    /// Common name, also added to the subject alternative names
+    #[arg(long, default_value = "localhost")]
+    pub cn: String,
+    /// More host names or IP addresses the certificate is valid for (comma separated)
+    #[arg(long, value_delimiter = ',')]
+    pub san: Vec<String>,
+    /// Days the certificate is valid for, starting today
+    #[arg(long, default_value_t = 365, value_parser = clap::value_parser!(u32).range(1..))]
+    pub days: u32,
+    /// Directory cert.pem and key.pem are written to (created if missing), or `-` to print them
+    // a String like the global -o it shadows, clap shares the value between the two
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+    /// Overwrite existing files
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct CertInspectOpts {
+    /// PEM (a whole chain is fine) or DER certificate, `-` for stdin
+    #[arg(value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+}
+
+impl CmdExector for CertGenOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let generated = process_cert_generate(&self.cn, &self.san, self.days)?;
+        if self.output == "-" {
+            write!(ctx.stdout(), "{}{}", generated.cert, *generated.key)?;
+            return Ok(());
+        }
+        fs::create_dir_all(&self.output)?;
+        let cert = Path::new(&self.output).join("cert.pem");
+        let key = Path::new(&self.output).join("key.pem");
+        write_key_file(&cert, generated.cert.as_bytes(), false, self.force)?;
+        write_key_file(&key, generated.key.as_bytes(), true, self.force)?;
+        Ok(())
+    }
+}
+
+impl CmdExector for CertInspectOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let certs = process_cert_inspect(&self.input)?;
+        let now = Utc::now();
+        let blocks: Vec<_> = certs
+            .iter()
+            .map(|cert| {
+                let days_left = (cert.not_after - now).num_days();
+                let status = if now < cert.not_before {
+                    "not valid yet".to_string()
+                } else if now > cert.not_after {
+                    "expired".to_string()
+                } else {
+                    format!("{} days left", days_left)
+                };
+                [
+                    format!("Subject:     {}", cert.subject),
+                    format!("Issuer:      {}", cert.issuer),
+                    format!("SANs:        {}", cert.sans.join(", ")),
+                    format!("Serial:      {}", cert.serial),
+                    format!("Not before:  {}", cert.not_before.to_rfc3339()),
+                    format!("Not after:   {} ({})", cert.not_after.to_rfc3339(), status),
+                    format!("CA:          {}", if cert.is_ca { "yes" } else { "no" }),
+                    format!("Fingerprint: {}", cert.fingerprint),
+                ]
+                .join("\n")
+            })
+            .collect();
+        CommandOutput::new(blocks.join("\n\n"))
+            .field("certificates", &certs)
+            .write(ctx)
+    }
+}
@@ -0,0 +1,238 @@
+use std::fs;
+
+use clap::Parser;
+use serde::Deserialize;
+
+use crate::{
+    process_chain, Base64SubCommand, ChainStep, ChainValue, CmdExector, ExecContext, HexSubCommand,
+    Input, JsonSubCommand, Opts, SubCommand, TextSubCommand,
+};
+
+use super::verify_file_exists;
+
+/// Run several rcli commands in-process, each fed with the output of the previous one
+#[derive(Debug, Parser)]
+#[command(
+    after_help = "Steps: genpass, uuid, base64 encode/decode, hash, hex dump/revert, \
+json fmt/get, text sign
+
+Examples:
+  rcli chain \"genpass -l 32 | base64 encode --format urlsafe\"
+  rcli chain -i data.json \"json get --path .token --raw | hash\"
+  rcli chain -f pipeline.yaml"
+)]
+pub struct ChainOpts {
+    /// Steps separated by `|`, each written like the rcli command it runs
+    #[arg(required_unless_present = "file", conflicts_with = "file")]
+    pub pipeline: Option<String>,
+    /// YAML file with a `steps:` list (and optionally `input:`) instead of a pipeline
+    #[arg(short, long, value_parser = verify_file_exists)]
+    pub file: Option<String>,
+    /// Input of the first step; stdin unless the chain starts with a generator
+    #[arg(short, long, value_parser = verify_file_exists)]
+    pub input: Option<String>,
+    /// Input of the first step given inline, instead of -i or stdin
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChainFile {
+    input: Option<String>,
+    steps: Vec<String>,
+}
+
+impl CmdExector for ChainOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let (stages, input) = match &self.file {
+            Some(file) => {
+                let chain: ChainFile = serde_yaml::from_str(&fs::read_to_string(file)?)?;
+                let stages = chain
+                    .steps
+                    .iter()
+                    .map(|step| split_stage(step))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                (stages, self.input.clone().or(chain.input))
+            }
+            None => (
+                split_pipeline(self.pipeline.as_deref().unwrap_or_default())?,
+                self.input.clone(),
+            ),
+        };
+        let steps = stages
+            .into_iter()
+            .map(parse_step)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let input = match (&self.text, input, steps.first()) {
+            (Some(text), _, _) => Some(ChainValue::Bytes(text.clone().into_bytes())),
+            (None, Some(input), _) => Some(ChainValue::Bytes(crate::read_file(input)?)),
+            (None, None, Some(step)) if step.takes_input() => {
+                let mut data = Vec::new();
+                std::io::Read::read_to_end(&mut std::io::stdin(), &mut data)?;
+                Some(ChainValue::Bytes(data))
+            }
+            (None, None, _) => None,
+        };
+        match process_chain(&steps, input)? {
+            ChainValue::Bytes(data) => ctx.write_bytes(&data),
+            value => ctx.write_text(&value.into_text()?),
+        }
+    }
+}
+
+/// Split a pipeline into stages and each stage into arguments. `|` and whitespace
+/// inside single or double quotes are kept.
+fn split_pipeline(pipeline: &str) -> anyhow::Result<Vec<Vec<String>>> {
+    let mut stages = vec![Vec::new()];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in pipeline.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, '|') => {
+                stages.last_mut().unwrap().extend(word.take());
+                stages.push(Vec::new());
+            }
+            (None, c) if c.is_whitespace() => stages.last_mut().unwrap().extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        anyhow::bail!("Unterminated quote in pipeline: {}", pipeline);
+    }
+    stages.last_mut().unwrap().extend(word);
+    if stages.iter().any(|stage| stage.is_empty()) {
+        anyhow::bail!("Empty step in pipeline: {}", pipeline);
+    }
+    Ok(stages)
+}
+
+fn split_stage(step: &str) -> anyhow::Result<Vec<String>> {
+    match split_pipeline(step)?.as_slice() {
+        [stage] => Ok(stage.clone()),
+        _ => anyhow::bail!("A step in a chain file can't contain `|`: {}", step),
+    }
+}
+
+/// Parse one stage with the regular rcli command line, so steps take the same options
+fn parse_step(args: Vec<String>) -> anyhow::Result<ChainStep> {
+    let stage = args.join(" ");
+    let opts = Opts::try_parse_from(std::iter::once("rcli".to_string()).chain(args))?;
+    if opts.output.is_some() {
+        anyhow::bail!("`{}`: only the chain itself takes -o", stage);
+    }
+    let piped = |input: &str, text: &Option<String>| match Input::new(input, text.as_deref()) {
+        Input::Stdin => Ok(()),
+        _ => Err(anyhow::anyhow!(
+            "`{}`: steps read the previous step's output, use `chain -i` for the first",
+            stage
+        )),
+    };
+    let step = match opts.cmd {
+        SubCommand::GenPass(o) if o.cmd.is_none() && o.share.is_none() && o.record.is_none() => {
+            ChainStep::GenPass {
+                length: o.length,
+                charset: o.charset(),
+            }
+        }
+        SubCommand::Uuid(o) if o.cmd.is_none() => ChainStep::Uuid {
+            version: o.version,
+            count: o.count,
+            upper: o.upper,
+        },
+        SubCommand::Base64(Base64SubCommand::Encode(o)) => {
+            piped(&o.input, &o.text)?;
+            ChainStep::Base64Encode {
+                format: o.format,
+                wrap: o.wrap,
+            }
+        }
+        SubCommand::Base64(Base64SubCommand::Decode(o)) => {
+            piped(&o.input, &o.text)?;
+            ChainStep::Base64Decode(o.format)
+        }
+        SubCommand::Hash(o) if o.cmd.is_none() => {
+            piped(&o.input, &o.text)?;
+            ChainStep::Hash(o.algorithm)
+        }
+        SubCommand::Hex(HexSubCommand::Dump(o)) => {
+            piped(&o.input, &o.text)?;
+            ChainStep::HexDump {
+                offset: o.offset,
+                length: o.length,
+            }
+        }
+        SubCommand::Hex(HexSubCommand::Revert(o)) => {
+            piped(&o.input, &o.text)?;
+            ChainStep::HexRevert
+        }
+        SubCommand::Json(JsonSubCommand::Fmt(o)) => {
+            piped(&o.input, &o.text)?;
+            ChainStep::JsonFmt {
+                minify: o.minify,
+                indent: o.indent,
+            }
+        }
+        SubCommand::Json(JsonSubCommand::Get(o)) => {
+            piped(&o.input, &o.text)?;
+            ChainStep::JsonGet {
+                path: o.path,
+                raw: o.raw,
+            }
+        }
+        SubCommand::Text(TextSubCommand::Sign(o)) if !o.armor => {
+            piped(&o.input, &o.text)?;
+            ChainStep::TextSign {
+                key: super::text::required_key_source(
+                    o.key.as_deref(),
+                    o.key_env.as_deref(),
+                    &Input::Text(String::new()),
+                )?,
+                format: super::text::sign_format(o.format, o.oneshot)?,
+                canonicalize: o.canonicalize,
+                binary: o.binary,
+                hex: o.hex,
+            }
+        }
+        _ => anyhow::bail!("`{}` can't be used in a chain", stage),
+    };
+    Ok(step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_pipeline() {
+        let stages = split_pipeline("genpass -l 32 | json get '.a | b' --raw|hash").unwrap();
+        assert_eq!(
+            stages,
+            [
+                vec!["genpass", "-l", "32"],
+                vec!["json", "get", ".a | b", "--raw"],
+                vec!["hash"],
+            ]
+        );
+        assert!(split_pipeline("genpass | | hash").is_err());
+        assert!(split_pipeline("json get \".a").is_err());
+    }
+
+    #[test]
+    fn test_parse_step() {
+        let step = parse_step(split_stage("base64 encode --format urlsafe").unwrap()).unwrap();
+        assert!(matches!(step, ChainStep::Base64Encode { .. }));
+        assert!(parse_step(split_stage("genpass -l 32").unwrap())
+            .unwrap()
+            .to_string()
+            .starts_with("genpass"));
+        // input comes from the previous step, and not every command can be chained
+        assert!(parse_step(split_stage("hash -i Cargo.toml").unwrap()).is_err());
+        assert!(parse_step(split_stage("http serve").unwrap()).is_err());
+    }
+}
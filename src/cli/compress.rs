@@ -0,0 +1,115 @@
+use std::{
+    fmt::{self, Formatter},
+    ops::RangeInclusive,
+    str::FromStr,
+};
+
+use clap::Parser;
+
+use crate::{
+    get_reader, process_compress, process_decompress, CmdExector, ExecContext, Input, OutputTarget,
+};
+
+use super::verify_file_exists;
+
+#[derive(Debug, Parser)]
+pub struct CompressOpts {
+    #[arg(short, long, value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+    /// Compress this text instead of reading -i
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+    /// gzip, zstd or brotli
+    #[arg(short, long, value_parser = parse_compress_algorithm, default_value = "gzip")]
+    pub algorithm: CompressAlgorithm,
+    /// gzip 0-9 (default 6), zstd -7-22 (default 3), brotli 0-11 (default 9)
+    #[arg(
+        short,
+        long,
+        allow_negative_numbers = true,
+        conflicts_with = "decompress"
+    )]
+    pub level: Option<i32>,
+    /// Decompress instead
+    #[arg(short, long)]
+    pub decompress: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompressAlgorithm {
+    Gzip,
+    Zstd,
+    Brotli,
+}
+
+fn parse_compress_algorithm(algorithm: &str) -> Result<CompressAlgorithm, anyhow::Error> {
+    algorithm.parse()
+}
+
+impl CompressAlgorithm {
+    /// What `level: None` stands for, each tool's own default except brotli's 11, which
+    /// is many times slower for a few percent
+    pub fn default_level(self) -> i32 {
+        match self {
+            CompressAlgorithm::Gzip => 6,
+            CompressAlgorithm::Zstd => 3,
+            CompressAlgorithm::Brotli => 9,
+        }
+    }
+
+    pub fn levels(self) -> RangeInclusive<i32> {
+        match self {
+            CompressAlgorithm::Gzip => 0..=9,
+            CompressAlgorithm::Zstd => -7..=22,
+            CompressAlgorithm::Brotli => 0..=11,
+        }
+    }
+}
+
+impl FromStr for CompressAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" | "gz" => Ok(CompressAlgorithm::Gzip),
+            "zstd" | "zst" => Ok(CompressAlgorithm::Zstd),
+            "brotli" | "br" => Ok(CompressAlgorithm::Brotli),
+            _ => Err(anyhow::anyhow!("Invalid compression algorithm: {}", s)),
+        }
+    }
+}
+
+impl From<CompressAlgorithm> for &'static str {
+    fn from(algorithm: CompressAlgorithm) -> Self {
+        match algorithm {
+            CompressAlgorithm::Gzip => "gzip",
+            CompressAlgorithm::Zstd => "zstd",
+            CompressAlgorithm::Brotli => "brotli",
+        }
+    }
+}
+
+impl fmt::Display for CompressAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for CompressOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        if !self.decompress && ctx.output == OutputTarget::Stdout && ctx.stdout_is_terminal() {
+            anyhow::bail!(
+                "Refusing to write compressed data to a terminal, pipe it or pass -o <file>"
+            );
+        }
+        let mut reader = get_reader(input)?;
+        let mut writer = ctx.writer()?;
+        if self.decompress {
+            process_decompress(&mut reader, &mut writer, self.algorithm)?;
+        } else {
+            process_compress(&mut reader, &mut writer, self.algorithm, self.level)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use std::{ffi::OsString, path::PathBuf};
+
+use anyhow::Context;
+use clap::{Command, CommandFactory, FromArgMatches};
+use toml::{Table, Value};
+
+use super::Opts;
+
+const DEFAULT_CONFIG: &str = ".config/rcli/config.toml";
+const ENV_PREFIX: &str = "RCLI";
+
+impl Opts {
+    /// Parse the command line with defaults from the config file. Precedence, highest
+    /// first: the command line, `RCLI_<SUBCOMMAND>_<OPTION>` env vars, the config file,
+    /// the built-in defaults. Exits with usage on invalid arguments, like `Opts::parse`.
+    pub fn parse_with_config() -> anyhow::Result<Self> {
+        let args: Vec<OsString> = std::env::args_os().collect();
+        let config = load_config(config_path(&args)?)?;
+        let matches = config_command(config.as_ref())?.get_matches_from(args);
+        Ok(Opts::from_arg_matches(&matches).unwrap_or_else(|e| e.exit()))
+    }
+}
+
+/// `--config` has to be known before parsing, since the file changes how parsing goes
+fn config_path(args: &[OsString]) -> anyhow::Result<Option<PathBuf>> {
+    let mut args = args.iter().map(|arg| arg.to_string_lossy());
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--config" {
+            let path = args.next().context("--config needs a file")?;
+            return Ok(Some(PathBuf::from(path.as_ref())));
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Ok(Some(PathBuf::from(path)));
+        }
+    }
+    if let Some(path) = std::env::var_os("RCLI_CONFIG") {
+        return Ok(Some(PathBuf::from(path)));
+    }
+    // the default location is optional, an explicit one must exist
+    Ok(dirs::home_dir()
+        .map(|home| home.join(DEFAULT_CONFIG))
+        .filter(|path| path.exists()))
+}
+
+fn load_config(path: Option<PathBuf>) -> anyhow::Result<Option<Table>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config {}", path.display()))?;
+    let table =
+        toml::from_str(&content).with_context(|| format!("Invalid config {}", path.display()))?;
+    Ok(Some(table))
+}
+
+/// The rcli command with env vars and config values set as option defaults
+fn config_command(config: Option<&Table>) -> anyhow::Result<Command> {
+    let cmd = with_env(Opts::command(), ENV_PREFIX);
+    match config {
+        Some(table) => with_config(cmd, table, ""),
+        None => Ok(cmd),
+    }
+}
+
+/// Let every option be set from `RCLI_<SUBCOMMAND>_<OPTION>`, e.g. `RCLI_HTTP_SERVE_PORT`
+fn with_env(mut cmd: Command, prefix: &str) -> Command {
+    let options: Vec<(String, String)> = cmd
+        .get_arguments()
+        .filter(|arg| arg.get_env().is_none() && !arg.is_positional())
+        .filter_map(|arg| Some((arg.get_id().to_string(), arg.get_long()?.to_string())))
+        .filter(|(id, _)| id != "help" && id != "version")
+        .collect();
+    for (id, long) in options {
+        let name = format!("{}_{}", prefix, long.to_uppercase().replace('-', "_"));
+        cmd = cmd.mut_arg(id, |arg| arg.env(name).hide_env(true));
+    }
+    let subcommands: Vec<String> = cmd
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+    for name in subcommands {
+        let prefix = format!("{}_{}", prefix, name.to_uppercase().replace('-', "_"));
+        cmd = cmd.mut_subcommand(name, |sub| with_env(sub, &prefix));
+    }
+    cmd
+}
+
+/// Apply one config table: nested tables name subcommands (`[http.serve]`), other keys
+/// are long option names, with `-` or `_` between words
+fn with_config(mut cmd: Command, table: &Table, section: &str) -> anyhow::Result<Command> {
+    for (key, value) in table {
+        let name = key.replace('_', "-");
+        let path = match section {
+            "" => name.clone(),
+            section => format!("{}.{}", section, name),
+        };
+        if let Value::Table(table) = value {
+            if cmd.find_subcommand(&name).is_none() {
+                anyhow::bail!("Unknown config section [{}]", path);
+            }
+            let mut result = Ok(());
+            cmd = cmd.mut_subcommand(&name, |sub| match with_config(sub.clone(), table, &path) {
+                Ok(sub) => sub,
+                Err(e) => {
+                    result = Err(e);
+                    sub
+                }
+            });
+            result?;
+            continue;
+        }
+        let id = cmd
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(name.as_str()))
+            .map(|arg| arg.get_id().to_string())
+            .with_context(|| format!("Unknown config option `{}`", path))?;
+        let values = config_values(value).with_context(|| format!("Invalid value for {}", path))?;
+        // a configured value satisfies options that are otherwise required
+        cmd = cmd.mut_arg(id, |arg| arg.default_values(values).required(false));
+    }
+    Ok(cmd)
+}
+
+fn config_values(value: &Value) -> anyhow::Result<Vec<String>> {
+    match value {
+        Value::String(s) => Ok(vec![s.clone()]),
+        Value::Integer(_) | Value::Float(_) | Value::Boolean(_) | Value::Datetime(_) => {
+            Ok(vec![value.to_string()])
+        }
+        Value::Array(values) => Ok(values
+            .iter()
+            .map(config_values)
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .concat()),
+        Value::Table(_) => anyhow::bail!("expected a value, not a table"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SubCommand;
+
+    fn parse(config: &str, args: &[&str]) -> anyhow::Result<Opts> {
+        let config: Table = toml::from_str(config)?;
+        let matches = config_command(Some(&config))?.try_get_matches_from(args)?;
+        Ok(Opts::from_arg_matches(&matches)?)
+    }
+
+    #[test]
+    fn test_config_defaults() -> anyhow::Result<()> {
+        let config = "[genpass]\nlength = 24\n\n[jwt.sign]\naud = \"device1\"\n";
+        let opts = parse(config, &["rcli", "genpass"])?;
+        assert!(matches!(opts.cmd, SubCommand::GenPass(o) if o.length == 24));
+        // the command line wins over the config
+        let opts = parse(config, &["rcli", "genpass", "-l", "8"])?;
+        assert!(matches!(opts.cmd, SubCommand::GenPass(o) if o.length == 8));
+        // and the config fills in required options
+        let opts = parse(config, &["rcli", "jwt", "sign", "-s", "acme", "-e", "1h"])?;
+        assert!(
+            matches!(opts.cmd, SubCommand::Jwt(crate::JwtSubCommand::Sign(o)) if o.aud == "device1")
+        );
+
+        assert!(parse("[genpass]\nlenght = 24\n", &["rcli", "genpass"]).is_err());
+        assert!(parse("[nope]\nx = 1\n", &["rcli", "genpass"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_path() -> anyhow::Result<()> {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(
+            config_path(&args(&["rcli", "--config", "a.toml", "genpass"]))?,
+            Some(PathBuf::from("a.toml"))
+        );
+        assert_eq!(
+            config_path(&args(&["rcli", "genpass", "--config=b.toml"]))?,
+            Some(PathBuf::from("b.toml"))
+        );
+        Ok(())
+    }
+}
@@ -1,24 +1,39 @@
 use std::{
     fmt::{self, Formatter},
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
-use clap::Parser;
+use clap::{ArgAction, Parser};
 
-use crate::{process_csv, CmdExector};
+use crate::{
+    process_csv, process_csv_convert_dir, process_csv_from_json, process_csv_join,
+    process_csv_schema, CmdExector, CsvDialect, CsvLookup, CsvSort, CsvTypes, ExecContext, Input,
+    SortKey,
+};
 
-use super::verify_file_exists;
+use super::{verify_file_exists, verify_file_or_glob, verify_path, watch};
 
 #[derive(Debug, Clone, Copy)]
 pub enum OutputFormat {
+    Csv,
     Json,
+    Ndjson,
     Yaml,
+    Xlsx,
+    /// CREATE TABLE and INSERT statements, column types inferred from the data
+    Sql,
 }
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CsvOpts {
-    #[arg(short, long, value_parser=verify_file_exists)]
-    pub input: String,
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubCommand>,
+
+    /// A CSV file, or a quoted glob like "logs/*.csv" whose files share one header
+    #[arg(short, long, value_parser = verify_file_or_glob, required = true)]
+    pub input: Option<String>,
 
     #[arg(short, long)]
     pub output: Option<String>,
@@ -26,22 +41,197 @@ pub struct CsvOpts {
     #[arg(long, value_parser=parse_format, default_value = "json")]
     pub format: OutputFormat,
 
-    #[arg(short, long, default_value_t = ',')]
-    pub delimiter: char,
+    /// A single character, or tab, pipe, semicolon, comma or space
+    #[arg(short, long, value_parser = parse_csv_char, default_value = ",")]
+    pub delimiter: u8,
+
+    #[arg(long, value_parser = parse_csv_char, default_value = "\"")]
+    pub quote: u8,
 
-    #[arg(long, default_value_t = true)]
+    /// Allow rows with more or fewer fields than the first one
+    #[arg(long)]
+    pub flexible: bool,
+
+    /// The first row is data, columns are named col_0, col_1, ...
+    #[arg(long = "no-header", action = ArgAction::SetFalse)]
     pub header: bool,
+
+    /// Add a column with this name holding the file each row came from
+    #[arg(long)]
+    pub source_column: Option<String>,
+
+    /// Emit numbers, booleans and empty fields as native JSON values (json, ndjson, yaml)
+    #[arg(long)]
+    pub infer_types: bool,
+
+    /// Force column types: {"column": "integer"} or the output of `csv schema`
+    #[arg(long, value_parser = verify_file_exists, requires = "infer_types")]
+    pub schema: Option<String>,
+
+    /// Reference CSV loaded into memory to enrich every input row
+    #[arg(long, value_parser = verify_file_exists, requires_all = ["lookup_key", "append"])]
+    pub lookup: Option<String>,
+
+    /// Column present in both the input and the lookup file
+    #[arg(long, requires = "lookup")]
+    pub lookup_key: Option<String>,
+
+    /// Lookup columns appended to each matching row (comma separated)
+    #[arg(long, value_delimiter = ',', requires = "lookup")]
+    pub append: Vec<String>,
+
+    /// Order rows by a column, `name` or `name:desc`; numbers sort as numbers, before text. Inputs
+    /// larger than memory are sorted in runs on disk
+    #[arg(long, value_parser = parse_sort_key)]
+    pub sort_by: Option<SortKey>,
+
+    /// Drop records identical to one already written
+    #[arg(long)]
+    pub dedup: bool,
+
+    /// Threads serializing json, ndjson and yaml rows, 0 for one per core; output order is kept
+    #[arg(short, long, default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Convert again every time the input, schema or lookup file changes, until Ctrl-C
+    #[arg(long)]
+    pub watch: bool,
+}
+
+#[derive(Debug, Parser)]
+pub enum CsvSubCommand {
+    #[command(about = "Convert every CSV in a directory, mirroring its structure")]
+    ConvertDir(CsvConvertDirOpts),
+    #[command(about = "Infer column types and print them as a JSON Schema or Arrow schema")]
+    Schema(CsvSchemaOpts),
+    #[command(about = "Convert a JSON array of objects or NDJSON back to CSV")]
+    FromJson(CsvFromJsonOpts),
+    #[command(about = "Join two CSV files on a shared column")]
+    Join(CsvJoinOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvConvertDirOpts {
+    #[arg(value_parser = verify_path)]
+    pub dir: PathBuf,
+
+    #[arg(long, value_parser=parse_format, default_value = "json")]
+    pub format: OutputFormat,
+
+    #[arg(long)]
+    pub out_dir: PathBuf,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvSchemaOpts {
+    #[arg(short, long, value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+    /// CSV to infer the schema of, instead of reading -i
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+
+    #[arg(long, value_parser = parse_schema_format, default_value = "json-schema")]
+    pub format: SchemaFormat,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFromJsonOpts {
+    #[arg(short, long, value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+    /// JSON rows to convert, instead of reading -i
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    /// A single character, or tab, pipe, semicolon, comma or space
+    #[arg(short, long, value_parser = parse_csv_char, default_value = ",")]
+    pub delimiter: u8,
+
+    #[arg(long, value_parser = parse_csv_char, default_value = "\"")]
+    pub quote: u8,
+
+    /// Write the rows only, without the header row
+    #[arg(long = "no-header", action = ArgAction::SetFalse)]
+    pub header: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvJoinOpts {
+    #[arg(long, value_parser = verify_file_exists)]
+    pub left: String,
+
+    /// Loaded into memory, so pass the smaller file here
+    #[arg(long, value_parser = verify_file_exists)]
+    pub right: String,
+
+    /// Column present in both files
+    #[arg(long)]
+    pub on: String,
+
+    /// `left` keeps left rows without a match, `inner` drops them
+    #[arg(long, value_parser = parse_join_kind, default_value = "left")]
+    pub how: JoinKind,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SchemaFormat {
+    JsonSchema,
+    Arrow,
 }
 
 fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
     format.parse()
 }
 
+fn parse_csv_char(value: &str) -> Result<u8, anyhow::Error> {
+    match value {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        "pipe" => Ok(b'|'),
+        "semicolon" => Ok(b';'),
+        "comma" => Ok(b','),
+        "space" => Ok(b' '),
+        _ => match value.as_bytes() {
+            [c] if c.is_ascii() => Ok(*c),
+            _ => Err(anyhow::anyhow!(
+                "Expected a single ASCII character, got: {}",
+                value
+            )),
+        },
+    }
+}
+
+fn parse_sort_key(key: &str) -> Result<SortKey, anyhow::Error> {
+    Ok(key.parse()?)
+}
+
+fn parse_join_kind(how: &str) -> Result<JoinKind, anyhow::Error> {
+    how.parse()
+}
+
+fn parse_schema_format(format: &str) -> Result<SchemaFormat, anyhow::Error> {
+    format.parse()
+}
+
 impl From<OutputFormat> for &'static str {
     fn from(format: OutputFormat) -> Self {
         match format {
+            OutputFormat::Csv => "csv",
             OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
             OutputFormat::Yaml => "yaml",
+            OutputFormat::Xlsx => "xlsx",
+            OutputFormat::Sql => "sql",
         }
     }
 }
@@ -51,8 +241,12 @@ impl FromStr for OutputFormat {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
+            "csv" => Ok(OutputFormat::Csv),
             "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
             "yaml" => Ok(OutputFormat::Yaml),
+            "xlsx" => Ok(OutputFormat::Xlsx),
+            "sql" => Ok(OutputFormat::Sql),
             _ => Err(anyhow::anyhow!("Invalid format: {}", s)),
         }
     }
@@ -64,14 +258,207 @@ impl fmt::Display for OutputFormat {
     }
 }
 
+impl FromStr for JoinKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inner" => Ok(JoinKind::Inner),
+            "left" => Ok(JoinKind::Left),
+            _ => Err(anyhow::anyhow!("Invalid join: {}", s)),
+        }
+    }
+}
+
+impl From<JoinKind> for &'static str {
+    fn from(how: JoinKind) -> Self {
+        match how {
+            JoinKind::Inner => "inner",
+            JoinKind::Left => "left",
+        }
+    }
+}
+
+impl fmt::Display for JoinKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for SchemaFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json-schema" => Ok(SchemaFormat::JsonSchema),
+            "arrow" => Ok(SchemaFormat::Arrow),
+            _ => Err(anyhow::anyhow!("Invalid schema format: {}", s)),
+        }
+    }
+}
+
+impl From<SchemaFormat> for &'static str {
+    fn from(format: SchemaFormat) -> Self {
+        match format {
+            SchemaFormat::JsonSchema => "json-schema",
+            SchemaFormat::Arrow => "arrow",
+        }
+    }
+}
+
+impl fmt::Display for SchemaFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
 impl CmdExector for CsvOpts {
-    async fn execute(&self) -> anyhow::Result<()> {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        match &self.cmd {
+            Some(CsvSubCommand::ConvertDir(opts)) => return opts.execute(ctx).await,
+            Some(CsvSubCommand::Schema(opts)) => return opts.execute(ctx).await,
+            Some(CsvSubCommand::FromJson(opts)) => return opts.execute(ctx).await,
+            Some(CsvSubCommand::Join(opts)) => return opts.execute(ctx).await,
+            None => {}
+        }
+        let input = self
+            .input
+            .as_deref()
+            .expect("input is required without a subcommand");
+        if !self.watch {
+            return self.convert(input);
+        }
+        if input == "-" || verify_file_exists(input).is_err() {
+            anyhow::bail!("--watch needs a single input file, not stdin or a glob");
+        }
+        let paths = [Some(input), self.schema.as_deref(), self.lookup.as_deref()];
+        let paths = paths.iter().flatten().map(Path::new).collect::<Vec<_>>();
+        watch(ctx, &paths, || self.convert(input))
+    }
+}
+
+impl CsvOpts {
+    fn convert(&self, input: &str) -> anyhow::Result<()> {
         let output = if let Some(output) = self.output.clone() {
             output.clone()
         } else {
             format!("output.{}", self.format)
         };
-        process_csv(&self.input, output, self.format)?;
+        let lookup = match (&self.lookup, &self.lookup_key) {
+            (Some(path), Some(key)) => Some(CsvLookup::load(path, key, &self.append)?),
+            _ => None,
+        };
+        let dialect = CsvDialect {
+            delimiter: self.delimiter,
+            quote: self.quote,
+            flexible: self.flexible,
+            has_headers: self.header,
+        };
+        let types = if self.infer_types {
+            Some(CsvTypes::load(self.schema.as_deref())?)
+        } else {
+            None
+        };
+        let sort = CsvSort {
+            sort_by: self.sort_by.clone(),
+            dedup: self.dedup,
+            ..Default::default()
+        };
+        process_csv(
+            input,
+            output,
+            self.format,
+            &dialect,
+            lookup.as_ref(),
+            self.source_column.as_deref(),
+            types.as_ref(),
+            match self.jobs {
+                0 => std::thread::available_parallelism()?.get(),
+                jobs => jobs,
+            },
+            (sort.sort_by.is_some() || sort.dedup).then_some(&sort),
+        )?;
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvConvertDirOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let results = process_csv_convert_dir(
+            &self.dir,
+            &self.out_dir,
+            self.format,
+            |result, done, total| {
+                let _ = writeln!(
+                    ctx.stderr(),
+                    "[{}/{}] {}",
+                    done,
+                    total,
+                    result.input.display()
+                );
+            },
+        )
+        .await?;
+        let width = results
+            .iter()
+            .map(|r| r.input.display().to_string().len())
+            .max()
+            .unwrap_or(4)
+            .max(4);
+        writeln!(ctx.stdout(), "{:<width$}  {:>8}  STATUS", "FILE", "ROWS")?;
+        for result in &results {
+            let status = match &result.error {
+                Some(e) => format!("error: {}", e),
+                None => format!("ok -> {}", result.output.display()),
+            };
+            let file = result.input.display().to_string();
+            writeln!(
+                ctx.stdout(),
+                "{:<width$}  {:>8}  {}",
+                file,
+                result.rows,
+                status
+            )?;
+        }
+        let failed = results.iter().filter(|r| r.error.is_some()).count();
+        if failed > 0 {
+            return Err(anyhow::anyhow!(
+                "{} of {} files failed to convert",
+                failed,
+                results.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvSchemaOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        ctx.write_text(&process_csv_schema(input, self.format)?)
+    }
+}
+
+impl CmdExector for CsvFromJsonOpts {
+    async fn execute(&self, _ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        let dialect = CsvDialect {
+            delimiter: self.delimiter,
+            quote: self.quote,
+            has_headers: self.header,
+            ..Default::default()
+        };
+        process_csv_from_json(input, &self.output, &dialect)?;
+        Ok(())
+    }
+}
+
+impl CmdExector for CsvJoinOpts {
+    async fn execute(&self, _ctx: &mut ExecContext) -> anyhow::Result<()> {
+        if self.left == "-" && self.right == "-" {
+            anyhow::bail!("Only one of --left and --right can be read from stdin");
+        }
+        process_csv_join(&self.left, &self.right, &self.on, self.how, &self.output)?;
         Ok(())
     }
 }
@@ -0,0 +1,114 @@
+use std::{
+    fmt::{self, Display},
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{process_dns_lookup, CmdExector, CommandOutput, ExecContext};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum DnsSubCommand {
+    #[command(about = "Look up DNS records of a name")]
+    Lookup(DnsLookupOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct DnsLookupOpts {
+    pub name: String,
+    #[arg(short = 't', long = "type", value_parser = parse_record_type, default_value = "A")]
+    pub record_type: DnsRecordType,
+    /// Ask this name server, e.g. 1.1.1.1 or 127.0.0.1:5353, instead of the system's
+    #[arg(short, long, value_parser = parse_server)]
+    pub server: Option<SocketAddr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DnsRecordType {
+    A,
+    Aaaa,
+    Cname,
+    Mx,
+    Ns,
+    Txt,
+    Soa,
+    Srv,
+    Ptr,
+    Caa,
+}
+
+fn parse_record_type(record_type: &str) -> Result<DnsRecordType, anyhow::Error> {
+    record_type.parse()
+}
+
+/// An IP address, on port 53 unless one is given
+fn parse_server(server: &str) -> Result<SocketAddr, anyhow::Error> {
+    if let Ok(ip) = server.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, 53));
+    }
+    server
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid name server, expected an IP address: {}", server))
+}
+
+impl FromStr for DnsRecordType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "A" => Ok(DnsRecordType::A),
+            "AAAA" => Ok(DnsRecordType::Aaaa),
+            "CNAME" => Ok(DnsRecordType::Cname),
+            "MX" => Ok(DnsRecordType::Mx),
+            "NS" => Ok(DnsRecordType::Ns),
+            "TXT" => Ok(DnsRecordType::Txt),
+            "SOA" => Ok(DnsRecordType::Soa),
+            "SRV" => Ok(DnsRecordType::Srv),
+            "PTR" => Ok(DnsRecordType::Ptr),
+            "CAA" => Ok(DnsRecordType::Caa),
+            _ => Err(anyhow::anyhow!("Invalid record type: {}", s)),
+        }
+    }
+}
+
+impl From<DnsRecordType> for &'static str {
+    fn from(record_type: DnsRecordType) -> Self {
+        match record_type {
+            DnsRecordType::A => "A",
+            DnsRecordType::Aaaa => "AAAA",
+            DnsRecordType::Cname => "CNAME",
+            DnsRecordType::Mx => "MX",
+            DnsRecordType::Ns => "NS",
+            DnsRecordType::Txt => "TXT",
+            DnsRecordType::Soa => "SOA",
+            DnsRecordType::Srv => "SRV",
+            DnsRecordType::Ptr => "PTR",
+            DnsRecordType::Caa => "CAA",
+        }
+    }
+}
+
+impl Display for DnsRecordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for DnsLookupOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let records = process_dns_lookup(&self.name, self.record_type, self.server).await?;
+        let lines: Vec<_> = records
+            .iter()
+            .map(|r| format!("{}\t{}\t{}\t{}", r.name, r.ttl, r.record_type, r.data))
+            .collect();
+        CommandOutput::new(lines.join("\n"))
+            .field("name", &self.name)
+            .field("type", self.record_type.to_string())
+            .field("server", self.server.map(|s| s.to_string()))
+            .field("records", &records)
+            .write(ctx)
+    }
+}
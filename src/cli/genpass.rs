@@ -1,9 +1,23 @@
-use crate::CmdExector;
+use std::path::PathBuf;
+
+use crate::{
+    password_policy_hash, process_genpass, process_share_secret, CmdExector, CommandOutput,
+    ExecContext, PasswordCharset, PasswordMeta, RngSeed,
+};
+use chrono::Utc;
 use clap::Parser;
 use zxcvbn::zxcvbn;
 
+use super::{parse_seed, verify_path};
+
+const DEFAULT_RECORD: &str = "~/.rcli/passwords.meta";
+
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct GenPassOpts {
+    #[command(subcommand)]
+    pub cmd: Option<GenPassSubCommand>,
+
     #[arg(short, long, default_value_t = 16)]
     pub length: u8,
 
@@ -18,21 +32,159 @@ pub struct GenPassOpts {
 
     #[arg(short, long, default_value_t = true)]
     pub symbols: bool,
+
+    /// Leave out characters that look alike: 0 O 1 l I
+    #[arg(long)]
+    pub no_ambiguous: bool,
+
+    /// Draw from exactly these characters instead of the classes above
+    #[arg(long)]
+    pub charset: Option<String>,
+
+    /// Never use these characters, e.g. --exclude '$%' for a site rejecting them
+    #[arg(long, default_value = "")]
+    pub exclude: String,
+
+    /// At least this many uppercase letters
+    #[arg(long, default_value_t = 0)]
+    pub min_upper: u8,
+
+    /// At least this many lowercase letters
+    #[arg(long, default_value_t = 0)]
+    pub min_lower: u8,
+
+    /// At least this many digits
+    #[arg(long, default_value_t = 0)]
+    pub min_digits: u8,
+
+    /// At least this many symbols
+    #[arg(long, default_value_t = 0)]
+    pub min_symbols: u8,
+
+    /// Encrypt the password into this `http serve` directory and print a one-time link
+    #[arg(long, value_parser = verify_path)]
+    pub share: Option<PathBuf>,
+
+    /// Base url of the `http serve` instance used by --share
+    #[arg(long, default_value = "http://localhost:8080")]
+    pub share_url: String,
+
+    /// Note the label, creation time and policy (never the password) for rotation reminders
+    #[arg(long, num_args = 0..=1, default_missing_value = DEFAULT_RECORD, requires = "label")]
+    pub record: Option<String>,
+
+    /// What the password is for, e.g. prod-db
+    #[arg(long, requires = "record")]
+    pub label: Option<String>,
+
+    /// Days until `genpass due` lists the password
+    #[arg(long, default_value_t = 90, requires = "record")]
+    pub rotate_days: u32,
+
+    /// Draw from a ChaCha20 stream seeded with this hex value, so the same seed gives the
+    /// same password (fixtures, CI). The password is only as secret as the seed, so this
+    /// also needs --insecure-seed
+    // the global --seed under a visible name; the rng comes from `secure_rng`, which enforces
+    // --insecure-seed for it and RCLI_SEED alike. Global so `genpass due` still accepts it
+    #[arg(long, global = true, value_parser = parse_seed)]
+    pub seed: Option<RngSeed>,
+}
+
+#[derive(Debug, Parser)]
+pub enum GenPassSubCommand {
+    #[command(about = "List recorded passwords past their rotation interval")]
+    Due(GenPassDueOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct GenPassDueOpts {
+    #[arg(long, default_value = DEFAULT_RECORD)]
+    pub record: String,
+}
+
+/// Expand a leading `~/` to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+impl GenPassOpts {
+    pub fn charset(&self) -> PasswordCharset {
+        PasswordCharset {
+            upper: self.uppercase,
+            lowercase: self.lowercase,
+            numbers: self.numbers,
+            symbols: self.symbols,
+            no_ambiguous: self.no_ambiguous,
+            custom: self.charset.clone(),
+            exclude: self.exclude.clone(),
+            min_upper: self.min_upper,
+            min_lowercase: self.min_lower,
+            min_numbers: self.min_digits,
+            min_symbols: self.min_symbols,
+        }
+    }
 }
 
 impl CmdExector for GenPassOpts {
-    async fn execute(&self) -> anyhow::Result<()> {
-        let password = crate::process_genpass(
-            self.length,
-            self.uppercase,
-            self.lowercase,
-            self.numbers,
-            self.symbols,
-        )?;
-        println!("{}", password);
-        // output the password strength in stderr
-        let estimate = zxcvbn(&password, &[])?;
-        eprintln!("Password strength: {}", estimate.score());
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        if let Some(GenPassSubCommand::Due(opts)) = &self.cmd {
+            return opts.execute(ctx).await;
+        }
+        let charset = self.charset();
+        let password = process_genpass(self.length, &charset)?;
+        if let (Some(record), Some(label)) = (&self.record, &self.label) {
+            let path = expand_home(record);
+            let mut meta = PasswordMeta::load(&path)?;
+            let policy = password_policy_hash(self.length, &charset);
+            meta.record(label, policy, self.rotate_days, Utc::now());
+            meta.save(&path)?;
+        }
+        if let Some(dir) = &self.share {
+            let shared = process_share_secret(password.as_bytes(), dir)?;
+            let url = self.share_url.trim_end_matches('/');
+            // the key travels in the fragment, which browsers never send to the server
+            let link = format!("{}/{}#{}", url, shared.path, shared.key);
+            return CommandOutput::new(&link).field("url", &link).write(ctx);
+        }
+        let strength = zxcvbn(&password, &[])?.score();
+        CommandOutput::new(&password)
+            .field("password", &password)
+            .field("strength", strength)
+            .write(ctx)?;
+        if !ctx.json {
+            // output the password strength in stderr
+            writeln!(ctx.stderr(), "Password strength: {}", strength)?;
+        }
         Ok(())
     }
 }
+
+impl CmdExector for GenPassDueOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let meta = PasswordMeta::load(expand_home(&self.record))?;
+        let now = Utc::now();
+        let due = meta.due(now);
+        if due.is_empty() {
+            writeln!(ctx.stderr(), "No passwords due for rotation")?;
+            return Ok(());
+        }
+        let width = due.iter().map(|r| r.label.len()).max().unwrap_or(5).max(5);
+        let mut lines = vec![format!(
+            "{:<width$}  {:<10}  {:>8}  POLICY",
+            "LABEL", "CREATED", "OVERDUE"
+        )];
+        for record in due {
+            lines.push(format!(
+                "{:<width$}  {:<10}  {:>7}d  {}",
+                record.label,
+                record.created_at.format("%Y-%m-%d"),
+                (now - record.due_at()).num_days(),
+                record.policy
+            ));
+        }
+        ctx.write_text(&lines.join("\n"))
+    }
+}
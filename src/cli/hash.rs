@@ -0,0 +1,128 @@
+use std::{fmt::Display, str::FromStr};
+
+use clap::Parser;
+
+use crate::{
+    process_hash_async, process_hash_check, CmdExector, CommandOutput, ExecContext, Input,
+};
+
+use super::verify_file_exists;
+
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct HashOpts {
+    #[command(subcommand)]
+    pub cmd: Option<HashSubCommand>,
+    #[arg(short, long, value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+    /// Hash this text instead of reading -i
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+    #[arg(short, long, value_parser = parse_algorithm, default_value = "sha256")]
+    pub algorithm: HashAlgorithm,
+}
+
+#[derive(Debug, Parser)]
+pub enum HashSubCommand {
+    #[command(about = "Verify files against a checksum manifest (e.g. SHA256SUMS)")]
+    Check(HashCheckOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct HashCheckOpts {
+    #[arg(short, long, value_parser = verify_file_exists)]
+    pub checksums: String,
+    #[arg(short, long, value_parser = parse_algorithm, default_value = "sha256")]
+    pub algorithm: HashAlgorithm,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha512,
+    Blake3,
+    Md5,
+}
+
+fn parse_algorithm(algorithm: &str) -> Result<HashAlgorithm, anyhow::Error> {
+    algorithm.parse()
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "md5" => Ok(HashAlgorithm::Md5),
+            _ => Err(anyhow::anyhow!("Invalid algorithm: {}", s)),
+        }
+    }
+}
+
+impl From<HashAlgorithm> for &'static str {
+    fn from(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Md5 => "md5",
+        }
+    }
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for HashOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        match &self.cmd {
+            Some(HashSubCommand::Check(opts)) => opts.execute(ctx).await,
+            None => {
+                let input = Input::new(&self.input, self.text.as_deref());
+                // like sha256sum, text that isn't a file is listed as `-`
+                let file = input.path().map_or("-".into(), |p| p.display().to_string());
+                let digest = process_hash_async(input, self.algorithm).await?;
+                CommandOutput::new(format!("{}  {}", digest, file))
+                    .field("file", &file)
+                    .field("algorithm", self.algorithm.to_string())
+                    .field("digest", &digest)
+                    .write(ctx)
+            }
+        }
+    }
+}
+
+impl CmdExector for HashCheckOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let results = process_hash_check(&self.checksums, self.algorithm)?;
+        let failed = results.iter().filter(|r| !r.ok).count();
+        let lines: Vec<_> = results
+            .iter()
+            .map(|result| match (&result.error, result.ok) {
+                (Some(e), _) => format!("{}: FAILED open or read ({})", result.file, e),
+                (None, true) => format!("{}: OK", result.file),
+                (None, false) => format!("{}: FAILED", result.file),
+            })
+            .collect();
+        let reason = (failed > 0).then(|| {
+            format!(
+                "{} of {} computed checksums did NOT match",
+                failed,
+                results.len()
+            )
+        });
+        if let (Some(reason), false) = (&reason, ctx.json) {
+            writeln!(ctx.stderr(), "{}", reason)?;
+        }
+        CommandOutput::verification(failed == 0, reason)
+            .field("files", &results)
+            .text(lines.join("\n"))
+            .write(ctx)
+    }
+}
@@ -0,0 +1,54 @@
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{process_hex_dump, process_hex_revert, CmdExector, ExecContext, Input};
+
+use super::verify_file_exists;
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum HexSubCommand {
+    #[command(about = "Dump a file as xxd-style hex")]
+    Dump(HexDumpOpts),
+    #[command(about = "Rebuild a binary file from a hex dump")]
+    Revert(HexRevertOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct HexDumpOpts {
+    #[arg(value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+    /// Dump this text instead of reading a file
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+    /// Start at this byte offset (-s, like xxd)
+    #[arg(short = 's', long, default_value_t = 0)]
+    pub offset: u64,
+    /// Dump at most this many bytes
+    #[arg(short, long)]
+    pub length: Option<u64>,
+}
+
+#[derive(Debug, Parser)]
+pub struct HexRevertOpts {
+    #[arg(value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+    /// A dump to revert, given inline instead of in a file
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+}
+
+impl CmdExector for HexDumpOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        let dump = process_hex_dump(input, self.offset, self.length)?;
+        ctx.write_bytes(dump.as_bytes())
+    }
+}
+
+impl CmdExector for HexRevertOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        ctx.write_bytes(&process_hex_revert(input)?)
+    }
+}
@@ -1,17 +1,36 @@
-use std::path::PathBuf;
+use std::{
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
+use axum::http::StatusCode;
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
+use glob::Pattern;
 
-use crate::CmdExector;
+use crate::{
+    process_genpass, process_http_bench, process_http_echo, ByteRate, CmdExector, ExecContext,
+    PasswordCharset, Secret,
+};
 
-use super::verify_path;
+use super::{verify_file_exists, verify_path};
 
+/// Generated tokens avoid symbols so they survive being pasted into urls
+const TOKEN_LENGTH: u8 = 24;
+
+// parsed once per run, boxing the serve options would buy nothing
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExector)]
 pub enum HttpSubCommand {
     #[command(about = "serve a directory over HTTP")]
     Serve(HttpServeOpts),
+    #[command(about = "load test a url with concurrent GET requests")]
+    Bench(HttpBenchOpts),
+    #[command(about = "print every request received, e.g. to debug webhooks")]
+    Echo(HttpEchoOpts),
 }
 
 #[derive(Debug, Parser)]
@@ -20,10 +39,173 @@ pub struct HttpServeOpts {
     pub dir: PathBuf,
     #[arg(long, default_value_t = 8080)]
     pub port: u16,
+    /// token for the /_rcli admin UI; a random one is printed at startup if omitted
+    #[arg(long)]
+    pub admin_token: Option<Secret>,
+    /// Require this token on every request (?token= or X-Auth-Token); generated if no value is given
+    #[arg(long, num_args = 0..=1, default_missing_value = "")]
+    pub token: Option<Secret>,
+    /// PEM certificate chain, enables HTTPS together with --tls-key
+    #[arg(long, value_parser = verify_file_exists, requires = "tls_key")]
+    pub tls_cert: Option<String>,
+    #[arg(long, value_parser = verify_file_exists, requires = "tls_cert")]
+    pub tls_key: Option<String>,
+    /// Also listen for plain HTTP on this port and redirect everything to HTTPS
+    #[arg(long, requires = "tls_cert")]
+    pub redirect_http: Option<u16>,
+    /// Send Strict-Transport-Security with this max-age in seconds
+    #[arg(long, requires = "tls_cert")]
+    pub hsts: Option<u64>,
+    /// Serve index.html (or the --index file) for paths that don't exist, for single-page apps
+    #[arg(long)]
+    pub spa: bool,
+    /// File served at directory roots instead of the HTML listing, e.g. index.html
+    #[arg(long)]
+    pub index: Option<String>,
+    /// Requests handled at once; more wait until one finishes
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_connections: Option<u32>,
+    /// Answer requests taking longer than this many seconds with 408, e.g. stalled uploads
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: Option<u64>,
+    /// Answer WebDAV requests so the directory mounts as a network drive; enables uploads
+    #[arg(long)]
+    pub webdav: bool,
+    /// Render .md files as HTML pages instead of serving the raw text
+    #[arg(long)]
+    pub render_markdown: bool,
+    /// Names left out of listings and answered with 404; --hide "" serves dotfiles too
+    #[arg(long, value_delimiter = ',', default_value = ".*", value_parser = parse_glob)]
+    pub hide: Vec<Pattern>,
+    /// More names to leave out like hidden ones, e.g. "*.secret,*.key"
+    #[arg(long, value_delimiter = ',', value_parser = parse_glob)]
+    pub exclude: Vec<Pattern>,
+    /// Slow each download down to this bandwidth, e.g. 1MBps or 256KBps
+    #[arg(long, value_parser = parse_byte_rate)]
+    pub rate_limit: Option<ByteRate>,
+    /// Bandwidth shared by all downloads at once
+    #[arg(long, value_parser = parse_byte_rate)]
+    pub rate_limit_total: Option<ByteRate>,
+}
+
+#[derive(Debug, Parser)]
+pub struct HttpBenchOpts {
+    pub url: String,
+    /// Requests in flight at once
+    #[arg(short, long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub concurrency: u32,
+    /// Requests in total
+    #[arg(short = 'n', long, default_value_t = 200)]
+    pub requests: usize,
+}
+
+#[derive(Debug, Parser)]
+pub struct HttpEchoOpts {
+    #[arg(long, default_value_t = 9000)]
+    pub port: u16,
+    /// Status code every request is answered with
+    #[arg(long, default_value_t = 200, value_parser = clap::value_parser!(u16).range(100..=999))]
+    pub status: u16,
 }
 
 impl CmdExector for HttpServeOpts {
-    async fn execute(&self) -> anyhow::Result<()> {
-        crate::process_http_serve(self.dir.clone(), self.port).await
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let config = crate::HttpServeConfig {
+            path: self.dir.clone(),
+            port: self.port,
+            admin_token: match &self.admin_token {
+                Some(token) => token.clone(),
+                None => generate_token()?,
+            },
+            access_token: match &self.token {
+                Some(token) if token.expose_str().is_empty() => Some(generate_token()?),
+                token => token.clone(),
+            },
+            tls: match (&self.tls_cert, &self.tls_key) {
+                (Some(cert), Some(key)) => Some(crate::TlsConfig {
+                    cert: cert.into(),
+                    key: key.into(),
+                    redirect_port: self.redirect_http,
+                    hsts: self.hsts,
+                }),
+                _ => None,
+            },
+            spa: self.spa,
+            index: self.index.clone(),
+            max_connections: self.max_connections.map(|max| max as usize),
+            timeout: self.timeout.map(Duration::from_secs),
+            webdav: self.webdav,
+            render_markdown: self.render_markdown,
+            hide: self.hide.clone(),
+            exclude: self.exclude.clone(),
+            rate_limit: self.rate_limit,
+            total_rate_limit: self.rate_limit_total,
+        };
+        writeln!(ctx.stdout(), "Admin UI: {}", config.admin_url())?;
+        if let Some(url) = config.share_url() {
+            writeln!(ctx.stdout(), "Share: {}", url)?;
+        }
+        Ok(crate::process_http_serve(config).await?)
+    }
+}
+
+impl CmdExector for HttpBenchOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let report =
+            process_http_bench(&self.url, self.concurrency as usize, self.requests).await?;
+        writeln!(
+            ctx.stdout(),
+            "Requests:   {} in {:.2}s, {:.1} req/s",
+            report.requests,
+            report.elapsed.as_secs_f64(),
+            report.throughput()
+        )?;
+        writeln!(ctx.stdout(), "Errors:     {}", report.errors)?;
+        for (status, count) in &report.statuses {
+            writeln!(ctx.stdout(), "Status {}: {}", status, count)?;
+        }
+        let (Some(min), Some(max)) = (report.latencies.first(), report.latencies.last()) else {
+            return Ok(());
+        };
+        writeln!(ctx.stdout(), "Latency:")?;
+        writeln!(ctx.stdout(), "  min  {:>10.2?}", min)?;
+        for p in [50.0, 90.0, 99.0] {
+            if let Some(latency) = report.percentile(p) {
+                writeln!(ctx.stdout(), "  p{:<3} {:>10.2?}", p, latency)?;
+            }
+        }
+        writeln!(ctx.stdout(), "  max  {:>10.2?}", max)?;
+        Ok(())
     }
 }
+
+impl CmdExector for HttpEchoOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let status = StatusCode::from_u16(self.status)?;
+        writeln!(
+            ctx.stdout(),
+            "Listening on http://localhost:{}, answering {}",
+            self.port,
+            status
+        )?;
+        let stdout = Arc::new(Mutex::new(ctx.stdout()));
+        let on_request = move |request: String| {
+            if let Ok(mut stdout) = stdout.lock() {
+                let _ = writeln!(stdout, "{}", request);
+            }
+        };
+        Ok(process_http_echo(self.port, status, on_request).await?)
+    }
+}
+
+fn parse_byte_rate(rate: &str) -> Result<ByteRate, anyhow::Error> {
+    Ok(rate.parse()?)
+}
+
+fn parse_glob(pattern: &str) -> Result<Pattern, glob::PatternError> {
+    Pattern::new(pattern)
+}
+
+pub(super) fn generate_token() -> anyhow::Result<Secret> {
+    Ok(process_genpass(TOKEN_LENGTH, &PasswordCharset::new(true, true, true, false))?.into())
+}
@@ -0,0 +1,56 @@
+use clap::Parser;
+
+use crate::{process_ip_interfaces, process_public_ip, CmdExector, CommandOutput, ExecContext};
+
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct IpOpts {
+    #[command(subcommand)]
+    pub cmd: Option<IpSubCommand>,
+    /// Also list loopback addresses
+    #[arg(short, long)]
+    pub all: bool,
+}
+
+#[derive(Debug, Parser)]
+pub enum IpSubCommand {
+    #[command(about = "Show the address this host has on the internet")]
+    Public(IpPublicOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct IpPublicOpts {
+    /// Service answering with the caller's IP as plain text
+    #[arg(long, default_value = "https://api.ipify.org")]
+    pub url: String,
+}
+
+impl CmdExector for IpOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        if let Some(IpSubCommand::Public(opts)) = &self.cmd {
+            return opts.execute(ctx).await;
+        }
+        let addrs: Vec<_> = process_ip_interfaces()?
+            .into_iter()
+            .filter(|addr| self.all || !addr.loopback)
+            .collect();
+        let width = addrs.iter().map(|a| a.name.len()).max().unwrap_or(0);
+        let lines: Vec<_> = addrs
+            .iter()
+            .map(|a| format!("{:<width$}  {}/{}", a.name, a.ip, a.prefix))
+            .collect();
+        CommandOutput::new(lines.join("\n"))
+            .field("interfaces", &addrs)
+            .write(ctx)
+    }
+}
+
+impl CmdExector for IpPublicOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let ip = process_public_ip(&self.url).await?;
+        CommandOutput::new(ip.to_string())
+            .field("ip", ip)
+            .field("version", if ip.is_ipv4() { 4 } else { 6 })
+            .write(ctx)
+    }
+}
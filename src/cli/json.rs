@@ -0,0 +1,68 @@
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+use serde_json::Value;
+
+use crate::{process_json_fmt, process_json_get, CmdExector, ExecContext, Input};
+
+use super::verify_file_exists;
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum JsonSubCommand {
+    #[command(about = "Pretty-print or minify JSON")]
+    Fmt(JsonFmtOpts),
+    #[command(about = "Extract values with a jq-like path, e.g. .items[0].name")]
+    Get(JsonGetOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct JsonFmtOpts {
+    #[arg(short, long, value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+    /// JSON to format, instead of reading -i
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+    #[arg(long)]
+    pub minify: bool,
+    #[arg(long, default_value_t = 2, conflicts_with = "minify")]
+    pub indent: usize,
+}
+
+#[derive(Debug, Parser)]
+pub struct JsonGetOpts {
+    #[arg(short, long, value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+    /// JSON to query, instead of reading -i
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+    #[arg(short, long)]
+    pub path: String,
+    /// Print strings without JSON quotes
+    #[arg(short, long)]
+    pub raw: bool,
+}
+
+impl CmdExector for JsonFmtOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        ctx.write_text(&process_json_fmt(input, self.minify, self.indent)?)
+    }
+}
+
+impl CmdExector for JsonGetOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        let values = process_json_get(input, &self.path)?;
+        if values.is_empty() {
+            return Err(anyhow::anyhow!("No value at path {}", self.path));
+        }
+        let lines = values
+            .into_iter()
+            .map(|value| match value {
+                Value::String(s) if self.raw => Ok(s),
+                value => serde_json::to_string_pretty(&value),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        ctx.write_text(&lines.join("\n"))
+    }
+}
@@ -1,9 +1,18 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
 use anyhow::Result;
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use clap::Parser;
+use colored::Colorize;
 use enum_dispatch::enum_dispatch;
 
-use crate::{process_jwt_sign, process_jwt_verify, CmdExector};
+use super::{verify_file_exists, verify_path};
+use crate::{
+    process_jwt_introspect, process_jwt_issue, process_jwt_refresh, process_jwt_report,
+    process_jwt_sign, process_jwt_verify, process_time_parse, process_uuid, CmdExector,
+    CommandOutput, ExecContext, JwtKeySet, JwtReport, RcliError, Secret, UuidVersion,
+    JWT_DEFAULT_LEEWAY,
+};
 
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExector)]
@@ -12,6 +21,18 @@ pub enum JwtSubCommand {
     Sign(JwtSignOpts),
     #[command(name = "verify", about = "verify jwt")]
     Verify(JwtVerifyOpts),
+    #[command(
+        name = "introspect",
+        about = "check a token against an OAuth2 introspection endpoint"
+    )]
+    Introspect(JwtIntrospectOpts),
+    #[command(name = "issue", about = "issue a linked access/refresh token pair")]
+    Issue(JwtIssueOpts),
+    #[command(
+        name = "refresh",
+        about = "mint a new access token from a refresh token"
+    )]
+    Refresh(JwtRefreshOpts),
 }
 
 #[derive(Debug, Parser)]
@@ -20,46 +41,359 @@ pub struct JwtSignOpts {
     pub sub: String,
     #[arg(short, long)]
     pub aud: String,
-    #[arg(short, long, value_parser = parse_duration)]
-    pub exp: Duration,
+    /// Lifetime, e.g. 45s, 15m, 1h30m, 2w3d or 6mo
+    #[arg(short, long, value_parser = parse_duration, required_unless_present = "exp_at")]
+    pub exp: Option<Duration>,
+    /// Absolute expiry instead of --exp, e.g. 2025-12-31T00:00:00Z
+    #[arg(long, value_parser = parse_exp_at, conflicts_with = "exp")]
+    pub exp_at: Option<DateTime<Utc>>,
+    /// Not valid before: a delay like 5m, or a timestamp
+    #[arg(long, value_parser = parse_not_before)]
+    pub nbf: Option<Duration>,
+    /// Token id; a random UUID when the flag has no value
+    #[arg(long)]
+    pub jti: Option<Option<String>>,
+}
+
+#[derive(Debug, Parser)]
+pub struct JwtIssueOpts {
+    #[arg(short, long)]
+    pub sub: String,
+    #[arg(short, long)]
+    pub aud: String,
+    #[arg(long, value_parser = parse_duration, default_value = "15m")]
+    pub access_exp: Duration,
+    #[arg(long, value_parser = parse_duration, default_value = "7d")]
+    pub refresh_exp: Duration,
+}
+
+#[derive(Debug, Parser)]
+pub struct JwtRefreshOpts {
+    /// Refresh token from `jwt issue`
+    #[arg(short, long)]
+    pub token: Secret,
+    #[arg(long, value_parser = parse_duration, default_value = "15m")]
+    pub access_exp: Duration,
+}
+
+/// Keys a token may be signed with; without any, the built-in signing secret is used
+#[derive(Debug, Parser)]
+pub struct JwtKeyOpts {
+    /// HMAC secret, repeat to accept tokens signed before a key rotation
+    #[arg(long)]
+    pub secret: Vec<Secret>,
+    /// RSA, EC or Ed25519 public key in PEM, its file stem is the kid
+    #[arg(long, value_parser = verify_file_exists)]
+    pub public_key: Vec<String>,
+    /// Directory of keys named <kid>.<ext>: PEM public keys or HMAC secrets
+    #[arg(long, value_parser = verify_path)]
+    pub keys_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Parser)]
 pub struct JwtVerifyOpts {
     #[arg(short, long)]
-    pub token: String,
+    pub token: Secret,
+    #[command(flatten)]
+    pub keys: JwtKeyOpts,
+    /// Seconds of clock skew tolerated on exp and nbf
+    #[arg(long, default_value_t = JWT_DEFAULT_LEEWAY)]
+    pub leeway: u64,
+    /// Print a full inspection report (human or json) instead of a bare verdict
+    #[arg(long, num_args = 0..=1, default_missing_value = "human", value_parser = parse_report_format)]
+    pub report: Option<ReportFormat>,
 }
 
-fn parse_duration(s: &str) -> Result<Duration> {
-    let len = s.len();
-    let (num_str, unit) = s.split_at(len - 1);
-    let num = num_str.parse::<i64>()?;
-
-    let duration = match unit {
-        "d" => Duration::days(num),
-        "w" => Duration::weeks(num),
-        "m" => Duration::minutes(num),
-        "h" => Duration::hours(num),
-        _ => {
-            return Err(anyhow::anyhow!("Invalid duration unit: {}", unit));
+#[derive(Debug, Parser)]
+pub struct JwtIntrospectOpts {
+    #[arg(short, long)]
+    pub token: Secret,
+    #[command(flatten)]
+    pub keys: JwtKeyOpts,
+    /// RFC 7662 token introspection URL
+    #[arg(long)]
+    pub endpoint: String,
+    #[arg(long)]
+    pub client_id: String,
+    #[arg(long)]
+    pub client_secret: Secret,
+    /// Seconds of clock skew tolerated on exp and nbf
+    #[arg(long, default_value_t = JWT_DEFAULT_LEEWAY)]
+    pub leeway: u64,
+    #[arg(long, default_value = "human", value_parser = parse_report_format)]
+    pub report: ReportFormat,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReportFormat {
+    Human,
+    Json,
+}
+
+fn parse_report_format(format: &str) -> Result<ReportFormat> {
+    format.parse()
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ReportFormat::Human),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(anyhow::anyhow!("Invalid format: {}", s)),
         }
-    };
+    }
+}
+
+impl From<ReportFormat> for &'static str {
+    fn from(format: ReportFormat) -> Self {
+        match format {
+            ReportFormat::Human => "human",
+            ReportFormat::Json => "json",
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
 
+/// A duration made of number-unit pairs, e.g. `45s`, `1h30m` or `2w3d`. Units are s, m
+/// (minutes), h, d, w and mo (30-day months); `M` is refused rather than guessed.
+fn parse_duration(s: &str) -> Result<Duration> {
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        anyhow::bail!("Duration can't be empty");
+    }
+    let mut units = Vec::new();
+    let mut duration = Duration::zero();
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            anyhow::bail!("Invalid duration {}: expected a number before {}", s, rest);
+        }
+        let num = rest[..digits].parse::<i64>()?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = &rest[..unit_len];
+        rest = &rest[unit_len..];
+        let part = match unit {
+            "s" => Duration::try_seconds(num),
+            "m" => Duration::try_minutes(num),
+            "h" => Duration::try_hours(num),
+            "d" => Duration::try_days(num),
+            "w" => Duration::try_weeks(num),
+            "mo" => num.checked_mul(30).and_then(Duration::try_days),
+            "M" => anyhow::bail!(
+                "Ambiguous duration unit M in {}: use m for minutes or mo for months",
+                s
+            ),
+            "" => anyhow::bail!("Missing unit after {} in {}, e.g. {}m", num, s, num),
+            _ => anyhow::bail!("Invalid duration unit {} in {}", unit, s),
+        };
+        if units.contains(&unit) {
+            anyhow::bail!("Duration unit {} is repeated in {}", unit, s);
+        }
+        units.push(unit);
+        duration = part
+            .and_then(|part| duration.checked_add(&part))
+            .ok_or_else(|| anyhow::anyhow!("Duration {} is out of range", s))?;
+    }
     Ok(duration)
 }
 
+fn parse_exp_at(s: &str) -> Result<DateTime<Utc>> {
+    Ok(process_time_parse(s)?)
+}
+
+/// A delay from now, or an absolute time turned into one
+fn parse_not_before(s: &str) -> Result<Duration> {
+    match parse_duration(s) {
+        Ok(delay) => Ok(delay),
+        Err(e) => match process_time_parse(s) {
+            Ok(time) => Ok(time - Utc::now()),
+            Err(_) => Err(e),
+        },
+    }
+}
+
 impl CmdExector for JwtSignOpts {
-    async fn execute(&self) -> anyhow::Result<()> {
-        let token = process_jwt_sign(&self.sub, &self.aud, self.exp)?;
-        println!("{}", token);
-        Ok(())
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let exp = match (self.exp, self.exp_at) {
+            (Some(exp), _) => exp,
+            // clap requires one of the two
+            (None, exp_at) => exp_at.unwrap_or_default() - Utc::now(),
+        };
+        let jti = match &self.jti {
+            Some(Some(jti)) => Some(jti.clone()),
+            Some(None) => Some(process_uuid(UuidVersion::V4, 1)[0].to_string()),
+            None => None,
+        };
+        let token = process_jwt_sign(&self.sub, &self.aud, exp, self.nbf, jti.as_deref())?;
+        CommandOutput::new(&token).field("token", &token).write(ctx)
+    }
+}
+
+impl CmdExector for JwtIssueOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let tokens = process_jwt_issue(&self.sub, &self.aud, self.access_exp, self.refresh_exp)?;
+        ctx.write_text(&serde_json::to_string_pretty(&tokens)?)
+    }
+}
+
+impl CmdExector for JwtRefreshOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let tokens = process_jwt_refresh(self.token.expose_str(), self.access_exp)?;
+        ctx.write_text(&serde_json::to_string_pretty(&tokens)?)
+    }
+}
+
+impl JwtKeyOpts {
+    fn load(&self) -> Result<JwtKeySet> {
+        let public_keys: Vec<PathBuf> = self.public_key.iter().map(PathBuf::from).collect();
+        Ok(JwtKeySet::load(
+            &self.secret,
+            &public_keys,
+            self.keys_dir.as_deref(),
+        )?)
     }
 }
 
 impl CmdExector for JwtVerifyOpts {
-    async fn execute(&self) -> anyhow::Result<()> {
-        let verified = process_jwt_verify(&self.token)?;
-        println!("{:?}", verified);
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let keys = self.keys.load()?;
+        let token = self.token.expose_str();
+        if let Some(format) = self.report {
+            return write_report(ctx, &process_jwt_report(token, &keys, self.leeway)?, format);
+        }
+        let (valid, reason) = match process_jwt_verify(token, &keys, self.leeway) {
+            Ok(valid) => (valid, None),
+            Err(e) if e.is_verification_failure() => (false, Some(e.to_string())),
+            Err(e) => return Err(e.into()),
+        };
+        if let (Some(reason), false) = (&reason, ctx.json) {
+            writeln!(ctx.stderr(), "{}", reason)?;
+        }
+        let claims = process_jwt_report(token, &keys, self.leeway)
+            .ok()
+            .map(|r| r.claims);
+        CommandOutput::verification(valid, reason)
+            .field("claims", claims)
+            .write(ctx)
+    }
+}
+
+impl CmdExector for JwtIntrospectOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let report = process_jwt_introspect(
+            self.token.expose_str(),
+            &self.keys.load()?,
+            &self.endpoint,
+            &self.client_id,
+            self.client_secret.expose_str(),
+            self.leeway,
+        )
+        .await?;
+        write_report(ctx, &report, self.report)
+    }
+}
+
+/// Print the report unless `--quiet`, failing with `VerificationFailed` when the token
+/// isn't valid so the process exits with 1
+fn write_report(ctx: &ExecContext, report: &JwtReport, format: ReportFormat) -> anyhow::Result<()> {
+    match (ctx.quiet, format) {
+        (true, _) => {}
+        (false, ReportFormat::Json) => ctx.write_text(&serde_json::to_string_pretty(report)?)?,
+        (false, ReportFormat::Human) => print_report(ctx, report)?,
+    }
+    if !report.is_valid() {
+        return Err(RcliError::VerificationFailed.into());
+    }
+    Ok(())
+}
+
+fn print_report(ctx: &ExecContext, report: &JwtReport) -> Result<()> {
+    let mut out = ctx.stdout();
+    writeln!(out, "{}", "Header".bold())?;
+    writeln!(out, "{}", serde_json::to_string_pretty(&report.header)?)?;
+    writeln!(out, "{}", "Claims".bold())?;
+    writeln!(out, "{}", serde_json::to_string_pretty(&report.claims)?)?;
+    if let Some(introspection) = &report.introspection {
+        writeln!(out, "{}", "Introspection".bold())?;
+        writeln!(out, "{}", serde_json::to_string_pretty(introspection)?)?;
+    }
+    writeln!(out, "{}", "Checks".bold())?;
+    for check in &report.checks {
+        let status = if check.passed {
+            "PASS".green()
+        } else {
+            "FAIL".red()
+        };
+        writeln!(out, "  [{}] {:<10} {}", status, check.name, check.detail)?;
+    }
+    match report.expires_in {
+        Some(secs) if secs >= 0 => writeln!(out, "Expires in: {}", format_secs(secs))?,
+        Some(secs) => writeln!(out, "Expired: {} ago", format_secs(-secs).red())?,
+        None => writeln!(out, "Expires in: {}", "never".yellow())?,
+    }
+    for warning in &report.warnings {
+        writeln!(out, "{} {}", "warning:".yellow().bold(), warning)?;
+    }
+    let verdict = if report.is_valid() {
+        "VALID".green().bold()
+    } else {
+        "INVALID".red().bold()
+    };
+    writeln!(out, "Verdict: {}", verdict)?;
+    Ok(())
+}
+
+fn format_secs(secs: i64) -> String {
+    let (d, h, m, s) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+    match (d, h, m) {
+        (0, 0, 0) => format!("{}s", s),
+        (0, 0, _) => format!("{}m {}s", m, s),
+        (0, _, _) => format!("{}h {}m {}s", h, m, s),
+        _ => format!("{}d {}h {}m", d, h, m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() -> Result<()> {
+        assert_eq!(parse_duration("45s")?, Duration::seconds(45));
+        assert_eq!(parse_duration("15m")?, Duration::minutes(15));
+        assert_eq!(parse_duration("1h30m")?, Duration::minutes(90));
+        assert_eq!(parse_duration("2w3d")?, Duration::days(17));
+        assert_eq!(parse_duration("6mo")?, Duration::days(180));
+        for invalid in [
+            "",
+            "30",
+            "h",
+            "1M",
+            "1y",
+            "1h2h",
+            "-5m",
+            "1.5h",
+            "99999999999999w",
+        ] {
+            assert!(parse_duration(invalid).is_err(), "{} parsed", invalid);
+        }
         Ok(())
     }
 }
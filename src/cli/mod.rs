@@ -1,25 +1,112 @@
+mod api;
+mod archive;
 mod base64;
+mod cert;
+mod chain;
+mod compress;
+mod config;
 mod csv;
+mod dns;
 mod genpass;
-use std::path::{Path, PathBuf};
+mod hash;
+mod hex;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
 mod http;
+mod ip;
+mod json;
 mod jwt;
+mod otp;
+mod qrcode;
+mod rand;
+mod template;
 mod text;
+mod time;
+mod url;
+mod uuid;
+mod vault;
 
+pub use self::url::*;
+pub use self::uuid::*;
+use crate::{process_watch, ExecContext, RngSeed};
+pub use api::*;
+pub use archive::*;
 pub use base64::*;
+pub use cert::*;
+pub use chain::*;
 use clap::Parser;
+pub use compress::*;
 pub use csv::*;
+pub use dns::*;
 use enum_dispatch::enum_dispatch;
 pub use genpass::*;
+pub use hash::*;
+pub use hex::*;
 pub use http::*;
+pub use ip::*;
+pub use json::*;
 pub use jwt::*;
+pub use otp::*;
+pub use qrcode::*;
+pub use rand::*;
+pub use template::*;
 pub use text::*;
+pub use time::*;
+pub use vault::*;
 
 #[derive(Debug, Parser)]
 #[command(name = "rcli", version, about, author, long_about=None)]
 pub struct Opts {
     #[command(subcommand)]
     pub cmd: SubCommand,
+
+    /// Make random output deterministic, for reproducible tests and docs (hex, up to 256 bits)
+    // `text generate` and `genpass` show it as a visible --seed of the same id
+    #[arg(long, global = true, hide = true, env = "RCLI_SEED", value_parser = parse_seed)]
+    pub seed: Option<RngSeed>,
+
+    /// Also apply --seed to keys, passwords and nonces, making them predictable
+    #[arg(long, global = true, hide = true, requires = "seed")]
+    pub insecure_seed: bool,
+
+    /// Write the result to a file, `clipboard`, or `-` for stdout. Commands that already
+    /// take their own -o (csv, text keygen, streaming encrypt/decrypt) keep its meaning
+    #[arg(short, long, global = true)]
+    pub output: Option<String>,
+
+    /// Print the result as a JSON object, for scripts
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Print nothing on stdout and no logs; verifications report through the exit code
+    /// alone: 0 when valid, 1 when not, 2 on errors
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Log more on stderr: -v info and step timings, -vv which files and keys are
+    /// read, -vvv everything. Without it RUST_LOG applies, errors only by default
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Config file with per-subcommand defaults, ~/.config/rcli/config.toml if present
+    #[arg(long, global = true, env = "RCLI_CONFIG")]
+    pub config: Option<std::path::PathBuf>,
+}
+
+impl Opts {
+    /// The tracing filter `-v`/`-q` ask for, `None` to leave it to RUST_LOG
+    pub fn log_filter(&self) -> Option<&'static str> {
+        match (self.quiet, self.verbose) {
+            (true, _) => Some("off"),
+            (false, 0) => None,
+            (false, 1) => Some("info"),
+            // only rcli itself gets chatty, dependencies stay at info
+            (false, 2) => Some("info,rcli=debug"),
+            (false, _) => Some("info,rcli=trace"),
+        }
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -37,6 +124,83 @@ pub enum SubCommand {
     Http(HttpSubCommand),
     #[command(subcommand)]
     Jwt(JwtSubCommand),
+    #[command(subcommand, about = "Format JSON or extract values from it")]
+    Json(JsonSubCommand),
+    #[command(
+        subcommand,
+        about = "Dump binary files as hex and rebuild them from dumps"
+    )]
+    Hex(HexSubCommand),
+    #[command(name = "hash", about = "Compute or verify file digests")]
+    Hash(HashOpts),
+    #[command(
+        subcommand,
+        visible_alias = "kv",
+        about = "Store secrets in an encrypted vault file"
+    )]
+    Vault(VaultSubCommand),
+    #[command(subcommand, about = "Convert and format timestamps")]
+    Time(TimeSubCommand),
+    #[command(name = "uuid", about = "Generate or decode UUIDs")]
+    Uuid(UuidOpts),
+    #[command(
+        name = "serve-api",
+        about = "Answer JSON-RPC requests over stdio or TCP, for tools embedding rcli"
+    )]
+    ServeApi(ServeApiOpts),
+    #[command(
+        name = "chain",
+        about = "Pipe the output of rcli commands into each other"
+    )]
+    Chain(ChainOpts),
+    #[command(
+        name = "qrcode",
+        about = "Render text as a QR code for the terminal, PNG or SVG"
+    )]
+    QrCode(QrCodeOpts),
+    #[command(
+        subcommand,
+        about = "Generate TOTP/HOTP codes, secrets and provisioning URIs"
+    )]
+    Otp(OtpSubCommand),
+    #[command(subcommand, about = "Query DNS records")]
+    Dns(DnsSubCommand),
+    #[command(
+        name = "ip",
+        about = "List local interface addresses or show the public IP"
+    )]
+    Ip(IpOpts),
+    #[command(
+        subcommand,
+        about = "Generate self-signed certificates and inspect certificates"
+    )]
+    Cert(CertSubCommand),
+    #[command(
+        subcommand,
+        about = "Random bytes, integers and tokens, e.g. for salts and API keys"
+    )]
+    Rand(RandSubCommand),
+    #[command(
+        name = "template",
+        about = "Render a Tera template with JSON data, or once per CSV row"
+    )]
+    Template(TemplateOpts),
+    #[command(
+        subcommand,
+        about = "Percent-encode and decode text, and split URLs into their parts"
+    )]
+    Url(UrlSubCommand),
+    #[command(subcommand, about = "Create and extract tar, tar.gz and zip archives")]
+    Archive(ArchiveSubCommand),
+    #[command(
+        name = "compress",
+        about = "Compress or decompress a stream with gzip, zstd or brotli"
+    )]
+    Compress(CompressOpts),
+}
+
+fn parse_seed(seed: &str) -> Result<RngSeed, anyhow::Error> {
+    Ok(seed.parse()?)
 }
 
 fn verify_file_exists(filename: &str) -> Result<String, String> {
@@ -46,6 +210,60 @@ fn verify_file_exists(filename: &str) -> Result<String, String> {
         Err(format!("File not found: {}", filename))
     }
 }
+
+/// A file, or a glob pattern such as `logs/*.csv` expanded by the command itself
+fn verify_file_or_glob(input: &str) -> Result<String, String> {
+    if input.contains(['*', '?', '[']) {
+        Ok(input.to_string())
+    } else {
+        verify_file_exists(input)
+    }
+}
+
+/// Key files, `-` for stdin or `env:NAME` for an environment variable
+fn verify_key_file(key: &str) -> Result<String, String> {
+    match key.strip_prefix("env:") {
+        Some(name) if std::env::var_os(name).is_none() => {
+            Err(format!("Environment variable not set: {}", name))
+        }
+        Some(_) => Ok(key.to_string()),
+        None => verify_file_exists(key),
+    }
+}
+
+/// Key files, stdin or env vars as for `verify_key_file`, or https URLs keys are fetched from
+fn verify_key_source(key: &str) -> Result<String, String> {
+    if key.starts_with("https://") {
+        Ok(key.to_string())
+    } else {
+        verify_key_file(key)
+    }
+}
+
+/// Re-run `step` on changes to `paths`, reporting failed runs on stderr and carrying on
+fn watch(
+    ctx: &ExecContext,
+    paths: &[&Path],
+    mut step: impl FnMut() -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    if !ctx.quiet {
+        let names = paths
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>();
+        writeln!(
+            ctx.stderr(),
+            "Watching {} for changes, Ctrl-C to stop",
+            names.join(", ")
+        )?;
+    }
+    Ok(process_watch(paths, || {
+        if let Err(e) = step() {
+            let _ = writeln!(ctx.stderr(), "Error: {:#}", e);
+        }
+    })?)
+}
+
 fn verify_path(path: &str) -> Result<PathBuf, String> {
     let p = Path::new(path);
     if p.exists() && p.is_dir() {
@@ -58,6 +276,132 @@ fn verify_path(path: &str) -> Result<PathBuf, String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::CmdExector;
+
+    #[test]
+    fn test_cli_definition() {
+        use clap::CommandFactory;
+        Opts::command().debug_assert();
+        // commands with their own -o must accept it with the same type as the global one
+        for args in [
+            &["rcli", "csv", "-i", "Cargo.toml", "-o", "out.json"][..],
+            &["rcli", "text", "generate", "-o", "keys"],
+            &["rcli", "genpass", "-o", "clipboard"],
+            &["rcli", "archive", "create", "-i", "src", "-o", "src.zip"],
+        ] {
+            assert!(Opts::try_parse_from(args).is_ok(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let filter = |args: &[&str]| Opts::try_parse_from(args).unwrap().log_filter();
+        assert_eq!(filter(&["rcli", "uuid"]), None);
+        assert_eq!(filter(&["rcli", "-v", "uuid"]), Some("info"));
+        assert_eq!(filter(&["rcli", "uuid", "-vv"]), Some("info,rcli=debug"));
+        assert_eq!(filter(&["rcli", "-q", "uuid"]), Some("off"));
+        assert!(Opts::try_parse_from(["rcli", "-q", "-v", "uuid"]).is_err());
+    }
+
+    /// Run a command line against a captured context
+    async fn run(args: &[&str]) -> (ExecContext, anyhow::Result<()>) {
+        let opts = Opts::try_parse_from(args).unwrap();
+        let mut ctx = ExecContext::new(Default::default(), opts.json, opts.quiet).captured();
+        let result = opts.cmd.execute(&mut ctx).await;
+        (ctx, result)
+    }
+
+    #[tokio::test]
+    async fn test_executors_capture_output() -> anyhow::Result<()> {
+        let input = std::env::temp_dir().join("rcli-exec-input.txt");
+        std::fs::write(&input, "hello world\n")?;
+        let input = input.to_string_lossy();
+
+        let (ctx, result) = run(&["rcli", "base64", "encode", "-i", &input]).await;
+        result?;
+        assert_eq!(ctx.stdout_text(), "aGVsbG8gd29ybGQK\n");
+
+        let (ctx, result) = run(&["rcli", "--json", "url", "encode", "-i", &input]).await;
+        result?;
+        assert_eq!(
+            ctx.stdout_text(),
+            "{\n  \"encoded\": \"hello%20world\"\n}\n"
+        );
+
+        let (ctx, result) = run(&["rcli", "base64", "inspect", "-i", &input]).await;
+        result?;
+        assert!(ctx.stdout_text().starts_with("alphabet:"));
+        assert_eq!(ctx.stderr_text(), "");
+
+        let (ctx, result) = run(&["rcli", "-q", "url", "encode", "-i", &input]).await;
+        result?;
+        assert_eq!(ctx.stdout_text(), "");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_text_input() -> anyhow::Result<()> {
+        let (ctx, result) = run(&["rcli", "base64", "encode", "--text", "hello world\n"]).await;
+        result?;
+        assert_eq!(ctx.stdout_text(), "aGVsbG8gd29ybGQK\n");
+
+        let (ctx, result) = run(&["rcli", "hash", "--text", "hello"]).await;
+        result?;
+        assert_eq!(
+            ctx.stdout_text(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  -\n"
+        );
+
+        let (ctx, result) = run(&[
+            "rcli",
+            "json",
+            "get",
+            "--text",
+            r#"{"a":[1]}"#,
+            "-p",
+            ".a[0]",
+        ])
+        .await;
+        result?;
+        assert_eq!(ctx.stdout_text(), "1\n");
+
+        let (ctx, result) = run(&["rcli", "chain", "--text", "hi", "base64 encode | hash"]).await;
+        result?;
+        assert_eq!(ctx.stdout_text().len(), 65);
+
+        assert!(
+            Opts::try_parse_from(["rcli", "url", "encode", "-i", "Cargo.toml", "--text", "x"])
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_kv_roundtrip() -> anyhow::Result<()> {
+        std::env::set_var("RCLI_VAULT_PASSWORD", "master");
+        let file = std::env::temp_dir().join("rcli-kv.vault");
+        let _ = std::fs::remove_file(&file);
+        let file = file.to_string_lossy();
+
+        let (_, result) = run(&[
+            "rcli", "kv", "set", "github", "--secret", "s3cret", "-f", &file,
+        ])
+        .await;
+        result?;
+        let (ctx, result) = run(&["rcli", "kv", "get", "github", "-f", &file]).await;
+        result?;
+        assert_eq!(ctx.stdout_text(), "s3cret\n");
+        let (ctx, result) = run(&["rcli", "kv", "list", "-f", &file]).await;
+        result?;
+        assert!(ctx.stdout_text().starts_with("github\t"));
+
+        let (_, result) = run(&["rcli", "kv", "del", "github", "-f", &file]).await;
+        result?;
+        let (_, result) = run(&["rcli", "kv", "get", "github", "-f", &file]).await;
+        assert!(result.is_err());
+        std::fs::remove_file(&*file)?;
+        Ok(())
+    }
 
     #[test]
     fn test_verify_file_exists() {
@@ -0,0 +1,206 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::Context;
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+use zeroize::Zeroizing;
+
+use super::{qrcode::write_qrcode, verify_key_file};
+use crate::{
+    decode_otp_secret, process_hotp, process_otp_new, process_otp_uri, process_totp_now, read_key,
+    CmdExector, CommandOutput, ExecContext, OtpParams,
+};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum OtpSubCommand {
+    #[command(about = "Create a new base32 secret, with its provisioning URI for --account")]
+    New(OtpNewOpts),
+    #[command(about = "Print the current TOTP code, or the HOTP code for --counter")]
+    Code(OtpCodeOpts),
+    #[command(about = "Print the otpauth:// URI authenticator apps scan")]
+    Uri(OtpUriOpts),
+}
+
+/// How codes are derived; keep the defaults unless the service asks otherwise
+#[derive(Debug, Parser)]
+pub struct OtpParamsOpts {
+    #[arg(long, value_parser = parse_otp_algorithm, default_value = "sha1")]
+    pub algorithm: OtpAlgorithm,
+    #[arg(long, default_value_t = 6)]
+    pub digits: u32,
+    /// TOTP time step in seconds
+    #[arg(long, default_value_t = 30)]
+    pub period: u64,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpNewOpts {
+    /// Secret length in bytes
+    #[arg(long, default_value_t = 20)]
+    pub bytes: usize,
+    /// Account name shown in the authenticator app, e.g. alice@example.com
+    #[arg(long)]
+    pub account: Option<String>,
+    #[arg(long, requires = "account")]
+    pub issuer: Option<String>,
+    /// Show the provisioning URI as a QR code
+    #[arg(long, requires = "account")]
+    pub qr: bool,
+    #[command(flatten)]
+    pub params: OtpParamsOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpCodeOpts {
+    /// File, `-` or `env:NAME` holding the base32 secret, spaces and lowercase are fine
+    #[arg(short, long, value_parser = verify_key_file)]
+    pub secret: String,
+    /// Generate an HOTP code for this counter instead of a TOTP code
+    #[arg(long)]
+    pub counter: Option<u64>,
+    #[command(flatten)]
+    pub params: OtpParamsOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpUriOpts {
+    /// File, `-` or `env:NAME` holding the base32 secret, spaces and lowercase are fine
+    #[arg(short, long, value_parser = verify_key_file)]
+    pub secret: String,
+    /// Account name shown in the authenticator app, e.g. alice@example.com
+    #[arg(long)]
+    pub account: String,
+    #[arg(long)]
+    pub issuer: Option<String>,
+    /// Make an HOTP URI starting at this counter instead of a TOTP one
+    #[arg(long)]
+    pub counter: Option<u64>,
+    /// Show the URI as a QR code, written as an image when -o is a .png or .svg file
+    #[arg(long)]
+    pub qr: bool,
+    #[command(flatten)]
+    pub params: OtpParamsOpts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+fn parse_otp_algorithm(algorithm: &str) -> Result<OtpAlgorithm, anyhow::Error> {
+    algorithm.parse()
+}
+
+impl FromStr for OtpAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sha1" => Ok(OtpAlgorithm::Sha1),
+            "sha256" => Ok(OtpAlgorithm::Sha256),
+            "sha512" => Ok(OtpAlgorithm::Sha512),
+            _ => Err(anyhow::anyhow!("Invalid algorithm: {}", s)),
+        }
+    }
+}
+
+impl From<OtpAlgorithm> for &'static str {
+    fn from(algorithm: OtpAlgorithm) -> Self {
+        match algorithm {
+            OtpAlgorithm::Sha1 => "sha1",
+            OtpAlgorithm::Sha256 => "sha256",
+            OtpAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+impl Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<&OtpParamsOpts> for OtpParams {
+    fn from(opts: &OtpParamsOpts) -> Self {
+        Self {
+            algorithm: opts.algorithm,
+            digits: opts.digits,
+            period: opts.period,
+        }
+    }
+}
+
+/// The base32 secret from a file, stdin or an environment variable, never argv where it
+/// would show up in `ps` and shell history
+fn read_otp_secret(source: &str) -> anyhow::Result<Zeroizing<String>> {
+    let secret = read_key(source)?;
+    let secret = std::str::from_utf8(&secret).context("OTP secret is not valid UTF-8")?;
+    Ok(Zeroizing::new(secret.trim().to_string()))
+}
+
+impl CmdExector for OtpNewOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let secret = process_otp_new(self.bytes)?;
+        let Some(account) = &self.account else {
+            return ctx.write_text(&secret);
+        };
+        let uri = process_otp_uri(
+            &secret,
+            account,
+            self.issuer.as_deref(),
+            None,
+            (&self.params).into(),
+        )?;
+        if self.qr {
+            writeln!(ctx.stderr(), "Secret: {}", secret)?;
+            return write_qrcode(ctx, uri.as_bytes(), None, 8);
+        }
+        ctx.write_text(&format!("{}\n{}", secret, uri))
+    }
+}
+
+impl CmdExector for OtpCodeOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let key = Zeroizing::new(decode_otp_secret(&read_otp_secret(&self.secret)?)?);
+        let params = (&self.params).into();
+        match self.counter {
+            Some(counter) => {
+                let code = process_hotp(&key, counter, params)?;
+                CommandOutput::new(&code)
+                    .field("code", &code)
+                    .field("counter", counter)
+                    .write(ctx)
+            }
+            None => {
+                let (code, remaining) = process_totp_now(&key, params)?;
+                CommandOutput::new(&code)
+                    .field("code", &code)
+                    .field("valid_for", remaining)
+                    .write(ctx)?;
+                if !ctx.json {
+                    writeln!(ctx.stderr(), "Valid for {}s", remaining)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl CmdExector for OtpUriOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let uri = process_otp_uri(
+            &read_otp_secret(&self.secret)?,
+            &self.account,
+            self.issuer.as_deref(),
+            self.counter,
+            (&self.params).into(),
+        )?;
+        match self.qr {
+            true => write_qrcode(ctx, uri.as_bytes(), None, 8),
+            false => ctx.write_text(&uri),
+        }
+    }
+}
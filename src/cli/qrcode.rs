@@ -0,0 +1,106 @@
+use std::{fmt::Display, io::Read, str::FromStr};
+
+use clap::Parser;
+
+use crate::{get_reader, process_qrcode, CmdExector, ExecContext, OutputTarget};
+
+use super::verify_file_exists;
+
+#[derive(Debug, Parser)]
+pub struct QrCodeOpts {
+    /// File to encode, or `-` for stdin (e.g. `rcli genpass | rcli qrcode`)
+    #[arg(short, long, value_parser = verify_file_exists, default_value = "-", conflicts_with = "text")]
+    pub input: String,
+    /// Encode this text instead of reading a file
+    #[arg(long)]
+    pub text: Option<String>,
+    /// terminal, png or svg; defaults to the extension of -o, the terminal otherwise
+    #[arg(long, value_parser = parse_qr_format)]
+    pub format: Option<QrFormat>,
+    /// Pixels per module in PNG output
+    #[arg(long, default_value_t = 8)]
+    pub scale: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QrFormat {
+    Terminal,
+    Png,
+    Svg,
+}
+
+fn parse_qr_format(format: &str) -> Result<QrFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for QrFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "terminal" => Ok(QrFormat::Terminal),
+            "png" => Ok(QrFormat::Png),
+            "svg" => Ok(QrFormat::Svg),
+            _ => Err(anyhow::anyhow!("Invalid format: {}", s)),
+        }
+    }
+}
+
+impl From<QrFormat> for &'static str {
+    fn from(format: QrFormat) -> Self {
+        match format {
+            QrFormat::Terminal => "terminal",
+            QrFormat::Png => "png",
+            QrFormat::Svg => "svg",
+        }
+    }
+}
+
+impl Display for QrFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for QrCodeOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let data = match &self.text {
+            Some(text) => text.clone().into_bytes(),
+            None => {
+                let mut data = Vec::new();
+                get_reader(&self.input)?.read_to_end(&mut data)?;
+                // piped commands end their output with a newline that isn't part of the value
+                if data.ends_with(b"\n") {
+                    data.pop();
+                    if data.ends_with(b"\r") {
+                        data.pop();
+                    }
+                }
+                data
+            }
+        };
+        write_qrcode(ctx, &data, self.format, self.scale)
+    }
+}
+
+/// Render `data` to the output, as an image when -o names a .png or .svg file
+pub(super) fn write_qrcode(
+    ctx: &ExecContext,
+    data: &[u8],
+    format: Option<QrFormat>,
+    scale: u32,
+) -> anyhow::Result<()> {
+    let format = format.unwrap_or(match &ctx.output {
+        OutputTarget::File(path) => match path.extension().and_then(|ext| ext.to_str()) {
+            Some("png") => QrFormat::Png,
+            Some("svg") => QrFormat::Svg,
+            _ => QrFormat::Terminal,
+        },
+        _ => QrFormat::Terminal,
+    });
+    let rendered = process_qrcode(data, format, scale)?;
+    match format {
+        QrFormat::Png => ctx.write_bytes(&rendered),
+        _ => ctx.write_text(&String::from_utf8(rendered)?),
+    }
+}
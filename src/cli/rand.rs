@@ -0,0 +1,127 @@
+use std::{fmt::Display, str::FromStr};
+
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{
+    encode_rand_bytes, process_rand_bytes, process_rand_int, process_rand_token, CmdExector,
+    CommandOutput, ExecContext,
+};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum RandSubCommand {
+    #[command(about = "Random bytes as hex, base64 or raw, e.g. for salts and nonces")]
+    Bytes(RandBytesOpts),
+    #[command(about = "Random integers from a range")]
+    Int(RandIntOpts),
+    #[command(about = "A random url-safe token, e.g. for API keys")]
+    Token(RandTokenOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct RandBytesOpts {
+    #[arg(short, long, default_value_t = 32)]
+    pub len: usize,
+    /// hex, base64, base64url or raw; raw needs -o or a pipe
+    #[arg(long, default_value = "hex", value_parser = parse_rand_format)]
+    pub format: RandFormat,
+}
+
+#[derive(Debug, Parser)]
+pub struct RandIntOpts {
+    #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+    pub min: i64,
+    /// Largest value, included in the range
+    #[arg(long, default_value_t = 100, allow_negative_numbers = true)]
+    pub max: i64,
+    #[arg(short = 'n', long, default_value_t = 1)]
+    pub count: usize,
+}
+
+#[derive(Debug, Parser)]
+pub struct RandTokenOpts {
+    /// Random bytes in the token; 32 make 43 characters
+    #[arg(short, long, default_value_t = 32, value_parser = clap::value_parser!(u16).range(1..))]
+    pub len: u16,
+    /// Put in front of the token, e.g. sk_live_
+    #[arg(long, default_value = "")]
+    pub prefix: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RandFormat {
+    Hex,
+    Base64,
+    Base64Url,
+    Raw,
+}
+
+fn parse_rand_format(format: &str) -> Result<RandFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for RandFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(RandFormat::Hex),
+            "base64" => Ok(RandFormat::Base64),
+            "base64url" => Ok(RandFormat::Base64Url),
+            "raw" => Ok(RandFormat::Raw),
+            _ => Err(anyhow::anyhow!("Invalid format: {}", s)),
+        }
+    }
+}
+
+impl From<RandFormat> for &'static str {
+    fn from(format: RandFormat) -> Self {
+        match format {
+            RandFormat::Hex => "hex",
+            RandFormat::Base64 => "base64",
+            RandFormat::Base64Url => "base64url",
+            RandFormat::Raw => "raw",
+        }
+    }
+}
+
+impl Display for RandFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for RandBytesOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let bytes = process_rand_bytes(self.len)?;
+        let format = match self.format {
+            RandFormat::Raw if !ctx.json => return ctx.write_bytes(&bytes),
+            // JSON can't hold the raw bytes
+            RandFormat::Raw => RandFormat::Base64,
+            format => format,
+        };
+        let text = String::from_utf8(encode_rand_bytes(&bytes, format))?;
+        CommandOutput::new(&text)
+            .field("bytes", &text)
+            .field("format", format.to_string())
+            .write(ctx)
+    }
+}
+
+impl CmdExector for RandIntOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let ints = process_rand_int(self.min, self.max, self.count)?;
+        let lines: Vec<_> = ints.iter().map(i64::to_string).collect();
+        CommandOutput::new(lines.join("\n"))
+            .field("ints", &ints)
+            .write(ctx)
+    }
+}
+
+impl CmdExector for RandTokenOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let token = process_rand_token(self.len as usize, &self.prefix)?;
+        CommandOutput::new(&token).field("token", &token).write(ctx)
+    }
+}
@@ -0,0 +1,111 @@
+use std::{fmt::Display, fs, path::Path, str::FromStr};
+
+use clap::Parser;
+
+use crate::{process_template, CmdExector, CommandOutput, ExecContext};
+
+use super::verify_file_exists;
+
+#[derive(Debug, Parser)]
+pub struct TemplateOpts {
+    /// Tera template file, https://keats.github.io/tera/docs/
+    #[arg(short, long, value_parser = verify_file_exists)]
+    pub template: String,
+    /// JSON data, or CSV to render the template once per row; `-` reads stdin
+    #[arg(short, long, value_parser = verify_file_exists)]
+    pub data: Option<String>,
+    /// json, csv or tsv; guessed from the data file's extension when omitted
+    #[arg(long, value_parser = parse_data_format)]
+    pub format: Option<TemplateDataFormat>,
+    /// Write each rendering to its own file, named by this template, e.g. "out/{{ id }}.html"
+    #[arg(long)]
+    pub each: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemplateDataFormat {
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl TemplateDataFormat {
+    /// The format a data file's extension suggests, JSON for anything else
+    fn guess(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => TemplateDataFormat::Csv,
+            Some("tsv") => TemplateDataFormat::Tsv,
+            _ => TemplateDataFormat::Json,
+        }
+    }
+}
+
+fn parse_data_format(format: &str) -> Result<TemplateDataFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for TemplateDataFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(TemplateDataFormat::Json),
+            "csv" => Ok(TemplateDataFormat::Csv),
+            "tsv" => Ok(TemplateDataFormat::Tsv),
+            _ => Err(anyhow::anyhow!("Invalid data format: {}", s)),
+        }
+    }
+}
+
+impl From<TemplateDataFormat> for &'static str {
+    fn from(format: TemplateDataFormat) -> Self {
+        match format {
+            TemplateDataFormat::Json => "json",
+            TemplateDataFormat::Csv => "csv",
+            TemplateDataFormat::Tsv => "tsv",
+        }
+    }
+}
+
+impl Display for TemplateDataFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for TemplateOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        if self.template == "-" && self.data.as_deref() == Some("-") {
+            anyhow::bail!("Only one of --template and --data can be read from stdin");
+        }
+        let format = self.format.unwrap_or_else(|| {
+            self.data
+                .as_deref()
+                .map_or(TemplateDataFormat::Json, TemplateDataFormat::guess)
+        });
+        let rendered = process_template(
+            &self.template,
+            self.data.as_deref(),
+            format,
+            self.each.as_deref(),
+        )?;
+        if self.each.is_none() {
+            let text: String = rendered.iter().map(|r| r.text.as_str()).collect();
+            return CommandOutput::new(text.strip_suffix('\n').unwrap_or(&text))
+                .field("rendered", &text)
+                .write(ctx);
+        }
+        let mut paths = Vec::with_capacity(rendered.len());
+        for rendered in rendered {
+            let path = rendered.path.unwrap_or_default();
+            if let Some(parent) = Path::new(&path).parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, rendered.text)?;
+            paths.push(path);
+        }
+        CommandOutput::new(format!("Rendered {} files", paths.len()))
+            .field("files", paths)
+            .write(ctx)
+    }
+}
@@ -0,0 +1,116 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::Utc;
+use chrono_tz::Tz;
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{process_time_format, process_time_parse, CmdExector, ExecContext};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum TimeSubCommand {
+    #[command(about = "Print the current time")]
+    Now(TimeNowOpts),
+    #[command(about = "Parse an epoch (s/ms/us/ns), RFC 3339 or RFC 2822 timestamp")]
+    Parse(TimeParseOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct TimeNowOpts {
+    #[arg(long, value_parser = parse_time_format, default_value = "iso")]
+    pub format: TimeFormat,
+    /// IANA timezone to render in, e.g. Asia/Shanghai; defaults to UTC
+    #[arg(long, value_parser = parse_tz)]
+    pub tz: Option<Tz>,
+}
+
+#[derive(Debug, Parser)]
+pub struct TimeParseOpts {
+    pub value: String,
+    /// Print only this format instead of all of them
+    #[arg(long, value_parser = parse_time_format)]
+    pub format: Option<TimeFormat>,
+    /// IANA timezone to render in, e.g. Asia/Shanghai; defaults to UTC
+    #[arg(long, value_parser = parse_tz)]
+    pub tz: Option<Tz>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TimeFormat {
+    Unix,
+    UnixMs,
+    Iso,
+    Rfc2822,
+}
+
+impl TimeFormat {
+    const ALL: [TimeFormat; 4] = [
+        TimeFormat::Unix,
+        TimeFormat::UnixMs,
+        TimeFormat::Iso,
+        TimeFormat::Rfc2822,
+    ];
+}
+
+fn parse_time_format(format: &str) -> Result<TimeFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_tz(tz: &str) -> Result<Tz, anyhow::Error> {
+    tz.parse()
+        .map_err(|_| anyhow::anyhow!("Invalid timezone: {}", tz))
+}
+
+impl FromStr for TimeFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unix" => Ok(TimeFormat::Unix),
+            "unix-ms" => Ok(TimeFormat::UnixMs),
+            "iso" | "rfc3339" => Ok(TimeFormat::Iso),
+            "rfc2822" => Ok(TimeFormat::Rfc2822),
+            _ => Err(anyhow::anyhow!("Invalid format: {}", s)),
+        }
+    }
+}
+
+impl From<TimeFormat> for &'static str {
+    fn from(format: TimeFormat) -> Self {
+        match format {
+            TimeFormat::Unix => "unix",
+            TimeFormat::UnixMs => "unix-ms",
+            TimeFormat::Iso => "iso",
+            TimeFormat::Rfc2822 => "rfc2822",
+        }
+    }
+}
+
+impl Display for TimeFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for TimeNowOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        ctx.write_text(&process_time_format(Utc::now(), self.format, self.tz))
+    }
+}
+
+impl CmdExector for TimeParseOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let time = process_time_parse(&self.value)?;
+        match self.format {
+            Some(format) => ctx.write_text(&process_time_format(time, format, self.tz))?,
+            None => {
+                for format in TimeFormat::ALL {
+                    let value = process_time_format(time, format, self.tz);
+                    writeln!(ctx.stdout(), "{:<8} {}", format.to_string(), value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
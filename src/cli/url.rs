@@ -0,0 +1,81 @@
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{
+    process_url_decode, process_url_encode, process_url_parse, CmdExector, CommandOutput,
+    ExecContext, Input,
+};
+
+use super::verify_file_exists;
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum UrlSubCommand {
+    #[command(about = "Percent-encode text for use in a URL")]
+    Encode(UrlEncodeOpts),
+    #[command(about = "Decode percent-encoded text")]
+    Decode(UrlDecodeOpts),
+    #[command(about = "Split a URL into scheme, host, port, path, query and fragment")]
+    Parse(UrlParseOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct UrlEncodeOpts {
+    /// Text to encode; a trailing newline is dropped
+    #[arg(short, long, value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+    /// Encode this text instead of reading -i
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+    /// Encode spaces as `+`, like HTML forms and most query strings
+    #[arg(long)]
+    pub form: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct UrlDecodeOpts {
+    #[arg(short, long, value_parser = verify_file_exists, default_value = "-")]
+    pub input: String,
+    /// Decode this text instead of reading -i
+    #[arg(long, conflicts_with = "input")]
+    pub text: Option<String>,
+    /// Read `+` as a space, like HTML forms and most query strings
+    #[arg(long)]
+    pub form: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct UrlParseOpts {
+    pub url: String,
+}
+
+impl CmdExector for UrlEncodeOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        let encoded = process_url_encode(input, self.form)?;
+        CommandOutput::new(&encoded)
+            .field("encoded", &encoded)
+            .write(ctx)
+    }
+}
+
+impl CmdExector for UrlDecodeOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let input = Input::new(&self.input, self.text.as_deref());
+        let decoded = process_url_decode(input, self.form)?;
+        match String::from_utf8(decoded) {
+            Ok(text) => CommandOutput::new(&text).field("decoded", &text).write(ctx),
+            Err(_) if ctx.json => anyhow::bail!("Decoded data is binary, it can't be JSON"),
+            // %-escapes can stand for any byte
+            Err(e) => ctx.write_bytes(e.as_bytes()),
+        }
+    }
+}
+
+impl CmdExector for UrlParseOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let parts = process_url_parse(&self.url)?;
+        // always JSON, so --json changes nothing
+        ctx.write_text(&serde_json::to_string_pretty(&parts)?)
+    }
+}
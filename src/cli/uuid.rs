@@ -0,0 +1,107 @@
+use std::{fmt::Display, str::FromStr};
+
+use clap::Parser;
+
+use crate::{process_uuid, process_uuid_decode, CmdExector, CommandOutput, ExecContext};
+
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct UuidOpts {
+    #[command(subcommand)]
+    pub cmd: Option<UuidSubCommand>,
+    // long only, -v is the global --verbose
+    #[arg(long, value_parser = parse_uuid_version, default_value = "v4")]
+    pub version: UuidVersion,
+    #[arg(short, long, default_value_t = 1)]
+    pub count: usize,
+    #[arg(long)]
+    pub upper: bool,
+}
+
+#[derive(Debug, Parser)]
+pub enum UuidSubCommand {
+    #[command(about = "Show the version, variant and embedded timestamp of a UUID")]
+    Decode(UuidDecodeOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct UuidDecodeOpts {
+    pub uuid: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum UuidVersion {
+    V4,
+    V7,
+}
+
+fn parse_uuid_version(version: &str) -> Result<UuidVersion, anyhow::Error> {
+    version.parse()
+}
+
+impl FromStr for UuidVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "v4" | "4" => Ok(UuidVersion::V4),
+            "v7" | "7" => Ok(UuidVersion::V7),
+            _ => Err(anyhow::anyhow!("Invalid version: {}", s)),
+        }
+    }
+}
+
+impl From<UuidVersion> for &'static str {
+    fn from(version: UuidVersion) -> Self {
+        match version {
+            UuidVersion::V4 => "v4",
+            UuidVersion::V7 => "v7",
+        }
+    }
+}
+
+impl Display for UuidVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl CmdExector for UuidOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        match &self.cmd {
+            Some(UuidSubCommand::Decode(opts)) => opts.execute(ctx).await,
+            None => {
+                let uuids: Vec<_> = process_uuid(self.version, self.count)
+                    .iter()
+                    .map(|uuid| match self.upper {
+                        true => uuid.hyphenated().to_string().to_uppercase(),
+                        false => uuid.hyphenated().to_string(),
+                    })
+                    .collect();
+                CommandOutput::new(uuids.join("\n"))
+                    .field("uuids", &uuids)
+                    .write(ctx)
+            }
+        }
+    }
+}
+
+impl CmdExector for UuidDecodeOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let info = process_uuid_decode(&self.uuid)?;
+        let mut lines = vec![
+            format!("uuid:      {}", info.uuid),
+            format!("version:   {}", info.version),
+            format!("variant:   {}", info.variant),
+        ];
+        if let Some(timestamp) = info.timestamp {
+            lines.push(format!("timestamp: {}", timestamp.to_rfc3339()));
+        }
+        CommandOutput::new(lines.join("\n"))
+            .field("uuid", info.uuid.hyphenated().to_string())
+            .field("version", info.version)
+            .field("variant", info.variant)
+            .field("timestamp", info.timestamp.map(|t| t.to_rfc3339()))
+            .write(ctx)
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    io::{self, IsTerminal, Read},
+    path::Path,
+};
+
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{
+    output::copy_to_clipboard, process_genpass, CmdExector, ExecContext, PasswordCharset, Secret,
+    Vault,
+};
+
+/// Read by every vault command instead of prompting, for scripts
+const VAULT_PASSWORD_ENV: &str = "RCLI_VAULT_PASSWORD";
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum VaultSubCommand {
+    #[command(visible_alias = "set", about = "Add a secret to the vault")]
+    Add(VaultAddOpts),
+    #[command(about = "Print or copy a secret from the vault")]
+    Get(VaultGetOpts),
+    #[command(about = "List the entries in the vault")]
+    List(VaultListOpts),
+    #[command(visible_alias = "del", about = "Remove a secret from the vault")]
+    Rm(VaultRmOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct VaultAddOpts {
+    pub name: String,
+    #[arg(short, long, default_value = "rcli.vault")]
+    pub file: String,
+    /// The secret; without it or --generate it's read from stdin when piped, e.g.
+    /// `rcli genpass | rcli kv set github`, and prompted for otherwise
+    #[arg(long, conflicts_with = "generate")]
+    pub secret: Option<Secret>,
+    /// Generate the secret with genpass
+    #[arg(short, long)]
+    pub generate: bool,
+    #[arg(short, long, default_value_t = 16, requires = "generate")]
+    pub length: u8,
+    /// Overwrite an existing entry
+    #[arg(long)]
+    pub force: bool,
+    /// Copy the secret to the clipboard after storing it
+    #[arg(short, long)]
+    pub clip: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct VaultGetOpts {
+    pub name: String,
+    #[arg(short, long, default_value = "rcli.vault")]
+    pub file: String,
+    /// Copy the secret to the clipboard instead of printing it
+    #[arg(short, long)]
+    pub clip: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct VaultListOpts {
+    #[arg(short, long, default_value = "rcli.vault")]
+    pub file: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct VaultRmOpts {
+    pub name: String,
+    #[arg(short, long, default_value = "rcli.vault")]
+    pub file: String,
+}
+
+/// Take the master password from the environment or the terminal, confirming it for new vaults
+fn vault_password(file: &str) -> anyhow::Result<String> {
+    if let Ok(password) = std::env::var(VAULT_PASSWORD_ENV) {
+        return Ok(password);
+    }
+    let password = rpassword::prompt_password("Vault password: ")?;
+    if !Path::new(file).exists()
+        && rpassword::prompt_password("Confirm vault password: ")? != password
+    {
+        return Err(anyhow::anyhow!("Passwords do not match"));
+    }
+    Ok(password)
+}
+
+impl CmdExector for VaultAddOpts {
+    async fn execute(&self, _ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let password = vault_password(&self.file)?;
+        let mut vault = Vault::open(&self.file, &password)?;
+        let secret = match &self.secret {
+            Some(secret) => secret.clone(),
+            None if self.generate => {
+                process_genpass(self.length, &PasswordCharset::default())?.into()
+            }
+            None if !io::stdin().is_terminal() => {
+                let mut secret = String::new();
+                io::stdin().read_to_string(&mut secret)?;
+                // piped commands end their output with a newline that isn't part of the secret
+                let len = secret.trim_end_matches(['\r', '\n']).len();
+                secret.truncate(len);
+                secret.into()
+            }
+            None => rpassword::prompt_password(format!("Secret for {}: ", self.name))?.into(),
+        };
+        vault.add(&self.name, secret.clone(), self.force)?;
+        vault.save(&self.file, &password)?;
+        if self.clip {
+            copy_to_clipboard(secret.expose_str())?;
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for VaultGetOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let vault = Vault::open(&self.file, &vault_password(&self.file)?)?;
+        let entry = vault.get(&self.name)?;
+        if self.clip {
+            copy_to_clipboard(entry.secret.expose_str())?;
+        } else {
+            writeln!(ctx.stdout(), "{}", entry.secret.expose_str())?;
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for VaultListOpts {
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let vault = Vault::open(&self.file, &vault_password(&self.file)?)?;
+        for (name, entry) in vault.list() {
+            writeln!(ctx.stdout(), "{}\t{}", name, entry.updated)?;
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for VaultRmOpts {
+    async fn execute(&self, _ctx: &mut ExecContext) -> anyhow::Result<()> {
+        let password = vault_password(&self.file)?;
+        let mut vault = Vault::open(&self.file, &password)?;
+        vault.remove(&self.name)?;
+        vault.save(&self.file, &password)?;
+        Ok(())
+    }
+}
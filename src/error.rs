@@ -0,0 +1,223 @@
+use std::{io, path::PathBuf};
+
+use thiserror::Error;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+pub type Result<T, E = RcliError> = std::result::Result<T, E>;
+
+/// Errors returned by the `process_*` functions, grouped so library users can match on
+/// the kind of failure rather than parse messages
+#[derive(Debug, Error)]
+pub enum RcliError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("{}: file not found", .0.display())]
+    FileNotFound(PathBuf),
+    #[error("{0} already exists, use --force to overwrite")]
+    AlreadyExists(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    /// Input that doesn't parse: bad JSON, CSV, base64, timestamps...
+    #[error("{0}")]
+    Format(BoxError),
+    /// A key that is malformed, of the wrong type or the wrong size
+    #[error("{0}")]
+    InvalidKey(BoxError),
+    #[error("Invalid signature")]
+    InvalidSignature,
+    /// A check ran and the input didn't pass; the verdict has already been printed
+    #[error("Verification failed")]
+    VerificationFailed,
+    /// Encryption, decryption or key derivation failed, e.g. a wrong password or tampered data
+    #[error("{0}")]
+    Crypto(BoxError),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Jwt(jsonwebtoken::errors::Error),
+    /// Options that can't be used together, or a feature the format doesn't have
+    #[error("{0}")]
+    Unsupported(String),
+}
+
+macro_rules! impl_from {
+    ($variant:ident: $($ty:ty),+ $(,)?) => {
+        $(
+            impl From<$ty> for RcliError {
+                fn from(e: $ty) -> Self {
+                    RcliError::$variant(Box::new(e))
+                }
+            }
+        )+
+    };
+}
+
+impl_from!(Format:
+    serde_json::Error,
+    serde_yaml::Error,
+    csv::Error,
+    base64::DecodeError,
+    data_encoding::DecodeError,
+    std::str::Utf8Error,
+    std::string::FromUtf8Error,
+    std::num::ParseIntError,
+    bs58::decode::Error,
+    toml::de::Error,
+    chrono::ParseError,
+    glob::PatternError,
+    axum::http::header::InvalidHeaderValue,
+    rust_xlsxwriter::XlsxError,
+    png::EncodingError,
+    rcgen::Error,
+);
+
+impl_from!(InvalidKey:
+    std::array::TryFromSliceError,
+    ed25519_dalek::SignatureError,
+    ed25519_dalek::pkcs8::Error,
+    ed25519_dalek::pkcs8::spki::Error,
+    rsa::pkcs1::Error,
+    p256::elliptic_curve::Error,
+    ssh_key::Error,
+    hmac::digest::InvalidLength,
+);
+
+impl_from!(Crypto:
+    rsa::Error,
+    age::EncryptError,
+    age::DecryptError,
+);
+
+impl From<glob::GlobError> for RcliError {
+    fn from(e: glob::GlobError) -> Self {
+        RcliError::Io(e.into())
+    }
+}
+
+impl From<tokio::task::JoinError> for RcliError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        RcliError::Io(io::Error::other(e))
+    }
+}
+
+impl From<std::time::SystemTimeError> for RcliError {
+    fn from(e: std::time::SystemTimeError) -> Self {
+        RcliError::Io(io::Error::other(e))
+    }
+}
+
+impl From<notify::Error> for RcliError {
+    fn from(e: notify::Error) -> Self {
+        match e.kind {
+            notify::ErrorKind::Io(e) => RcliError::Io(e),
+            _ => RcliError::Io(io::Error::other(e)),
+        }
+    }
+}
+
+impl From<async_zip::error::ZipError> for RcliError {
+    fn from(e: async_zip::error::ZipError) -> Self {
+        match e {
+            async_zip::error::ZipError::UpstreamReadError(e) => RcliError::Io(e),
+            e => RcliError::Format(Box::new(e)),
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for RcliError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => RcliError::Io(e),
+            e => RcliError::Format(Box::new(e)),
+        }
+    }
+}
+
+impl From<tera::Error> for RcliError {
+    fn from(e: tera::Error) -> Self {
+        // the useful part, e.g. which variable is missing, is in the sources
+        let mut message = e.to_string();
+        let mut source = std::error::Error::source(&e);
+        while let Some(e) = source {
+            message.push_str(&format!(": {}", e));
+            source = e.source();
+        }
+        RcliError::format(message)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for RcliError {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        match e.kind() {
+            jsonwebtoken::errors::ErrorKind::InvalidSignature => RcliError::InvalidSignature,
+            _ => RcliError::Jwt(e),
+        }
+    }
+}
+
+impl RcliError {
+    pub(crate) fn format(message: impl Into<String>) -> Self {
+        RcliError::Format(message.into().into())
+    }
+
+    pub(crate) fn invalid_key(message: impl Into<String>) -> Self {
+        RcliError::InvalidKey(message.into().into())
+    }
+
+    pub(crate) fn crypto(message: impl Into<String>) -> Self {
+        RcliError::Crypto(message.into().into())
+    }
+
+    /// Whether the input was checked and rejected, e.g. a bad signature or an expired
+    /// token, as opposed to not being checkable at all
+    pub fn is_verification_failure(&self) -> bool {
+        use jsonwebtoken::errors::ErrorKind;
+        match self {
+            RcliError::InvalidSignature | RcliError::VerificationFailed => true,
+            RcliError::Jwt(e) => matches!(
+                e.kind(),
+                ErrorKind::ExpiredSignature
+                    | ErrorKind::ImmatureSignature
+                    | ErrorKind::InvalidAudience
+                    | ErrorKind::InvalidIssuer
+                    | ErrorKind::InvalidSubject
+                    | ErrorKind::InvalidAlgorithm
+                    | ErrorKind::MissingRequiredClaim(_)
+            ),
+            _ => false,
+        }
+    }
+
+    /// The process exit code for this error: 1 when verification failed, 2 otherwise
+    pub fn exit_code(&self) -> i32 {
+        if self.is_verification_failure() {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_hash, process_jwt_sign, process_jwt_verify, HashAlgorithm, JwtKeySet};
+
+    #[test]
+    fn test_error_kinds() -> Result<()> {
+        assert!(matches!(
+            process_hash("fixtures/missing.txt", HashAlgorithm::Sha256),
+            Err(RcliError::FileNotFound(path)) if path.ends_with("missing.txt")
+        ));
+        let token = process_jwt_sign("acme", "device1", chrono::Duration::minutes(1), None, None)?;
+        let keys = JwtKeySet::load(&["other-secret".parse().unwrap()], &[], None)?;
+        assert!(matches!(
+            process_jwt_verify(&token, &keys, crate::JWT_DEFAULT_LEEWAY),
+            Err(RcliError::InvalidSignature)
+        ));
+        assert_eq!(RcliError::InvalidSignature.exit_code(), 1);
+        assert_eq!(RcliError::NotFound("key".to_string()).exit_code(), 2);
+        Ok(())
+    }
+}
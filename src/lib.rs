@@ -1,12 +1,35 @@
+//! The `rcli` command line tool, usable as a library.
+//!
+//! Every `process_*` function is silent: it returns its result and leaves printing to the
+//! caller, so the same code backs the CLI and other tools embedding it. Failures are
+//! reported as [`RcliError`], whose variants can be matched on.
+//!
+//! ```
+//! let charset = rcli::PasswordCharset::new(true, true, true, false);
+//! let password = rcli::process_genpass(16, &charset)?;
+//! assert_eq!(password.len(), 16);
+//!
+//! let ids = rcli::process_uuid(rcli::UuidVersion::V7, 2);
+//! assert!(ids[0] < ids[1]);
+//! # Ok::<(), rcli::RcliError>(())
+//! ```
 mod cli;
+mod error;
+mod metrics;
+mod output;
 mod process;
+mod secret;
 mod utils;
 pub use cli::*;
 use enum_dispatch::enum_dispatch;
+pub use error::{RcliError, Result};
+pub use metrics::{set_verbose, take_samples, timer, verbose, CountingReader, Sample, Timer};
+pub use output::{CommandOutput, ExecContext, OutputTarget};
 pub use process::*;
+pub use secret::Secret;
 pub use utils::*;
 #[allow(async_fn_in_trait)]
 #[enum_dispatch]
 pub trait CmdExector {
-    async fn execute(&self) -> anyhow::Result<()>;
+    async fn execute(&self, ctx: &mut ExecContext) -> anyhow::Result<()>;
 }
@@ -1,12 +1,45 @@
-use clap::Parser;
-use rcli::{CmdExector, Opts};
+use rcli::{
+    set_rng_seed, set_verbose, take_samples, CmdExector, ExecContext, Opts, OutputTarget, RcliError,
+};
+use tracing_subscriber::EnvFilter;
 
 // rcli csv -i input.csv -o output.json --header -d ','
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
-    let opts = Opts::parse();
-    opts.cmd.execute().await?;
-    Ok(())
+async fn main() {
+    if let Err(e) = run().await {
+        let error = e.downcast_ref::<RcliError>();
+        // a failed verification already printed its verdict
+        if !matches!(error, Some(RcliError::VerificationFailed)) {
+            eprintln!("Error: {:?}", e);
+        }
+        std::process::exit(error.map_or(2, RcliError::exit_code));
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
+    let opts = Opts::parse_with_config()?;
+    let filter = match opts.log_filter() {
+        Some(filter) => EnvFilter::new(filter),
+        None => EnvFilter::from_default_env(),
+    };
+    // logs go to stderr so they never mix with results piped from stdout
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+    let output = match &opts.output {
+        Some(output) => output.parse()?,
+        None => OutputTarget::Stdout,
+    };
+    let mut ctx = ExecContext::new(output, opts.json, opts.quiet);
+    set_verbose(opts.verbose > 0);
+    if let Some(seed) = opts.seed {
+        set_rng_seed(seed, opts.insecure_seed);
+    }
+    let result = opts.cmd.execute(&mut ctx).await;
+    for sample in take_samples() {
+        eprintln!("{}", sample);
+    }
+    result
 }
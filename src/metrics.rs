@@ -0,0 +1,152 @@
+use std::{
+    fmt,
+    io::{self, Read},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use tracing::{debug, trace};
+
+use crate::ByteRate;
+
+/// Set from the global `--verbose` flag before the command runs
+static VERBOSE: OnceLock<bool> = OnceLock::new();
+
+/// Timings recorded while the command ran, printed by main once it's done
+static SAMPLES: Mutex<Vec<Sample>> = Mutex::new(Vec::new());
+
+pub fn set_verbose(verbose: bool) {
+    let _ = VERBOSE.set(verbose);
+}
+
+/// Whether process functions record how long they took and how much they read
+pub fn verbose() -> bool {
+    *VERBOSE.get_or_init(|| false)
+}
+
+/// How long one process function took, and how many input bytes it got through
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: &'static str,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// Times a process function from its creation until it's dropped. Nothing is recorded
+/// unless `--verbose` is set, so leaving timers in hot paths costs next to nothing.
+#[must_use = "a timer records when it's dropped, bind it to a variable"]
+pub struct Timer {
+    name: &'static str,
+    start: Instant,
+    bytes: AtomicU64,
+}
+
+/// Start timing `name`, e.g. `"base64 encode"`
+pub fn timer(name: &'static str) -> Timer {
+    trace!(step = name, "started");
+    Timer {
+        name,
+        start: Instant::now(),
+        bytes: AtomicU64::new(0),
+    }
+}
+
+/// Everything recorded so far, oldest first, leaving none behind
+pub fn take_samples() -> Vec<Sample> {
+    let mut samples = SAMPLES.lock().unwrap_or_else(|e| e.into_inner());
+    std::mem::take(&mut *samples)
+}
+
+impl Timer {
+    pub fn add_bytes(&self, n: u64) {
+        self.bytes.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Wrap a reader so everything read through it counts towards this timer
+    pub fn reader<R: Read>(&self, inner: R) -> CountingReader<'_, R> {
+        CountingReader { inner, timer: self }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let sample = Sample {
+            name: self.name,
+            bytes: self.bytes.load(Ordering::Relaxed),
+            elapsed: self.start.elapsed(),
+        };
+        debug!(step = sample.name, bytes = sample.bytes, elapsed = ?sample.elapsed, "finished");
+        if !verbose() {
+            return;
+        }
+        SAMPLES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sample);
+    }
+}
+
+pub struct CountingReader<'a, R> {
+    inner: R,
+    timer: &'a Timer,
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.timer.add_bytes(n as u64);
+        Ok(n)
+    }
+}
+
+impl Sample {
+    /// Bytes per second, `None` when too quick to measure
+    pub fn rate(&self) -> Option<ByteRate> {
+        let secs = self.elapsed.as_secs_f64();
+        (secs > 0.0).then(|| ByteRate((self.bytes as f64 / secs) as u64))
+    }
+}
+
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = match self.bytes {
+            n if n >= 1_000_000 => format!("{:.1} MB", n as f64 / 1e6),
+            n if n >= 1_000 => format!("{:.1} KB", n as f64 / 1e3),
+            n => format!("{} B", n),
+        };
+        write!(f, "{}: {} in {:.2?}", self.name, size, self.elapsed)?;
+        match self.rate() {
+            Some(rate) if self.bytes > 0 => write!(f, " ({})", rate),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_counts_bytes() -> anyhow::Result<()> {
+        let timer = timer("test");
+        let mut data = Vec::new();
+        timer.reader(&b"hello world"[..]).read_to_end(&mut data)?;
+        timer.add_bytes(5);
+        assert_eq!(timer.bytes.load(Ordering::Relaxed), 16);
+
+        let sample = Sample {
+            name: "base64 encode",
+            bytes: 3_000_000,
+            elapsed: Duration::from_millis(1500),
+        };
+        assert_eq!(sample.rate(), Some(ByteRate(2_000_000)));
+        assert_eq!(
+            sample.to_string(),
+            "base64 encode: 3.0 MB in 1.50s (2.0 MB/s)"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,359 @@
+use std::{
+    fs::File,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tokio::io::AsyncWrite;
+
+use crate::RcliError;
+
+const CLIPBOARD_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// Where a command's result goes: `-` for stdout, `clipboard`, or a file path
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum OutputTarget {
+    #[default]
+    Stdout,
+    Clipboard,
+    File(PathBuf),
+}
+
+/// What a command writes to and how, handed to every executor: the `-o` target, the
+/// `--json` and `--quiet` flags, and stdout/stderr, which tests swap for buffers
+#[derive(Debug, Clone, Default)]
+pub struct ExecContext {
+    /// The target chosen with `-o`, stdout when none was given
+    pub output: OutputTarget,
+    /// Whether results are written as JSON objects rather than text
+    pub json: bool,
+    /// Whether results are left out entirely, for scripts that only look at the exit code
+    pub quiet: bool,
+    stdout: Stream,
+    stderr: Stream,
+}
+
+#[derive(Debug, Clone, Default)]
+enum Stream {
+    #[default]
+    Real,
+    Captured(Arc<Mutex<Vec<u8>>>),
+}
+
+/// A writer appending to a captured stream
+struct CaptureWriter(Arc<Mutex<Vec<u8>>>);
+
+/// A command's result: the text it prints normally, and the fields of the object it
+/// prints instead with `--json`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandOutput {
+    text: String,
+    fields: Map<String, Value>,
+    /// set for verifications, whose failure decides the exit code
+    valid: Option<bool>,
+}
+
+impl CommandOutput {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            fields: Map::new(),
+            valid: None,
+        }
+    }
+
+    /// The outcome of a verification, with why it failed when it did. Writing a failed
+    /// one returns `RcliError::VerificationFailed`, so the process exits with 1
+    pub fn verification(valid: bool, reason: Option<String>) -> Self {
+        let mut output = Self::new(valid.to_string())
+            .field("valid", valid)
+            .field("reason", reason);
+        output.valid = Some(valid);
+        output
+    }
+
+    /// Replace the text, e.g. when more than the verdict is printed
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = text.into();
+        self
+    }
+
+    pub fn field(mut self, name: &str, value: impl Serialize) -> Self {
+        // everything passed here is plain data, which always converts
+        let value = serde_json::to_value(value).unwrap_or(Value::Null);
+        self.fields.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.fields)?)
+    }
+
+    /// Write to the `-o` target, as JSON when `--json` is set, or nothing with `--quiet`
+    pub fn write(&self, ctx: &ExecContext) -> Result<()> {
+        if !ctx.quiet {
+            match ctx.json {
+                true => ctx.write_text(&self.to_json()?)?,
+                false => ctx.write_text(&self.text)?,
+            }
+        }
+        match self.valid {
+            Some(false) => Err(RcliError::VerificationFailed.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for OutputTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err(anyhow::anyhow!("Output can't be empty")),
+            "-" => Ok(OutputTarget::Stdout),
+            "clipboard" => Ok(OutputTarget::Clipboard),
+            path => Ok(OutputTarget::File(path.into())),
+        }
+    }
+}
+
+impl OutputTarget {
+    /// Whether `writer` can be used, i.e. the output doesn't need the whole result at once
+    pub fn is_streamable(&self) -> bool {
+        !matches!(self, OutputTarget::Clipboard)
+    }
+}
+
+impl ExecContext {
+    pub fn new(output: OutputTarget, json: bool, quiet: bool) -> Self {
+        Self {
+            output,
+            json,
+            quiet,
+            ..Default::default()
+        }
+    }
+
+    /// Keep what's written to stdout and stderr instead of printing it, see [`Self::stdout_text`]
+    pub fn captured(mut self) -> Self {
+        self.stdout = Stream::Captured(Default::default());
+        self.stderr = Stream::Captured(Default::default());
+        self
+    }
+
+    /// Everything written to stdout so far by a [captured](Self::captured) context
+    pub fn stdout_text(&self) -> String {
+        self.stdout.text()
+    }
+
+    /// Everything written to stderr so far by a [captured](Self::captured) context
+    pub fn stderr_text(&self) -> String {
+        self.stderr.text()
+    }
+
+    pub fn stdout(&self) -> Box<dyn Write + Send> {
+        match &self.stdout {
+            Stream::Real => Box::new(io::stdout()),
+            Stream::Captured(buf) => Box::new(CaptureWriter(buf.clone())),
+        }
+    }
+
+    /// For progress and notes that must not end up in piped results
+    pub fn stderr(&self) -> Box<dyn Write + Send> {
+        match &self.stderr {
+            Stream::Real => Box::new(io::stderr()),
+            Stream::Captured(buf) => Box::new(CaptureWriter(buf.clone())),
+        }
+    }
+
+    /// Whether stdout is a terminal, where binary data would garble the screen
+    pub fn stdout_is_terminal(&self) -> bool {
+        matches!(self.stdout, Stream::Real) && io::stdout().is_terminal()
+    }
+
+    /// Write a line of text, without the trailing newline on the clipboard
+    pub fn write_text(&self, text: &str) -> Result<()> {
+        match self.output {
+            OutputTarget::Clipboard => copy_to_clipboard(text),
+            _ => self.write_bytes(format!("{}\n", text).as_bytes()),
+        }
+    }
+
+    /// Write raw bytes, refusing to dump binary data onto a terminal or the clipboard
+    pub fn write_bytes(&self, data: &[u8]) -> Result<()> {
+        match &self.output {
+            OutputTarget::Stdout => {
+                if self.stdout_is_terminal() && std::str::from_utf8(data).is_err() {
+                    return Err(anyhow::anyhow!(
+                        "Refusing to write binary data to the terminal, use -o <file> or a pipe"
+                    ));
+                }
+                let mut stdout = self.stdout();
+                stdout.write_all(data)?;
+                stdout.flush()?;
+            }
+            OutputTarget::Clipboard => {
+                copy_to_clipboard(std::str::from_utf8(data).map_err(|_| {
+                    anyhow::anyhow!("Binary data can't be copied to the clipboard")
+                })?)?
+            }
+            OutputTarget::File(path) => std::fs::write(path, data)?,
+        }
+        Ok(())
+    }
+
+    /// The `-o` target as a stream, for results too large to hold in memory
+    pub fn writer(&self) -> Result<Box<dyn Write + Send>> {
+        match &self.output {
+            OutputTarget::Stdout => Ok(self.stdout()),
+            OutputTarget::File(path) => Ok(Box::new(File::create(path)?)),
+            OutputTarget::Clipboard => Err(anyhow::anyhow!(
+                "Streaming output can't be written to the clipboard"
+            )),
+        }
+    }
+
+    pub async fn async_writer(&self) -> Result<Box<dyn AsyncWrite + Unpin + Send>> {
+        match (&self.output, &self.stdout) {
+            (OutputTarget::Stdout, Stream::Real) => Ok(Box::new(tokio::io::stdout())),
+            (OutputTarget::Stdout, Stream::Captured(buf)) => {
+                Ok(Box::new(CaptureWriter(buf.clone())))
+            }
+            (OutputTarget::File(path), _) => Ok(Box::new(tokio::fs::File::create(path).await?)),
+            (OutputTarget::Clipboard, _) => Err(anyhow::anyhow!(
+                "Streaming output can't be written to the clipboard"
+            )),
+        }
+    }
+}
+
+impl Stream {
+    fn text(&self) -> String {
+        match self {
+            Stream::Real => String::new(),
+            Stream::Captured(buf) => {
+                String::from_utf8_lossy(&buf.lock().unwrap_or_else(|e| e.into_inner())).into()
+            }
+        }
+    }
+}
+
+impl Write for CaptureWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let mut buf = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncWrite for CaptureWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Write::write(&mut *self, data))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub(crate) fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut clipboard = arboard::Clipboard::new()?;
+    // X11/Wayland clipboards are served by the owning process, so stay alive until
+    // something else is copied or the timeout clears it
+    #[cfg(target_os = "linux")]
+    {
+        use arboard::SetExtLinux;
+        eprintln!(
+            "Copied to clipboard, clearing in {}s",
+            CLIPBOARD_TIMEOUT.as_secs()
+        );
+        clipboard
+            .set()
+            .wait_until(std::time::Instant::now() + CLIPBOARD_TIMEOUT)
+            .text(text)?;
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        clipboard.set_text(text)?;
+        eprintln!("Copied to clipboard");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_target() -> Result<()> {
+        assert_eq!("-".parse::<OutputTarget>()?, OutputTarget::Stdout);
+        assert_eq!(
+            "clipboard".parse::<OutputTarget>()?,
+            OutputTarget::Clipboard
+        );
+        let path = std::env::temp_dir().join("rcli-output.txt");
+        let target: OutputTarget = path.to_string_lossy().parse()?;
+        ExecContext::new(target, false, false).write_text("hello")?;
+        assert_eq!(std::fs::read_to_string(&path)?, "hello\n");
+        let ctx = ExecContext::new(OutputTarget::Clipboard, false, false);
+        assert!(ctx.write_bytes(&[0xff, 0xfe]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_exec_context_captures_output() -> Result<()> {
+        let output = CommandOutput::new("hello").field("greeting", "hello");
+        let ctx = ExecContext::default().captured();
+        output.write(&ctx)?;
+        writeln!(ctx.stderr(), "note")?;
+        assert_eq!(ctx.stdout_text(), "hello\n");
+        assert_eq!(ctx.stderr_text(), "note\n");
+
+        let ctx = ExecContext::new(OutputTarget::Stdout, true, false).captured();
+        output.write(&ctx)?;
+        assert_eq!(ctx.stdout_text(), "{\n  \"greeting\": \"hello\"\n}\n");
+
+        let ctx = ExecContext::new(OutputTarget::Stdout, false, true).captured();
+        let verdict = CommandOutput::verification(false, None).write(&ctx);
+        assert!(verdict.is_err());
+        assert_eq!(ctx.stdout_text(), "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_output() -> Result<()> {
+        let output = CommandOutput::verification(false, Some("signature mismatch".to_string()))
+            .field("claims", serde_json::json!({ "sub": "acme" }));
+        assert_eq!(output.text, "false");
+        let json: Value = serde_json::from_str(&output.to_json()?)?;
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "valid": false,
+                "reason": "signature mismatch",
+                "claims": { "sub": "acme" },
+            })
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,393 @@
+use std::{
+    io::{BufRead, Write},
+    net::SocketAddr,
+    str::FromStr,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use subtle::ConstantTimeEq;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::info;
+
+use super::{
+    hash::hash_reader,
+    text::{sign_reader, verify_reader},
+};
+use crate::{
+    decode_signature, process_csv, process_decode_stream, process_encode_stream, process_hash,
+    process_jwt_sign, process_jwt_verify, Base64Format, CsvDialect, HashAlgorithm, JwtKeySet,
+    OutputFormat, RcliError, Result, Secret, TextSignFormat, JWT_DEFAULT_LEEWAY,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+const UNAUTHORIZED: i64 = -32001;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    /// absent on notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug)]
+enum ApiError {
+    MethodNotFound(String),
+    InvalidParams(String),
+    Failed(RcliError),
+}
+
+impl From<RcliError> for ApiError {
+    fn from(e: RcliError) -> Self {
+        ApiError::Failed(e)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthParams {
+    token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DataParams {
+    data: String,
+    format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HashParams {
+    data: Option<String>,
+    input: Option<String>,
+    algorithm: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignParams {
+    data: String,
+    key: String,
+    signature: Option<String>,
+    format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtSignParams {
+    sub: String,
+    aud: String,
+    /// lifetime in seconds
+    exp: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtVerifyParams {
+    token: String,
+    #[serde(default)]
+    secrets: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CsvParams {
+    input: String,
+    output: String,
+    format: Option<String>,
+}
+
+/// Answer one JSON-RPC 2.0 request line, `None` for notifications
+pub fn process_api_request(line: &str) -> Option<String> {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, e.to_string())),
+    };
+    let result = dispatch(&request.method, request.params);
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string(),
+        Err(ApiError::MethodNotFound(method)) => error_response(
+            id,
+            METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        ),
+        Err(ApiError::InvalidParams(msg)) => error_response(id, INVALID_PARAMS, msg),
+        Err(ApiError::Failed(e)) => error_response(id, SERVER_ERROR, e.to_string()),
+    })
+}
+
+fn error_response(id: Value, code: i64, message: String) -> String {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }).to_string()
+}
+
+fn dispatch(method: &str, params: Value) -> Result<Value, ApiError> {
+    match method {
+        "encode" => {
+            let p: DataParams = parse_params(params)?;
+            let format: Base64Format = parse_enum(p.format.as_deref().unwrap_or("standard"))?;
+            let mut encoded = Vec::new();
+            process_encode_stream(&mut p.data.as_bytes(), &mut encoded, format, None)?;
+            Ok(json!(String::from_utf8(encoded).map_err(RcliError::from)?))
+        }
+        "decode" => {
+            let p: DataParams = parse_params(params)?;
+            let format: Base64Format = parse_enum(p.format.as_deref().unwrap_or("standard"))?;
+            let mut decoded = Vec::new();
+            process_decode_stream(&mut p.data.trim().as_bytes(), &mut decoded, format)?;
+            Ok(json!(String::from_utf8(decoded).map_err(RcliError::from)?))
+        }
+        "hash" => {
+            let p: HashParams = parse_params(params)?;
+            let algorithm: HashAlgorithm = parse_enum(p.algorithm.as_deref().unwrap_or("sha256"))?;
+            let digest = match (p.data, p.input) {
+                (Some(data), None) => hash_reader(&mut data.as_bytes(), algorithm)?,
+                (None, Some(input)) => process_hash(&input, algorithm)?,
+                _ => return Err(invalid_params("expected one of data or input")),
+            };
+            Ok(json!(digest))
+        }
+        "sign" => {
+            let p: SignParams = parse_params(params)?;
+            let format: TextSignFormat = parse_enum(p.format.as_deref().unwrap_or("blake3"))?;
+            let signature = sign_reader(&mut p.data.as_bytes(), &p.key, format)?;
+            Ok(json!(URL_SAFE_NO_PAD.encode(signature)))
+        }
+        "verify" => {
+            let p: SignParams = parse_params(params)?;
+            let format: TextSignFormat = parse_enum(p.format.as_deref().unwrap_or("blake3"))?;
+            let signature = p
+                .signature
+                .ok_or_else(|| invalid_params("missing field `signature`"))?;
+            let signature = decode_signature(signature.as_bytes())?;
+            let verified = verify_reader(&mut p.data.as_bytes(), &p.key, format, &signature)?;
+            Ok(json!(verified.is_valid()))
+        }
+        "jwt.sign" => {
+            let p: JwtSignParams = parse_params(params)?;
+            let token =
+                process_jwt_sign(&p.sub, &p.aud, chrono::Duration::seconds(p.exp), None, None)?;
+            Ok(json!(token))
+        }
+        "jwt.verify" => {
+            let p: JwtVerifyParams = parse_params(params)?;
+            let secrets = p
+                .secrets
+                .iter()
+                .map(|s| {
+                    s.parse::<Secret>()
+                        .map_err(|e| invalid_params(e.to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let keys = JwtKeySet::load(&secrets, &[], None)?;
+            match process_jwt_verify(&p.token, &keys, JWT_DEFAULT_LEEWAY) {
+                Ok(valid) => Ok(json!(valid)),
+                Err(RcliError::InvalidSignature) => Ok(json!(false)),
+                Err(e) => Err(e.into()),
+            }
+        }
+        "csv.convert" => {
+            let p: CsvParams = parse_params(params)?;
+            let format: OutputFormat = parse_enum(p.format.as_deref().unwrap_or("json"))?;
+            let rows = process_csv(
+                &p.input,
+                p.output,
+                format,
+                &CsvDialect::default(),
+                None,
+                None,
+                None,
+                1,
+                None,
+            )?;
+            Ok(json!({ "rows": rows }))
+        }
+        _ => Err(ApiError::MethodNotFound(method.to_string())),
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, ApiError> {
+    serde_json::from_value(params).map_err(|e| invalid_params(e.to_string()))
+}
+
+fn parse_enum<T: FromStr>(value: &str) -> Result<T, ApiError>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e: T::Err| invalid_params(e.to_string()))
+}
+
+fn invalid_params(msg: impl Into<String>) -> ApiError {
+    ApiError::InvalidParams(msg.into())
+}
+
+/// Serve requests read line by line from stdin until it closes, one response line each
+/// to `stdout`
+pub fn process_api_stdio(mut stdout: impl Write) -> Result<()> {
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = process_api_request(&line) {
+            writeln!(stdout, "{}", response)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// The response to the first line of a TCP connection, and whether it authenticated it.
+/// Any local process or web page can reach the port, so only a client that knows `token`
+/// and sends `{"method": "auth", "params": {"token": ...}}` first gets to call methods.
+pub fn process_api_auth(line: &str, token: &Secret) -> (String, bool) {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        // e.g. the request line of an HTTP POST from a browser
+        Err(e) => {
+            return (
+                error_response(Value::Null, PARSE_ERROR, e.to_string()),
+                false,
+            )
+        }
+    };
+    let id = request.id.unwrap_or(Value::Null);
+    let authorized = request.method == "auth"
+        && parse_params::<AuthParams>(request.params).is_ok_and(|p| {
+            p.token
+                .as_bytes()
+                .ct_eq(token.expose_str().as_bytes())
+                .into()
+        });
+    if authorized {
+        let response = json!({ "jsonrpc": "2.0", "id": id, "result": true });
+        (response.to_string(), true)
+    } else {
+        let message = "Unauthorized, the first request must be auth with the server token";
+        (error_response(id, UNAUTHORIZED, message.to_string()), false)
+    }
+}
+
+/// Serve the same line protocol to every TCP client on localhost that authenticates with
+/// `token`, see [`process_api_auth`]
+pub async fn process_api_serve(port: u16, token: Secret) -> Result<()> {
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("JSON-RPC listening on {}", addr);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let token = token.clone();
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let Ok(Some(line)) = lines.next_line().await else {
+                return;
+            };
+            let (response, authorized) = process_api_auth(&line, &token);
+            let _ = writer.write_all(format!("{}\n", response).as_bytes()).await;
+            if !authorized {
+                info!("JSON-RPC client {} failed to authenticate", peer);
+                return;
+            }
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                // requests read files and run crypto, keep them off the async workers
+                let response = tokio::task::spawn_blocking(move || process_api_request(&line))
+                    .await
+                    .ok()
+                    .flatten();
+                if let Some(response) = response {
+                    if writer
+                        .write_all(format!("{}\n", response).as_bytes())
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+            info!("JSON-RPC client {} disconnected", peer);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(method: &str, params: Value) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        serde_json::from_str(&process_api_request(&request.to_string()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_api_requests() {
+        let response = call("encode", json!({ "data": "hello" }));
+        assert_eq!(response["result"], "aGVsbG8=");
+        let response = call("decode", json!({ "data": "aGVsbG8", "format": "urlsafe" }));
+        assert_eq!(response["result"], "hello");
+        let response = call("hash", json!({ "data": "abc" }));
+        assert_eq!(
+            response["result"],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let key = "fixtures/blake3.txt";
+        let response = call("sign", json!({ "data": "hello", "key": key }));
+        let signature = response["result"].as_str().unwrap();
+        let params = json!({ "data": "hello", "key": key, "signature": signature });
+        assert_eq!(call("verify", params)["result"], true);
+
+        let response = call(
+            "jwt.sign",
+            json!({ "sub": "acme", "aud": "device1", "exp": 60 }),
+        );
+        let token = response["result"].as_str().unwrap();
+        let response = call(
+            "jwt.verify",
+            json!({ "token": token, "secrets": ["other"] }),
+        );
+        assert_eq!(response["result"], false);
+
+        assert_eq!(call("nope", json!({}))["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(call("hash", json!({}))["error"]["code"], INVALID_PARAMS);
+        let response = call("hash", json!({ "input": "fixtures/missing.txt" }));
+        assert_eq!(response["error"]["code"], SERVER_ERROR);
+
+        let notification =
+            json!({ "jsonrpc": "2.0", "method": "encode", "params": { "data": "x" } });
+        assert!(process_api_request(&notification.to_string()).is_none());
+        let response: Value = serde_json::from_str(&process_api_request("{").unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+    }
+
+    #[test]
+    fn test_api_auth() {
+        let token: Secret = "s3cret".parse().unwrap();
+        let auth = |params: Value| {
+            let request = json!({ "jsonrpc": "2.0", "id": 0, "method": "auth", "params": params });
+            process_api_auth(&request.to_string(), &token)
+        };
+        let (response, authorized) = auth(json!({ "token": "s3cret" }));
+        assert!(authorized);
+        assert_eq!(
+            serde_json::from_str::<Value>(&response).unwrap()["result"],
+            true
+        );
+        let (response, authorized) = auth(json!({ "token": "guess" }));
+        assert!(!authorized);
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["error"]["code"], UNAUTHORIZED);
+
+        // methods can't be called before authenticating, nor can browsers post to the port
+        let request =
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "hash", "params": { "data": "x" } });
+        assert!(!process_api_auth(&request.to_string(), &token).1);
+        assert!(!process_api_auth("POST / HTTP/1.1", &token).1);
+    }
+}
@@ -0,0 +1,355 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Datelike, Local, Timelike};
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use serde::Serialize;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use super::http_archive::entry_name;
+use crate::{cli::ArchiveFormat, get_reader, get_writer, RcliError, Result};
+
+/// What went into or came out of an archive
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ArchiveSummary {
+    /// Files and directories
+    pub entries: usize,
+    /// Size of the files, uncompressed
+    pub bytes: u64,
+}
+
+/// Pack `input`, a directory or a single file, into `output` (`-` for stdout with tar
+/// formats). Entries are named after the input like `tar` does, so `-i ./site` extracts
+/// to `site/`. `on_progress` gets each entry as it's added, with the count so far and
+/// the total.
+pub fn process_archive_create(
+    input: &Path,
+    output: &str,
+    format: ArchiveFormat,
+    mut on_progress: impl FnMut(&Path, usize, usize),
+) -> Result<ArchiveSummary> {
+    let input = input.canonicalize().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => RcliError::FileNotFound(input.into()),
+        _ => e.into(),
+    })?;
+    let root = input.parent().unwrap_or(&input).to_path_buf();
+    let entries = collect_entries(&root, &input)?;
+    let total = entries.len();
+    let mut summary = ArchiveSummary::default();
+    let mut add = |rel: &Path| -> Result<()> {
+        summary.entries += 1;
+        if root.join(rel).is_file() {
+            summary.bytes += root.join(rel).metadata()?.len();
+        }
+        on_progress(rel, summary.entries, total);
+        Ok(())
+    };
+    match format {
+        ArchiveFormat::Tar => {
+            let mut tar = tar::Builder::new(get_writer(output)?);
+            append_tar(&mut tar, &root, &entries, &mut add)?;
+            tar.into_inner()?.flush()?;
+        }
+        ArchiveFormat::TarGz => {
+            let writer = GzEncoder::new(get_writer(output)?, Compression::default());
+            let mut tar = tar::Builder::new(writer);
+            append_tar(&mut tar, &root, &entries, &mut add)?;
+            tar.into_inner()?.finish()?.flush()?;
+        }
+        ArchiveFormat::Zip => {
+            if output == "-" {
+                return Err(RcliError::Unsupported(
+                    "zip archives can't be written to stdout, pass a file name".to_string(),
+                ));
+            }
+            let mut zip = ZipWriter::new(File::create(output)?);
+            for rel in &entries {
+                let path = root.join(rel);
+                let metadata = path.metadata()?;
+                let options = SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .large_file(metadata.len() >= u32::MAX as u64)
+                    .last_modified_time(zip_time(metadata.modified()?));
+                #[cfg(unix)]
+                let options = {
+                    use std::os::unix::fs::PermissionsExt;
+                    options.unix_permissions(metadata.permissions().mode())
+                };
+                if path.is_dir() {
+                    zip.add_directory(entry_name(rel), options)?;
+                } else {
+                    zip.start_file(entry_name(rel), options)?;
+                    io::copy(&mut File::open(&path)?, &mut zip)?;
+                }
+                add(rel)?;
+            }
+            zip.finish()?;
+        }
+    }
+    Ok(summary)
+}
+
+/// Unpack `input` (`-` for stdin with tar formats) under `out_dir`, which is created if
+/// missing. Entries that would land outside of it, through `..` or absolute paths, are
+/// refused. `on_progress` gets each entry once written, with the count so far and the
+/// total when the format tells it upfront.
+pub fn process_archive_extract(
+    input: &str,
+    out_dir: &Path,
+    format: ArchiveFormat,
+    mut on_progress: impl FnMut(&Path, usize, Option<usize>),
+) -> Result<ArchiveSummary> {
+    fs::create_dir_all(out_dir)?;
+    let mut summary = ArchiveSummary::default();
+    match format {
+        ArchiveFormat::Tar => {
+            extract_tar(get_reader(input)?, out_dir, &mut summary, on_progress)?;
+        }
+        ArchiveFormat::TarGz => {
+            let reader = MultiGzDecoder::new(get_reader(input)?);
+            extract_tar(reader, out_dir, &mut summary, on_progress)?;
+        }
+        ArchiveFormat::Zip => {
+            if input == "-" {
+                return Err(RcliError::Unsupported(
+                    "zip archives can't be read from stdin, pass a file name".to_string(),
+                ));
+            }
+            let file = File::open(input).map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => RcliError::FileNotFound(input.into()),
+                _ => e.into(),
+            })?;
+            let mut zip = ZipArchive::new(file)?;
+            let total = zip.len();
+            for i in 0..total {
+                let mut entry = zip.by_index(i)?;
+                let rel = entry
+                    .enclosed_name()
+                    .ok_or_else(|| unsafe_entry(entry.name()))?;
+                let path = out_dir.join(&rel);
+                if entry.is_dir() {
+                    fs::create_dir_all(&path)?;
+                } else {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    summary.bytes += io::copy(&mut entry, &mut File::create(&path)?)?;
+                }
+                #[cfg(unix)]
+                if let Some(mode) = entry.unix_mode() {
+                    use std::os::unix::fs::PermissionsExt;
+                    // setuid, setgid and sticky bits from an untrusted archive are dropped
+                    fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))?;
+                }
+                summary.entries += 1;
+                on_progress(&rel, summary.entries, Some(total));
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// `input` and everything under it, relative to `root` and sorted, parents first.
+/// Symlinks are stored as what they point to, but symlinked directories aren't
+/// descended into, so a link loop can't make the archive endless.
+fn collect_entries(root: &Path, input: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = Vec::new();
+    let mut pending = vec![input.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let rel = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        let is_dir = fs::symlink_metadata(&path)?.is_dir();
+        entries.push(rel);
+        if !is_dir {
+            continue;
+        }
+        let mut children = fs::read_dir(&path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        // popped from the end, so reversed to come out in name order
+        children.sort_by(|a, b| b.cmp(a));
+        pending.extend(children.into_iter().filter(|p| p.is_dir() || p.is_file()));
+    }
+    Ok(entries)
+}
+
+/// Zip stores local time to the second; times it can't hold (before 1980) become 1980-01-01
+fn zip_time(time: SystemTime) -> zip::DateTime {
+    let time = DateTime::<Local>::from(time);
+    zip::DateTime::from_date_and_time(
+        time.year().try_into().unwrap_or(0),
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )
+    .unwrap_or_default()
+}
+
+fn append_tar<W: Write>(
+    tar: &mut tar::Builder<W>,
+    root: &Path,
+    entries: &[PathBuf],
+    add: &mut impl FnMut(&Path) -> Result<()>,
+) -> Result<()> {
+    for rel in entries {
+        let path = root.join(rel);
+        if fs::symlink_metadata(&path)?.is_dir() {
+            tar.append_dir(rel, &path)?;
+        } else {
+            tar.append_path_with_name(&path, rel)?;
+        }
+        add(rel)?;
+    }
+    Ok(())
+}
+
+fn extract_tar(
+    reader: impl Read,
+    out_dir: &Path,
+    summary: &mut ArchiveSummary,
+    mut on_progress: impl FnMut(&Path, usize, Option<usize>),
+) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let rel = entry.path()?.into_owned();
+        let size = entry.header().entry_type().is_file().then(|| entry.size());
+        // `unpack_in` skips entries escaping out_dir rather than failing, report them instead
+        if !entry.unpack_in(out_dir)? {
+            return Err(unsafe_entry(&rel.to_string_lossy()));
+        }
+        summary.bytes += size.unwrap_or(0);
+        summary.entries += 1;
+        on_progress(&rel, summary.entries, None);
+    }
+    Ok(())
+}
+
+fn unsafe_entry(name: &str) -> RcliError {
+    RcliError::format(format!(
+        "Refusing to extract {}, it would be written outside of the output directory",
+        name
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_roundtrip() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join("rcli-archive");
+        let _ = fs::remove_dir_all(&dir);
+        let site = dir.join("site");
+        fs::create_dir_all(site.join("css"))?;
+        fs::create_dir_all(site.join("empty"))?;
+        fs::write(site.join("index.html"), "<html></html>")?;
+        fs::write(site.join("css/main.css"), "body{}")?;
+
+        for (format, name) in [
+            (ArchiveFormat::Tar, "site.tar"),
+            (ArchiveFormat::TarGz, "site.tar.gz"),
+            (ArchiveFormat::Zip, "site.zip"),
+        ] {
+            let archive = dir.join(name);
+            let mut added = Vec::new();
+            let created =
+                process_archive_create(&site, &archive.to_string_lossy(), format, |rel, _, _| {
+                    added.push(entry_name(rel))
+                })?;
+            assert_eq!(
+                added,
+                [
+                    "site",
+                    "site/css",
+                    "site/css/main.css",
+                    "site/empty",
+                    "site/index.html"
+                ]
+            );
+            assert_eq!(
+                created,
+                ArchiveSummary {
+                    entries: 5,
+                    bytes: 19
+                }
+            );
+
+            let out = dir.join(format!("out-{}", name));
+            let extracted =
+                process_archive_extract(&archive.to_string_lossy(), &out, format, |_, _, _| {})?;
+            assert_eq!(extracted, created, "{}", name);
+            assert_eq!(fs::read_to_string(out.join("site/css/main.css"))?, "body{}");
+            assert!(out.join("site/empty").is_dir());
+        }
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_extract_refuses_escaping_entries() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join("rcli-archive-escape");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let archive = dir.join("evil.zip");
+        let mut zip = ZipWriter::new(File::create(&archive)?);
+        zip.start_file("../evil.txt", SimpleFileOptions::default())?;
+        zip.write_all(b"gotcha")?;
+        zip.finish()?;
+
+        let out = dir.join("out");
+        let result = process_archive_extract(
+            &archive.to_string_lossy(),
+            &out,
+            ArchiveFormat::Zip,
+            |_, _, _| {},
+        );
+        assert!(matches!(result, Err(RcliError::Format(_))));
+        assert!(!dir.join("evil.txt").exists());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_archive_extract_masks_zip_modes() -> anyhow::Result<()> {
+        use std::{io::Cursor, os::unix::fs::PermissionsExt};
+
+        let dir = std::env::temp_dir().join("rcli-archive-mode");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let archive = dir.join("setuid.zip");
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file(
+            "run.sh",
+            SimpleFileOptions::default().unix_permissions(0o755),
+        )?;
+        zip.write_all(b"#!/bin/sh")?;
+        let mut data = zip.finish()?.into_inner();
+        // the writer masks the mode itself, so set the setuid bit in the central directory
+        // entry's external attributes, whose high half is the unix mode
+        let header = data
+            .windows(4)
+            .position(|w| w == b"PK\x01\x02")
+            .expect("central directory entry");
+        data[header + 40..header + 42].copy_from_slice(&0o104755u16.to_le_bytes());
+        fs::write(&archive, data)?;
+
+        let out = dir.join("out");
+        process_archive_extract(
+            &archive.to_string_lossy(),
+            &out,
+            ArchiveFormat::Zip,
+            |_, _, _| {},
+        )?;
+        let mode = fs::metadata(out.join("run.sh"))?.permissions().mode();
+        assert_eq!(mode & 0o7777, 0o755);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,40 +1,438 @@
 use base64::{
-    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    alphabet,
+    engine::{
+        general_purpose::{GeneralPurpose, STANDARD, URL_SAFE_NO_PAD},
+        DecodePaddingMode, GeneralPurposeConfig,
+    },
     Engine as _,
 };
+use serde::Serialize;
 
-use std::io::Read;
+use data_encoding::{BASE32, HEXLOWER_PERMISSIVE};
+use std::io::{Read, Write};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::{get_reader, Base64Format};
+use crate::{get_reader, timer, Base64Format, Input, RcliError, Result};
 
-pub fn process_encode(input: &str, format: Base64Format) -> anyhow::Result<String> {
+pub fn process_encode(input: impl Into<Input>, format: Base64Format) -> Result<String> {
+    let timer = timer("base64 encode");
     let mut reader = get_reader(input)?;
     let mut buf = Vec::new();
-    reader.read_to_end(&mut buf)?;
-
-    let encoded = match format {
-        Base64Format::Standard => STANDARD.encode(&buf),
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.encode(&buf),
-    };
+    timer.add_bytes(reader.read_to_end(&mut buf)? as u64);
+    Ok(encode_bytes(&buf, format))
+}
 
-    Ok(encoded)
+pub(super) fn encode_bytes(buf: &[u8], format: Base64Format) -> String {
+    match format {
+        Base64Format::Standard => STANDARD.encode(buf),
+        Base64Format::UrlSafe => URL_SAFE_NO_PAD.encode(buf),
+        Base64Format::Hex => HEXLOWER_PERMISSIVE.encode(buf),
+        Base64Format::Base32 => BASE32.encode(buf),
+        Base64Format::Base58 => bs58::encode(buf).into_string(),
+        Base64Format::Ascii85 => ascii85_encode(buf),
+    }
 }
 
-pub fn process_decode(input: &str, format: Base64Format) -> anyhow::Result<String> {
+pub fn process_decode(input: impl Into<Input>, format: Base64Format) -> Result<Vec<u8>> {
+    let timer = timer("base64 decode");
     let mut reader = get_reader(input)?;
     let mut buf = String::new();
-    reader.read_to_string(&mut buf)?;
+    timer.add_bytes(reader.read_to_string(&mut buf)? as u64);
+    decode_text(&buf, format)
+}
+
+pub(super) fn decode_text(buf: &str, format: Base64Format) -> Result<Vec<u8>> {
     let buf = buf.trim();
     let decoded = match format {
         Base64Format::Standard => STANDARD.decode(buf)?,
         Base64Format::UrlSafe => URL_SAFE_NO_PAD.decode(buf)?,
+        Base64Format::Hex => HEXLOWER_PERMISSIVE.decode(buf.as_bytes())?,
+        Base64Format::Base32 => BASE32.decode(buf.as_bytes())?,
+        Base64Format::Base58 => bs58::decode(buf).into_vec()?,
+        Base64Format::Ascii85 => ascii85_decode(buf)?,
     };
-    // TODO: decoded data might not be string(but for this example. we assume it is)
-    let decoded = String::from_utf8(decoded)?;
-
     Ok(decoded)
 }
 
+const STREAM_CHUNK: usize = 4096;
+
+#[derive(Debug, Serialize)]
+pub struct Base64Inspection {
+    pub alphabet: &'static str,
+    pub padding: &'static str,
+    pub encoded_len: usize,
+    pub decoded_len: Option<usize>,
+    pub content_type: Option<&'static str>,
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub preview: Vec<u8>,
+}
+
+/// Triage a base64 blob: guess its alphabet, validate padding, sniff the decoded content
+pub fn process_inspect(input: impl Into<Input>, preview: usize) -> Result<Base64Inspection> {
+    let mut reader = get_reader(input)?;
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    buf.retain(|b| !b.is_ascii_whitespace());
+
+    let has_standard = buf.iter().any(|b| matches!(b, b'+' | b'/'));
+    let has_urlsafe = buf.iter().any(|b| matches!(b, b'-' | b'_'));
+    let (alphabet_name, alphabet) = match (has_standard, has_urlsafe) {
+        (true, true) => ("mixed (invalid)", None),
+        (false, true) => ("urlsafe", Some(&alphabet::URL_SAFE)),
+        (true, false) => ("standard", Some(&alphabet::STANDARD)),
+        (false, false) => (
+            "standard/urlsafe (common subset)",
+            Some(&alphabet::STANDARD),
+        ),
+    };
+
+    let data_len = buf.iter().rposition(|b| *b != b'=').map_or(0, |i| i + 1);
+    let pad_len = buf.len() - data_len;
+    let padding = if buf[..data_len].contains(&b'=') || pad_len > 2 || data_len % 4 == 1 {
+        "invalid"
+    } else if pad_len == 0 {
+        if data_len % 4 == 0 {
+            "none needed"
+        } else {
+            "missing (unpadded)"
+        }
+    } else if buf.len() % 4 == 0 {
+        "valid"
+    } else {
+        "invalid"
+    };
+
+    let config = GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+        .with_decode_allow_trailing_bits(true);
+    let decoded = match alphabet {
+        Some(alphabet) => GeneralPurpose::new(alphabet, config)
+            .decode(&buf)
+            .map_err(|e| e.to_string()),
+        None => Err("both standard and urlsafe characters found".to_string()),
+    };
+    let (decoded_len, content_type, preview, error) = match decoded {
+        Ok(decoded) => (
+            Some(decoded.len()),
+            Some(sniff_content_type(&decoded)),
+            decoded[..preview.min(decoded.len())].to_vec(),
+            None,
+        ),
+        Err(e) => (None, None, Vec::new(), Some(e)),
+    };
+
+    Ok(Base64Inspection {
+        alphabet: alphabet_name,
+        padding,
+        encoded_len: buf.len(),
+        decoded_len,
+        content_type,
+        error,
+        preview,
+    })
+}
+
+fn sniff_content_type(data: &[u8]) -> &'static str {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"BZh", "application/x-bzip2"),
+        (b"\xfd7zXZ\x00", "application/x-xz"),
+        (b"\x28\xb5\x2f\xfd", "application/zstd"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"\x7fELF", "application/x-elf"),
+        (b"\x00asm", "application/wasm"),
+        (b"Salted__", "application/x-openssl-encrypted"),
+    ];
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return mime;
+    }
+    match std::str::from_utf8(data) {
+        Ok(text) if serde_json::from_str::<serde_json::Value>(text).is_ok() => "application/json",
+        Ok(_) => "text/plain",
+        Err(_) => "application/octet-stream",
+    }
+}
+
+/// Encode the reader into the writer in 4KB blocks, carrying partial 3-byte groups
+/// over to the next block. Optionally wrap the output at `wrap` columns (76 for MIME).
+/// Returns the number of encoded bytes written, excluding line breaks.
+pub fn process_encode_stream(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: Base64Format,
+    wrap: Option<usize>,
+) -> Result<u64> {
+    let timer = timer("base64 encode");
+    let mut encoder = StreamEncoder::new(format, wrap)?;
+    let mut buf = vec![0u8; STREAM_CHUNK];
+    loop {
+        let n = reader.read(&mut buf)?;
+        timer.add_bytes(n as u64);
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&encoder.update(&buf[..n]))?;
+    }
+    writer.write_all(&encoder.finish())?;
+    writer.flush()?;
+    Ok(encoder.written)
+}
+
+/// [`process_encode_stream`] on async IO, which leaves the runtime free between blocks
+pub async fn process_encode_stream_async(
+    reader: &mut (dyn AsyncRead + Unpin + Send),
+    writer: &mut (dyn AsyncWrite + Unpin + Send),
+    format: Base64Format,
+    wrap: Option<usize>,
+) -> Result<u64> {
+    let timer = timer("base64 encode");
+    let mut encoder = StreamEncoder::new(format, wrap)?;
+    let mut buf = vec![0u8; STREAM_CHUNK];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        timer.add_bytes(n as u64);
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&encoder.update(&buf[..n])).await?;
+    }
+    writer.write_all(&encoder.finish()).await?;
+    writer.flush().await?;
+    Ok(encoder.written)
+}
+
+/// Decode the reader into the writer in 4KB blocks, skipping whitespace (e.g. MIME
+/// line breaks) and carrying partial 4-char groups over to the next block.
+/// Returns the number of decoded bytes written.
+pub fn process_decode_stream(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: Base64Format,
+) -> Result<u64> {
+    let timer = timer("base64 decode");
+    let mut decoder = StreamDecoder::new(format)?;
+    let mut buf = vec![0u8; STREAM_CHUNK];
+    loop {
+        let n = reader.read(&mut buf)?;
+        timer.add_bytes(n as u64);
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&decoder.update(&buf[..n])?)?;
+    }
+    writer.write_all(&decoder.finish()?)?;
+    writer.flush()?;
+    Ok(decoder.written)
+}
+
+/// [`process_decode_stream`] on async IO
+pub async fn process_decode_stream_async(
+    reader: &mut (dyn AsyncRead + Unpin + Send),
+    writer: &mut (dyn AsyncWrite + Unpin + Send),
+    format: Base64Format,
+) -> Result<u64> {
+    let timer = timer("base64 decode");
+    let mut decoder = StreamDecoder::new(format)?;
+    let mut buf = vec![0u8; STREAM_CHUNK];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        timer.add_bytes(n as u64);
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&decoder.update(&buf[..n])?).await?;
+    }
+    writer.write_all(&decoder.finish()?).await?;
+    writer.flush().await?;
+    Ok(decoder.written)
+}
+
+fn stream_engine(format: Base64Format) -> Result<&'static base64::engine::GeneralPurpose> {
+    match format {
+        Base64Format::Standard => Ok(&STANDARD),
+        Base64Format::UrlSafe => Ok(&URL_SAFE_NO_PAD),
+        _ => Err(RcliError::Unsupported(format!(
+            "Streaming is not supported for {}",
+            format
+        ))),
+    }
+}
+
+/// Encoding state between blocks, independent of how the blocks are read
+struct StreamEncoder {
+    engine: &'static GeneralPurpose,
+    /// input bytes that don't fill a 3-byte group yet
+    carry: Vec<u8>,
+    wrap: Option<usize>,
+    column: usize,
+    /// encoded bytes, excluding line breaks
+    written: u64,
+}
+
+impl StreamEncoder {
+    fn new(format: Base64Format, wrap: Option<usize>) -> Result<Self> {
+        Ok(Self {
+            engine: stream_engine(format)?,
+            carry: Vec::with_capacity(STREAM_CHUNK + 2),
+            wrap: wrap.filter(|w| *w > 0),
+            column: 0,
+            written: 0,
+        })
+    }
+
+    fn update(&mut self, data: &[u8]) -> Vec<u8> {
+        self.carry.extend_from_slice(data);
+        let full = self.carry.len() - self.carry.len() % 3;
+        let encoded = self.engine.encode(&self.carry[..full]);
+        self.carry.drain(..full);
+        self.wrap_lines(encoded.as_bytes())
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        let encoded = self.engine.encode(&self.carry);
+        self.carry.clear();
+        let mut out = self.wrap_lines(encoded.as_bytes());
+        if self.column > 0 {
+            out.push(b'\n');
+            self.column = 0;
+        }
+        out
+    }
+
+    fn wrap_lines(&mut self, mut data: &[u8]) -> Vec<u8> {
+        self.written += data.len() as u64;
+        let Some(wrap) = self.wrap else {
+            return data.to_vec();
+        };
+        let mut out = Vec::with_capacity(data.len() + data.len() / wrap + 1);
+        while !data.is_empty() {
+            let n = (wrap - self.column).min(data.len());
+            out.extend_from_slice(&data[..n]);
+            self.column += n;
+            data = &data[n..];
+            if self.column == wrap {
+                out.push(b'\n');
+                self.column = 0;
+            }
+        }
+        out
+    }
+}
+
+/// Decoding state between blocks: characters that don't fill a 4-char group yet
+struct StreamDecoder {
+    engine: &'static GeneralPurpose,
+    pending: Vec<u8>,
+    /// decoded bytes
+    written: u64,
+}
+
+impl StreamDecoder {
+    fn new(format: Base64Format) -> Result<Self> {
+        Ok(Self {
+            engine: stream_engine(format)?,
+            pending: Vec::with_capacity(STREAM_CHUNK + 4),
+            written: 0,
+        })
+    }
+
+    fn update(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        self.pending
+            .extend(data.iter().filter(|b| !b.is_ascii_whitespace()));
+        let full = self.pending.len() - self.pending.len() % 4;
+        let decoded = self.engine.decode(&self.pending[..full])?;
+        self.pending.drain(..full);
+        self.written += decoded.len() as u64;
+        Ok(decoded)
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>> {
+        if self.pending.is_empty() {
+            return Ok(Vec::new());
+        }
+        let decoded = self.engine.decode(&self.pending)?;
+        self.pending.clear();
+        self.written += decoded.len() as u64;
+        Ok(decoded)
+    }
+}
+
+fn ascii85_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len() * 5 / 4 + 4);
+    for chunk in data.chunks(4) {
+        let mut group = [0u8; 4];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(group);
+        // a full group of zeros is abbreviated as 'z'
+        if value == 0 && chunk.len() == 4 {
+            out.push('z');
+            continue;
+        }
+        let mut digits = [0u8; 5];
+        for d in digits.iter_mut().rev() {
+            *d = (value % 85) as u8 + b'!';
+            value /= 85;
+        }
+        for d in &digits[..chunk.len() + 1] {
+            out.push(*d as char);
+        }
+    }
+    out
+}
+
+fn ascii85_decode(data: &str) -> Result<Vec<u8>> {
+    let data = data.trim();
+    let data = data.strip_prefix("<~").unwrap_or(data);
+    let data = data.strip_suffix("~>").unwrap_or(data);
+    let mut out = Vec::with_capacity(data.len() * 4 / 5);
+    let mut group = Vec::with_capacity(5);
+    for c in data.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        match c {
+            b'z' if group.is_empty() => out.extend_from_slice(&[0u8; 4]),
+            b'!'..=b'u' => {
+                group.push(c - b'!');
+                if group.len() == 5 {
+                    out.extend_from_slice(&ascii85_group(&group)?);
+                    group.clear();
+                }
+            }
+            _ => {
+                return Err(RcliError::format(format!(
+                    "Invalid ascii85 character: {}",
+                    c as char
+                )))
+            }
+        }
+    }
+    if !group.is_empty() {
+        if group.len() == 1 {
+            return Err(RcliError::format(
+                "Invalid ascii85 data: dangling character",
+            ));
+        }
+        // pad the last partial group with 'u' and drop the extra bytes
+        let n = group.len() - 1;
+        group.resize(5, b'u' - b'!');
+        out.extend_from_slice(&ascii85_group(&group)?[..n]);
+    }
+    Ok(out)
+}
+
+fn ascii85_group(group: &[u8]) -> Result<[u8; 4]> {
+    let value = group
+        .iter()
+        .try_fold(0u32, |acc, d| acc.checked_mul(85)?.checked_add(*d as u32))
+        .ok_or_else(|| RcliError::format("Invalid ascii85 data: group overflow"))?;
+    Ok(value.to_be_bytes())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -52,4 +450,112 @@ mod tests {
         let format = Base64Format::UrlSafe;
         process_decode(input, format).unwrap();
     }
+
+    #[test]
+    fn test_process_inspect() -> anyhow::Result<()> {
+        let inspection = process_inspect("fixtures/b64.txt", 16)?;
+        assert_eq!(inspection.alphabet, "standard/urlsafe (common subset)");
+        assert_eq!(inspection.padding, "missing (unpadded)");
+        assert!(inspection.error.is_none());
+        assert_eq!(inspection.content_type, Some("text/plain"));
+        assert_eq!(inspection.preview.len(), 16);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sniff_content_type() {
+        assert_eq!(sniff_content_type(b"\x89PNG\r\n\x1a\n...."), "image/png");
+        assert_eq!(sniff_content_type(b"{\"a\":1}"), "application/json");
+        assert_eq!(
+            sniff_content_type(&[0xff, 0x00, 0xfe]),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn test_process_stream_roundtrip() -> anyhow::Result<()> {
+        // larger than a single chunk and not a multiple of 3
+        let data: Vec<u8> = (0..STREAM_CHUNK * 3 + 7).map(|i| (i % 251) as u8).collect();
+        for format in [Base64Format::Standard, Base64Format::UrlSafe] {
+            for wrap in [None, Some(76)] {
+                let mut encoded = Vec::new();
+                process_encode_stream(&mut &data[..], &mut encoded, format, wrap)?;
+                if wrap.is_some() {
+                    let text = String::from_utf8(encoded.clone())?;
+                    assert!(text.lines().all(|l| l.len() <= 76));
+                } else {
+                    assert_eq!(encoded, stream_engine(format)?.encode(&data).into_bytes());
+                }
+                let mut decoded = Vec::new();
+                process_decode_stream(&mut &encoded[..], &mut decoded, format)?;
+                assert_eq!(decoded, data);
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_stream_async() -> anyhow::Result<()> {
+        let data: Vec<u8> = (0..STREAM_CHUNK * 2 + 5).map(|i| (i % 251) as u8).collect();
+        let mut expected = Vec::new();
+        process_encode_stream(
+            &mut &data[..],
+            &mut expected,
+            Base64Format::Standard,
+            Some(76),
+        )?;
+        let mut encoded = Vec::new();
+        process_encode_stream_async(
+            &mut &data[..],
+            &mut encoded,
+            Base64Format::Standard,
+            Some(76),
+        )
+        .await?;
+        assert_eq!(encoded, expected);
+        let mut decoded = Vec::new();
+        process_decode_stream_async(&mut &encoded[..], &mut decoded, Base64Format::Standard)
+            .await?;
+        assert_eq!(decoded, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ascii85_roundtrip() -> anyhow::Result<()> {
+        assert_eq!(ascii85_encode(b"Man "), "9jqo^");
+        assert_eq!(ascii85_encode(&[0, 0, 0, 0, 1]), "z!<");
+        for data in [&b""[..], b"h", b"he", b"hel", b"hello world", &[0, 0, 0, 0]] {
+            assert_eq!(ascii85_decode(&ascii85_encode(data))?, data);
+        }
+        assert_eq!(ascii85_decode("<~9jqo^~>")?, b"Man ");
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_encode_decode_formats() -> anyhow::Result<()> {
+        let input = "fixtures/ed25519.pk";
+        let expected = std::fs::read(input)?;
+        for format in [
+            Base64Format::Hex,
+            Base64Format::Base32,
+            Base64Format::Base58,
+            Base64Format::Ascii85,
+        ] {
+            let encoded = process_encode(input, format)?;
+            let path = std::env::temp_dir().join(format!("rcli-b64-{}.txt", format));
+            std::fs::write(&path, encoded)?;
+            let decoded = process_decode(path.to_str().unwrap(), format)?;
+            assert_eq!(decoded, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_encode_decode_binary() -> anyhow::Result<()> {
+        let input = "fixtures/ed25519.sk";
+        let encoded = process_encode(input, Base64Format::Standard)?;
+        let decoded = STANDARD.decode(encoded)?;
+        assert_eq!(decoded, std::fs::read(input)?);
+        Ok(())
+    }
 }
@@ -0,0 +1,266 @@
+use std::fmt::Display;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use data_encoding::HEXLOWER;
+use serde_json::Value;
+
+use super::{
+    b64::{decode_text, encode_bytes},
+    hash::hash_reader,
+    hex::revert_dump,
+    json::{format_json, select_path},
+    text::{canonicalize, sign_reader},
+};
+use crate::{
+    hexdump, process_encode_stream, process_genpass, process_uuid, Base64Format, Canonicalize,
+    HashAlgorithm, PasswordCharset, RcliError, Result, TextSignFormat, UuidVersion,
+};
+
+/// What one step of a chain hands to the next
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainValue {
+    Bytes(Vec<u8>),
+    Text(String),
+    Json(Value),
+}
+
+/// One operation of a chain, with the options of the rcli command it stands for
+#[derive(Debug, Clone)]
+pub enum ChainStep {
+    GenPass {
+        length: u8,
+        charset: PasswordCharset,
+    },
+    Uuid {
+        version: UuidVersion,
+        count: usize,
+        upper: bool,
+    },
+    Base64Encode {
+        format: Base64Format,
+        wrap: Option<usize>,
+    },
+    Base64Decode(Base64Format),
+    Hash(HashAlgorithm),
+    HexDump {
+        offset: u64,
+        length: Option<u64>,
+    },
+    HexRevert,
+    JsonFmt {
+        minify: bool,
+        indent: usize,
+    },
+    JsonGet {
+        path: String,
+        raw: bool,
+    },
+    TextSign {
+        key: String,
+        format: TextSignFormat,
+        canonicalize: Vec<Canonicalize>,
+        binary: bool,
+        hex: bool,
+    },
+}
+
+impl ChainValue {
+    pub fn into_bytes(self) -> Result<Vec<u8>> {
+        Ok(match self {
+            ChainValue::Bytes(data) => data,
+            ChainValue::Text(text) => text.into_bytes(),
+            ChainValue::Json(value) => serde_json::to_vec(&value)?,
+        })
+    }
+
+    pub fn into_text(self) -> Result<String> {
+        match self {
+            ChainValue::Bytes(data) => String::from_utf8(data)
+                .map_err(|_| RcliError::format("Expected text, got binary data")),
+            ChainValue::Text(text) => Ok(text),
+            ChainValue::Json(value) => Ok(serde_json::to_string_pretty(&value)?),
+        }
+    }
+
+    pub fn into_json(self) -> Result<Value> {
+        match self {
+            ChainValue::Bytes(data) => Ok(serde_json::from_slice(&data)?),
+            ChainValue::Text(text) => Ok(serde_json::from_str(&text)?),
+            ChainValue::Json(value) => Ok(value),
+        }
+    }
+}
+
+impl ChainStep {
+    /// Generators start a chain, every other step transforms what the previous one produced
+    pub fn takes_input(&self) -> bool {
+        !matches!(self, ChainStep::GenPass { .. } | ChainStep::Uuid { .. })
+    }
+
+    fn apply(&self, input: Option<ChainValue>) -> Result<ChainValue> {
+        let input = match (self.takes_input(), input) {
+            (true, Some(input)) => input,
+            (false, None) => ChainValue::Bytes(Vec::new()),
+            (true, None) => {
+                return Err(RcliError::Unsupported(format!("{} needs an input", self)));
+            }
+            (false, Some(_)) => {
+                return Err(RcliError::Unsupported(format!(
+                    "{} takes no input, it can only start a chain",
+                    self
+                )));
+            }
+        };
+        let value = match self {
+            ChainStep::GenPass { length, charset } => {
+                ChainValue::Text(process_genpass(*length, charset)?)
+            }
+            ChainStep::Uuid {
+                version,
+                count,
+                upper,
+            } => {
+                let uuids: Vec<_> = process_uuid(*version, *count)
+                    .iter()
+                    .map(|uuid| match upper {
+                        true => uuid.hyphenated().to_string().to_uppercase(),
+                        false => uuid.hyphenated().to_string(),
+                    })
+                    .collect();
+                ChainValue::Text(uuids.join("\n"))
+            }
+            ChainStep::Base64Encode { format, wrap } => {
+                let data = input.into_bytes()?;
+                if format.is_streamable() {
+                    let mut encoded = Vec::new();
+                    process_encode_stream(&mut data.as_slice(), &mut encoded, *format, *wrap)?;
+                    ChainValue::Text(String::from_utf8(encoded)?)
+                } else {
+                    ChainValue::Text(encode_bytes(&data, *format))
+                }
+            }
+            ChainStep::Base64Decode(format) => {
+                ChainValue::Bytes(decode_text(&input.into_text()?, *format)?)
+            }
+            ChainStep::Hash(algorithm) => {
+                let data = input.into_bytes()?;
+                ChainValue::Text(hash_reader(&mut data.as_slice(), *algorithm)?)
+            }
+            ChainStep::HexDump { offset, length } => {
+                let data = input.into_bytes()?;
+                let start = (*offset as usize).min(data.len());
+                let end = match length {
+                    Some(length) => (start + *length as usize).min(data.len()),
+                    None => data.len(),
+                };
+                ChainValue::Text(hexdump(&data[start..end], start))
+            }
+            ChainStep::HexRevert => ChainValue::Bytes(revert_dump(&input.into_text()?)?),
+            ChainStep::JsonFmt { minify, indent } => {
+                ChainValue::Text(format_json(&input.into_json()?, *minify, *indent)?)
+            }
+            ChainStep::JsonGet { path, raw } => {
+                let mut values = select_path(&input.into_json()?, path)?;
+                match (values.len(), raw) {
+                    (0, _) => {
+                        return Err(RcliError::NotFound(format!("No value at path {}", path)))
+                    }
+                    (1, true) if values[0].is_string() => match values.remove(0) {
+                        Value::String(s) => ChainValue::Text(s),
+                        _ => unreachable!("checked to be a string"),
+                    },
+                    (1, _) => ChainValue::Json(values.remove(0)),
+                    _ => ChainValue::Json(Value::Array(values)),
+                }
+            }
+            ChainStep::TextSign {
+                key,
+                format,
+                canonicalize: steps,
+                binary,
+                hex,
+            } => {
+                let data = canonicalize(input.into_bytes()?, steps)?;
+                let signature = sign_reader(&mut data.as_slice(), key, *format)?;
+                match (binary, hex) {
+                    (true, _) => ChainValue::Bytes(signature),
+                    (_, true) => ChainValue::Text(HEXLOWER.encode(&signature)),
+                    _ => ChainValue::Text(URL_SAFE_NO_PAD.encode(&signature)),
+                }
+            }
+        };
+        Ok(value)
+    }
+}
+
+impl Display for ChainStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ChainStep::GenPass { .. } => "genpass",
+            ChainStep::Uuid { .. } => "uuid",
+            ChainStep::Base64Encode { .. } => "base64 encode",
+            ChainStep::Base64Decode(_) => "base64 decode",
+            ChainStep::Hash(_) => "hash",
+            ChainStep::HexDump { .. } => "hex dump",
+            ChainStep::HexRevert => "hex revert",
+            ChainStep::JsonFmt { .. } => "json fmt",
+            ChainStep::JsonGet { .. } => "json get",
+            ChainStep::TextSign { .. } => "text sign",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Run the steps in order, each one fed with the value the previous one produced
+pub fn process_chain(steps: &[ChainStep], input: Option<ChainValue>) -> Result<ChainValue> {
+    let mut value = input;
+    for (n, step) in steps.iter().enumerate() {
+        let result = step
+            .apply(value.take())
+            .map_err(|e| RcliError::format(format!("Step {} ({}): {}", n + 1, step, e)))?;
+        value = Some(result);
+    }
+    value.ok_or_else(|| RcliError::Unsupported("A chain needs at least one step".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_chain() -> Result<()> {
+        let genpass = ChainStep::GenPass {
+            length: 32,
+            charset: PasswordCharset::new(true, true, true, false),
+        };
+        let encode = ChainStep::Base64Encode {
+            format: Base64Format::UrlSafe,
+            wrap: None,
+        };
+        let decode = ChainStep::Base64Decode(Base64Format::UrlSafe);
+        let password = process_chain(&[genpass.clone(), encode.clone(), decode], None)?;
+        assert_eq!(password.into_text()?.len(), 32);
+
+        let input = ChainValue::Text(r#"{"user": {"name": "alice"}}"#.to_string());
+        let get = ChainStep::JsonGet {
+            path: ".user.name".to_string(),
+            raw: true,
+        };
+        let value = process_chain(&[get, ChainStep::Hash(HashAlgorithm::Sha256)], Some(input))?;
+        assert_eq!(
+            value,
+            ChainValue::Text(
+                "2bd806c97f0e00af1a1fc3328fa763a9269723c8db8fac4f93af71db186d6e90".to_string()
+            )
+        );
+
+        // binary values are never silently turned into text
+        let input = ChainValue::Bytes(vec![0xff, 0xfe]);
+        assert!(process_chain(&[ChainStep::HexRevert], Some(input)).is_err());
+        assert!(
+            process_chain(&[encode.clone(), genpass], Some(ChainValue::Bytes(vec![]))).is_err()
+        );
+        assert!(process_chain(&[encode], None).is_err());
+        Ok(())
+    }
+}
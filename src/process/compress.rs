@@ -0,0 +1,134 @@
+use std::io::{self, Read, Write};
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+
+use crate::{cli::CompressAlgorithm, timer, RcliError, Result};
+
+const BROTLI_BUFFER: usize = 64 * 1024;
+/// Window size used by the `brotli` command line tool, 4MB
+const BROTLI_WINDOW: u32 = 22;
+
+/// Compress the reader into the writer, at the algorithm's default level when `level` is
+/// `None`. Returns the number of uncompressed bytes read.
+pub fn process_compress(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    algorithm: CompressAlgorithm,
+    level: Option<i32>,
+) -> Result<u64> {
+    let level = level.unwrap_or(algorithm.default_level());
+    let range = algorithm.levels();
+    if !range.contains(&level) {
+        return Err(RcliError::Unsupported(format!(
+            "{} levels go from {} to {}, got {}",
+            algorithm,
+            range.start(),
+            range.end(),
+            level
+        )));
+    }
+    let timer = timer(match algorithm {
+        CompressAlgorithm::Gzip => "gzip compress",
+        CompressAlgorithm::Zstd => "zstd compress",
+        CompressAlgorithm::Brotli => "brotli compress",
+    });
+    let mut reader = timer.reader(reader);
+    let read = match algorithm {
+        CompressAlgorithm::Gzip => {
+            let mut encoder = GzEncoder::new(writer, Compression::new(level as u32));
+            let read = io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?.flush()?;
+            read
+        }
+        CompressAlgorithm::Zstd => {
+            let mut encoder = zstd::Encoder::new(writer, level)?;
+            let read = io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?.flush()?;
+            read
+        }
+        CompressAlgorithm::Brotli => {
+            let mut encoder =
+                brotli::CompressorWriter::new(writer, BROTLI_BUFFER, level as u32, BROTLI_WINDOW);
+            let read = io::copy(&mut reader, &mut encoder)?;
+            // into_inner ends the stream but swallows write errors, flushing first surfaces them
+            encoder.flush()?;
+            encoder.into_inner().flush()?;
+            read
+        }
+    };
+    Ok(read)
+}
+
+/// Decompress the reader into the writer. Returns the number of decompressed bytes written.
+pub fn process_decompress(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    algorithm: CompressAlgorithm,
+) -> Result<u64> {
+    let timer = timer(match algorithm {
+        CompressAlgorithm::Gzip => "gzip decompress",
+        CompressAlgorithm::Zstd => "zstd decompress",
+        CompressAlgorithm::Brotli => "brotli decompress",
+    });
+    let reader = timer.reader(reader);
+    let written = match algorithm {
+        CompressAlgorithm::Gzip => io::copy(&mut MultiGzDecoder::new(reader), writer),
+        CompressAlgorithm::Zstd => io::copy(&mut zstd::Decoder::new(reader)?, writer),
+        CompressAlgorithm::Brotli => io::copy(
+            &mut brotli::Decompressor::new(reader, BROTLI_BUFFER),
+            writer,
+        ),
+    }
+    // corrupt or truncated input surfaces as an io error from the decoder
+    .map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidData
+        | io::ErrorKind::InvalidInput
+        | io::ErrorKind::UnexpectedEof
+        | io::ErrorKind::Other => RcliError::format(format!("Invalid {} data: {}", algorithm, e)),
+        _ => e.into(),
+    })?;
+    writer.flush()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_roundtrip() -> anyhow::Result<()> {
+        let data = "rcli compress | rcli base64 encode\n".repeat(1000);
+        for algorithm in [
+            CompressAlgorithm::Gzip,
+            CompressAlgorithm::Zstd,
+            CompressAlgorithm::Brotli,
+        ] {
+            let mut compressed = Vec::new();
+            let read = process_compress(&mut data.as_bytes(), &mut compressed, algorithm, None)?;
+            assert_eq!(read, data.len() as u64);
+            assert!(compressed.len() < data.len() / 10, "{}", algorithm);
+
+            let mut decompressed = Vec::new();
+            process_decompress(&mut &compressed[..], &mut decompressed, algorithm)?;
+            assert_eq!(decompressed, data.as_bytes(), "{}", algorithm);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_compress_rejects_bad_levels_and_data() {
+        let result = process_compress(
+            &mut &b"data"[..],
+            &mut Vec::new(),
+            CompressAlgorithm::Gzip,
+            Some(10),
+        );
+        assert!(matches!(result, Err(RcliError::Unsupported(_))));
+        let result = process_decompress(
+            &mut &b"not gzip"[..],
+            &mut Vec::new(),
+            CompressAlgorithm::Gzip,
+        );
+        assert!(matches!(result, Err(RcliError::Format(_))));
+    }
+}
@@ -1,12 +1,27 @@
-use std::fs;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
 
-use csv::Reader;
+use csv::{Reader, ReaderBuilder, StringRecord, Writer};
+use rayon::prelude::*;
+use rust_xlsxwriter::{Format, Workbook};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use tokio::task::JoinSet;
 
-use crate::cli::OutputFormat;
+use super::csv_schema::infer_columns;
+use crate::cli::{JoinKind, OutputFormat};
+use crate::{get_reader, get_writer, timer, ColumnType, CsvSort, CsvTypes, RcliError, Result};
+
+/// Records converted per round when `jobs` > 1, enough to keep every thread busy
+/// without holding much of a huge input in memory
+const PARALLEL_BATCH: usize = 8192;
 
 // Name,Position,DOB,Nationality,Kit Number
+#[allow(dead_code)]
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 struct Player {
@@ -19,20 +34,877 @@ struct Player {
     kit: u8,
 }
 
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> anyhow::Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let headers = reader.headers()?.clone();
+/// How the input CSV is laid out
+#[derive(Debug, Clone, Copy)]
+pub struct CsvDialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    /// Accept records with more or fewer fields than the header
+    pub flexible: bool,
+    /// Without a header row columns are named `col_0`, `col_1`, ...
+    pub has_headers: bool,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            flexible: false,
+            has_headers: true,
+        }
+    }
+}
+
+impl CsvDialect {
+    pub(super) fn builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .flexible(self.flexible)
+            .has_headers(self.has_headers);
+        builder
+    }
+
+    fn reader(&self, path: &str) -> Result<Reader<fs::File>> {
+        Ok(self.builder().from_path(path)?)
+    }
+
+    fn headers(&self, reader: &mut Reader<fs::File>) -> Result<StringRecord> {
+        // without a header row this is the first record, which is still yielded as data
+        let first = reader.headers()?;
+        if self.has_headers {
+            return Ok(first.clone());
+        }
+        Ok((0..first.len()).map(|i| format!("col_{}", i)).collect())
+    }
+}
+
+/// A small reference table kept in memory to enrich a streamed CSV by key
+#[derive(Debug)]
+pub struct CsvLookup {
+    key: String,
+    append: Vec<String>,
+    rows: HashMap<String, Vec<String>>,
+}
+
+impl CsvLookup {
+    /// Load `path`, keeping only the `append` columns of every row indexed by `key`
+    pub fn load(path: &str, key: &str, append: &[String]) -> Result<Self> {
+        let mut reader = Reader::from_path(path)?;
+        let headers = reader.headers()?.clone();
+        let key_index = column_index(&headers, key, path)?;
+        let indices = append
+            .iter()
+            .map(|col| column_index(&headers, col, path))
+            .collect::<Result<Vec<_>>>()?;
+        let mut rows = HashMap::new();
+        for record in reader.records() {
+            let record = record?;
+            let values = indices
+                .iter()
+                .map(|&i| record.get(i).unwrap_or_default().to_string())
+                .collect();
+            // the first row wins on duplicate keys
+            rows.entry(record.get(key_index).unwrap_or_default().to_string())
+                .or_insert(values);
+        }
+        Ok(Self {
+            key: key.to_string(),
+            append: append.to_vec(),
+            rows,
+        })
+    }
+
+    fn extend_headers(&self, headers: &StringRecord) -> StringRecord {
+        let mut headers = headers.clone();
+        headers.extend(self.append.iter());
+        headers
+    }
+
+    /// Append the matching columns, or empty fields when the key has no match
+    fn enrich(&self, mut record: StringRecord, key_index: usize) -> StringRecord {
+        match self.rows.get(record.get(key_index).unwrap_or_default()) {
+            Some(values) => record.extend(values.iter()),
+            None => record.extend(self.append.iter().map(|_| "")),
+        }
+        record
+    }
+}
+
+fn column_index(headers: &StringRecord, column: &str, path: &str) -> Result<usize> {
+    headers
+        .iter()
+        .position(|h| h == column)
+        .ok_or_else(|| RcliError::NotFound(format!("Column {} in {}", column, path)))
+}
+
+/// Files matched by `input` when it is a glob pattern, in path order, or `input` itself
+fn expand_inputs(input: &str) -> Result<Vec<String>> {
+    if !input.contains(['*', '?', '[']) {
+        return Ok(vec![input.to_string()]);
+    }
+    let mut paths = glob::glob(input)?
+        .map(|p| p.map(|p| p.to_string_lossy().to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if paths.is_empty() {
+        return Err(RcliError::NotFound(format!("CSV file matching {}", input)));
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Convert `input`, a file or a glob pattern whose files share the same header, into
+/// `output`. `source_column` names an extra column holding the file each row came from.
+/// With `jobs` > 1, JSON, NDJSON and YAML rows are serialized on that many threads.
+/// `sort` orders and deduplicates the rows, after the lookup and source column are added.
+#[allow(clippy::too_many_arguments)]
+pub fn process_csv(
+    input: &str,
+    output: String,
+    format: OutputFormat,
+    dialect: &CsvDialect,
+    lookup: Option<&CsvLookup>,
+    source_column: Option<&str>,
+    types: Option<&CsvTypes>,
+    jobs: usize,
+    sort: Option<&CsvSort>,
+) -> Result<usize> {
+    let timer = timer("csv convert");
+    let mut readers = Vec::new();
+    let mut headers: Option<StringRecord> = None;
+    for path in expand_inputs(input)? {
+        let mut reader = dialect.reader(&path)?;
+        timer.add_bytes(fs::metadata(&path).map_or(0, |m| m.len()));
+        let file_headers = dialect.headers(&mut reader)?;
+        match &headers {
+            Some(headers) if *headers != file_headers => {
+                return Err(RcliError::format(format!(
+                    "{}: header differs from the first file",
+                    path
+                )))
+            }
+            Some(_) => {}
+            None => headers = Some(file_headers),
+        }
+        readers.push((path, reader));
+    }
+    let mut headers = headers.expect("at least one input file");
+    let lookup = match lookup {
+        Some(lookup) => {
+            let key_index = column_index(&headers, &lookup.key, input)?;
+            Some((lookup, key_index))
+        }
+        None => None,
+    };
+    if let Some(column) = source_column {
+        headers.push_field(column);
+    }
+    if let Some((lookup, _)) = lookup {
+        headers = lookup.extend_headers(&headers);
+    }
+    let records = readers.into_iter().flat_map(|(path, reader)| {
+        reader.into_records().map(move |record| {
+            let mut record = record?;
+            if source_column.is_some() {
+                record.push_field(&path);
+            }
+            Ok::<_, RcliError>(record)
+        })
+    });
+    let records = records.map(|record| {
+        let record = record?;
+        Ok::<_, RcliError>(match lookup {
+            Some((lookup, key_index)) => lookup.enrich(record, key_index),
+            None => record,
+        })
+    });
+    let mut records: Box<dyn Iterator<Item = Result<StringRecord>>> = Box::new(records);
+    if let Some(sort) = sort {
+        records = sort.apply(&headers, records)?;
+    }
+    let column_types = match types {
+        Some(types)
+            if matches!(
+                format,
+                OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Yaml
+            ) =>
+        {
+            // inference needs every row before the first one can be written
+            let rows = records.collect::<Result<Vec<_>>>()?;
+            let column_types = types.resolve(&headers, &rows)?;
+            records = Box::new(rows.into_iter().map(Ok));
+            Some(column_types)
+        }
+        _ => None,
+    };
+    match format {
+        OutputFormat::Csv => {
+            // rows are written as they are read so huge inputs never sit in memory
+            let mut writer = Writer::from_path(output)?;
+            writer.write_record(&headers)?;
+            let mut rows = 0;
+            for record in records {
+                writer.write_record(&record?)?;
+                rows += 1;
+            }
+            writer.flush()?;
+            return Ok(rows);
+        }
+        OutputFormat::Ndjson | OutputFormat::Json | OutputFormat::Yaml if jobs > 1 => {
+            return write_parallel(
+                &headers,
+                records,
+                column_types.as_deref(),
+                format,
+                &output,
+                jobs,
+            );
+        }
+        OutputFormat::Ndjson => {
+            let mut writer = BufWriter::new(fs::File::create(output)?);
+            let mut rows = 0;
+            for record in records {
+                let json_value = record_to_json(&headers, &record?, column_types.as_deref())?;
+                serde_json::to_writer(&mut writer, &json_value)?;
+                writer.write_all(b"\n")?;
+                rows += 1;
+            }
+            writer.flush()?;
+            return Ok(rows);
+        }
+        OutputFormat::Xlsx => {
+            let records = records.collect::<Result<Vec<_>>>()?;
+            write_xlsx(&headers, &records, &output)?;
+            return Ok(records.len());
+        }
+        OutputFormat::Sql => {
+            let records = records.collect::<Result<Vec<_>>>()?;
+            write_sql(&headers, &records, &output)?;
+            return Ok(records.len());
+        }
+        _ => {}
+    }
     let mut ret = Vec::with_capacity(128);
-    for result in reader.records() {
-        let record = result?;
-        let json_value: Value = headers.iter().zip(record.iter()).collect::<Value>();
-        ret.push(json_value);
+    for record in records {
+        ret.push(record_to_json(&headers, &record?, column_types.as_deref())?);
     }
 
     let content = match format {
         OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
         OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
+        OutputFormat::Csv | OutputFormat::Ndjson | OutputFormat::Xlsx | OutputFormat::Sql => {
+            unreachable!("written while streaming")
+        }
     };
     fs::write(output, content)?; //=> ()
+    Ok(ret.len())
+}
+
+/// Serialize rows in batches on a pool of `jobs` threads, writing every batch in input
+/// order before reading the next. The output is the same as the single-threaded one.
+fn write_parallel(
+    headers: &StringRecord,
+    records: impl Iterator<Item = Result<StringRecord>>,
+    types: Option<&[ColumnType]>,
+    format: OutputFormat,
+    output: &str,
+    jobs: usize,
+) -> Result<usize> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(|e| RcliError::Unsupported(format!("Can't start {} threads: {}", jobs, e)))?;
+    let serialize = |record: &StringRecord| -> Result<String> {
+        let value = record_to_json(headers, record, types)?;
+        Ok(match format {
+            OutputFormat::Ndjson => serde_json::to_string(&value)?,
+            // each row as an element of the array `to_string_pretty` would produce
+            OutputFormat::Json => indent(&serde_json::to_string_pretty(&value)?, "  ", "  "),
+            _ => indent(serde_yaml::to_string(&value)?.trim_end(), "- ", "  "),
+        })
+    };
+    let mut writer = BufWriter::new(fs::File::create(output)?);
+    let separator = match format {
+        OutputFormat::Json => ",\n",
+        _ => "\n",
+    };
+    let mut records = records.peekable();
+    let mut rows = 0;
+    while records.peek().is_some() {
+        let batch = records
+            .by_ref()
+            .take(PARALLEL_BATCH)
+            .collect::<Result<Vec<_>>>()?;
+        let lines = pool.install(|| batch.par_iter().map(serialize).collect::<Result<Vec<_>>>())?;
+        for line in lines {
+            match (rows, format) {
+                (0, OutputFormat::Json) => writer.write_all(b"[\n")?,
+                (0, _) => {}
+                _ => writer.write_all(separator.as_bytes())?,
+            }
+            writer.write_all(line.as_bytes())?;
+            rows += 1;
+        }
+    }
+    match (rows, format) {
+        (0, OutputFormat::Json) => writer.write_all(b"[]")?,
+        (0, OutputFormat::Yaml) => writer.write_all(b"[]\n")?,
+        (_, OutputFormat::Json) => writer.write_all(b"\n]")?,
+        (0, _) => {}
+        _ => writer.write_all(b"\n")?,
+    }
+    writer.flush()?;
+    Ok(rows)
+}
+
+/// Prefix the first line with `first` and the others with `rest`
+fn indent(text: &str, first: &str, rest: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 16);
+    for (i, line) in text.lines().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(if i == 0 { first } else { rest });
+        out.push_str(line);
+    }
+    out
+}
+
+/// A row as a JSON object, of strings unless the column types are known
+pub(super) fn record_to_json(
+    headers: &StringRecord,
+    record: &StringRecord,
+    types: Option<&[ColumnType]>,
+) -> Result<Value> {
+    let Some(types) = types else {
+        return Ok(headers.iter().zip(record.iter()).collect::<Value>());
+    };
+    let mut row = serde_json::Map::new();
+    for ((name, value), ty) in headers.iter().zip(record.iter()).zip(types) {
+        let value = ty.json_value(value).ok_or_else(|| {
+            RcliError::format(format!("Column {}: {:?} is not a {:?}", name, value, ty))
+        })?;
+        row.insert(name.to_string(), value);
+    }
+    Ok(Value::Object(row))
+}
+
+#[derive(Debug)]
+pub struct CsvConvertResult {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub rows: usize,
+    pub error: Option<String>,
+}
+
+/// Convert every CSV under `dir` concurrently into `out_dir`, mirroring the directory layout.
+/// `on_progress` is called as each file finishes, with the number of files done so far.
+pub async fn process_csv_convert_dir(
+    dir: &Path,
+    out_dir: &Path,
+    format: OutputFormat,
+    on_progress: impl Fn(&CsvConvertResult, usize, usize),
+) -> Result<Vec<CsvConvertResult>> {
+    let pattern = dir.join("**").join("*.csv");
+    let inputs = glob::glob(&pattern.to_string_lossy())?.collect::<Result<Vec<_>, _>>()?;
+    let total = inputs.len();
+    let mut tasks = JoinSet::new();
+    for input in inputs {
+        let rel = input.strip_prefix(dir).unwrap_or(&input);
+        let output = out_dir.join(rel).with_extension(format.to_string());
+        tasks.spawn_blocking(move || {
+            let converted = output
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .map_err(RcliError::from)
+                .and_then(|_| {
+                    process_csv(
+                        &input.to_string_lossy(),
+                        output.to_string_lossy().to_string(),
+                        format,
+                        &CsvDialect::default(),
+                        None,
+                        None,
+                        None,
+                        1,
+                        None,
+                    )
+                });
+            let (rows, error) = match converted {
+                Ok(rows) => (rows, None),
+                Err(e) => (0, Some(e.to_string())),
+            };
+            CsvConvertResult {
+                input,
+                output,
+                rows,
+                error,
+            }
+        });
+    }
+    let mut results = Vec::with_capacity(total);
+    while let Some(result) = tasks.join_next().await {
+        let result = result?;
+        on_progress(&result, results.len() + 1, total);
+        results.push(result);
+    }
+    results.sort_by(|a, b| a.input.cmp(&b.input));
+    Ok(results)
+}
+
+/// Hash-join two CSV files on the `on` column: `right` is loaded into memory and
+/// `left` streamed through it. Right columns whose name is taken on the left get a
+/// `_right` suffix. Returns the number of rows written.
+pub fn process_csv_join(
+    left: &str,
+    right: &str,
+    on: &str,
+    how: JoinKind,
+    output: &str,
+) -> Result<usize> {
+    let mut right_reader = Reader::from_reader(get_reader(right)?);
+    let right_headers = right_reader.headers()?.clone();
+    let right_key = column_index(&right_headers, on, right)?;
+    let mut right_rows: HashMap<String, Vec<StringRecord>> = HashMap::new();
+    for record in right_reader.records() {
+        let record = record?;
+        let values: StringRecord = record
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != right_key)
+            .map(|(_, value)| value)
+            .collect();
+        right_rows
+            .entry(record.get(right_key).unwrap_or_default().to_string())
+            .or_default()
+            .push(values);
+    }
+
+    let mut left_reader = Reader::from_reader(get_reader(left)?);
+    let mut headers = left_reader.headers()?.clone();
+    let left_key = column_index(&headers, on, left)?;
+    let right_columns: Vec<_> = right_headers
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != right_key)
+        .map(|(_, name)| match headers.iter().any(|h| h == name) {
+            true => format!("{}_right", name),
+            false => name.to_string(),
+        })
+        .collect();
+    headers.extend(&right_columns);
+
+    let mut writer = Writer::from_writer(get_writer(output)?);
+    writer.write_record(&headers)?;
+    let mut rows = 0;
+    for record in left_reader.records() {
+        let record = record?;
+        match right_rows.get(record.get(left_key).unwrap_or_default()) {
+            // every matching right row gives one output row
+            Some(matches) => {
+                for values in matches {
+                    writer.write_record(record.iter().chain(values.iter()))?;
+                    rows += 1;
+                }
+            }
+            None if how == JoinKind::Left => {
+                writer.write_record(record.iter().chain(right_columns.iter().map(|_| "")))?;
+                rows += 1;
+            }
+            None => {}
+        }
+    }
+    writer.flush()?;
+    Ok(rows)
+}
+
+fn write_xlsx(headers: &StringRecord, records: &[StringRecord], output: &str) -> Result<()> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    let header_format = Format::new().set_bold();
+    for (col, header) in headers.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, header, &header_format)?;
+    }
+    for (row, record) in records.iter().enumerate() {
+        let row = row as u32 + 1;
+        for (col, field) in record.iter().enumerate() {
+            let col = col as u16;
+            // keep typed cells so spreadsheet formulas and sorting work as expected
+            if let Ok(n) = field.parse::<f64>() {
+                worksheet.write_number(row, col, n)?;
+            } else if let Ok(b) = field.parse::<bool>() {
+                worksheet.write_boolean(row, col, b)?;
+            } else {
+                worksheet.write_string(row, col, field)?;
+            }
+        }
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+    worksheet.autofit();
+    workbook.save(output)?;
+    Ok(())
+}
+
+/// A `CREATE TABLE` named after the output file, then one `INSERT` per row
+fn write_sql(headers: &StringRecord, records: &[StringRecord], output: &str) -> Result<()> {
+    let columns = infer_columns(headers, records.iter().cloned().map(Ok::<_, RcliError>))?;
+    let table = Path::new(output)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let table = quote_identifier(&table);
+    let mut writer = BufWriter::new(fs::File::create(output)?);
+    let definitions = columns
+        .iter()
+        .map(|c| {
+            let ty = match c.column_type {
+                ColumnType::Boolean => "BOOLEAN",
+                ColumnType::Integer => "INTEGER",
+                ColumnType::Number => "REAL",
+                ColumnType::Date => "DATE",
+                ColumnType::DateTime => "TIMESTAMP",
+                ColumnType::String => "TEXT",
+            };
+            let null = if c.nullable { "" } else { " NOT NULL" };
+            format!("  {} {}{}", quote_identifier(&c.name), ty, null)
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    writeln!(writer, "CREATE TABLE {} (\n{}\n);", table, definitions)?;
+    let names = columns
+        .iter()
+        .map(|c| quote_identifier(&c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(writer, "BEGIN;")?;
+    for record in records {
+        let values = columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| sql_value(record.get(i).unwrap_or_default().trim(), c.column_type))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            writer,
+            "INSERT INTO {} ({}) VALUES ({});",
+            table, names, values
+        )?;
+    }
+    writeln!(writer, "COMMIT;")?;
+    writer.flush()?;
     Ok(())
 }
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn sql_value(value: &str, column_type: ColumnType) -> String {
+    match column_type {
+        _ if value.is_empty() => "NULL".to_string(),
+        ColumnType::Integer | ColumnType::Number => value.to_string(),
+        ColumnType::Boolean => value.to_ascii_uppercase(),
+        _ => format!("'{}'", value.replace('\'', "''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_csv_xlsx() -> anyhow::Result<()> {
+        let output = std::env::temp_dir().join("rcli-juventus.xlsx");
+        let output = output.to_string_lossy().to_string();
+        process_csv(
+            "assets/juventus.csv",
+            output.clone(),
+            OutputFormat::Xlsx,
+            &CsvDialect::default(),
+            None,
+            None,
+            None,
+            1,
+            None,
+        )?;
+        // xlsx files are zip archives
+        assert_eq!(&fs::read(output)?[..2], b"PK");
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_parallel_matches_sequential() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir();
+        let input = dir.join("rcli-parallel.csv");
+        // a quoted newline and more rows than one batch
+        let mut csv = "id,name,note\n1,\"a\",\"two\nlines\"\n".to_string();
+        for i in 2..PARALLEL_BATCH + 10 {
+            csv.push_str(&format!("{},name {},\n", i, i));
+        }
+        fs::write(&input, csv)?;
+        let input = input.to_string_lossy();
+        for format in [OutputFormat::Json, OutputFormat::Ndjson, OutputFormat::Yaml] {
+            let convert = |jobs| -> anyhow::Result<String> {
+                let output = dir.join(format!("rcli-parallel-{}.{}", jobs, format));
+                let output = output.to_string_lossy().to_string();
+                let dialect = CsvDialect::default();
+                process_csv(
+                    &input,
+                    output.clone(),
+                    format,
+                    &dialect,
+                    None,
+                    None,
+                    None,
+                    jobs,
+                    None,
+                )?;
+                Ok(fs::read_to_string(output)?)
+            };
+            assert_eq!(convert(1)?, convert(4)?, "{}", format);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_lookup() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir();
+        let lookup = dir.join("rcli-lookup-countries.csv");
+        fs::write(
+            &lookup,
+            "Nationality,Continent\nItaly,Europe\nBrazil,South America\n",
+        )?;
+        let lookup = CsvLookup::load(
+            lookup.to_str().unwrap(),
+            "Nationality",
+            &["Continent".to_string()],
+        )?;
+        let output = dir.join("rcli-juventus-enriched.csv");
+        process_csv(
+            "assets/juventus.csv",
+            output.to_string_lossy().to_string(),
+            OutputFormat::Csv,
+            &CsvDialect::default(),
+            Some(&lookup),
+            None,
+            None,
+            1,
+            None,
+        )?;
+        let mut reader = Reader::from_path(output)?;
+        assert_eq!(reader.headers()?.get(5), Some("Continent"));
+        for record in reader.records() {
+            let record = record?;
+            let expected = match &record[3] {
+                "Italy" => "Europe",
+                "Brazil" => "South America",
+                _ => "",
+            };
+            assert_eq!(&record[5], expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_join() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir();
+        let left = dir.join("rcli-join-left.csv");
+        let right = dir.join("rcli-join-right.csv");
+        let output = dir.join("rcli-join.csv");
+        fs::write(&left, "id,name\n1,Ann\n2,Bob\n3,Cid\n")?;
+        fs::write(&right, "name,id,total\nA,1,10\nB,1,20\nC,3,5\nD,4,7\n")?;
+        let join = |how| {
+            process_csv_join(
+                left.to_str().unwrap(),
+                right.to_str().unwrap(),
+                "id",
+                how,
+                output.to_str().unwrap(),
+            )
+            .and_then(|rows| Ok((rows, fs::read_to_string(&output)?)))
+        };
+        let (rows, joined) = join(JoinKind::Left)?;
+        assert_eq!(rows, 4);
+        assert_eq!(
+            joined,
+            "id,name,name_right,total\n1,Ann,A,10\n1,Ann,B,20\n2,Bob,,\n3,Cid,C,5\n"
+        );
+        let (rows, joined) = join(JoinKind::Inner)?;
+        assert_eq!(rows, 3);
+        assert!(!joined.contains("Bob"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_dialect() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir();
+        let input = dir.join("rcli-dialect.csv");
+        fs::write(&input, "1;'a;b'\n2;c;extra\n")?;
+        let output = dir.join("rcli-dialect.json");
+        let dialect = CsvDialect {
+            delimiter: b';',
+            quote: b'\'',
+            flexible: true,
+            has_headers: false,
+        };
+        let rows = process_csv(
+            input.to_str().unwrap(),
+            output.to_string_lossy().to_string(),
+            OutputFormat::Json,
+            &dialect,
+            None,
+            None,
+            None,
+            1,
+            None,
+        )?;
+        assert_eq!(rows, 2);
+        let json: Value = serde_json::from_str(&fs::read_to_string(output)?)?;
+        assert_eq!(json[0]["col_1"], "a;b");
+        assert_eq!(json[1]["col_0"], "2");
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_infer_types() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir();
+        let input = dir.join("rcli-infer.csv");
+        fs::write(&input, "id,zip,active,score\n1,01234,true,2.5\n2,,FALSE,\n")?;
+        let schema = dir.join("rcli-infer-schema.json");
+        fs::write(&schema, r#"{"zip": "string"}"#)?;
+        let types = CsvTypes::load(Some(schema.to_str().unwrap()))?;
+        let output = dir.join("rcli-infer.json");
+        process_csv(
+            input.to_str().unwrap(),
+            output.to_string_lossy().to_string(),
+            OutputFormat::Json,
+            &CsvDialect::default(),
+            None,
+            None,
+            Some(&types),
+            1,
+            None,
+        )?;
+        let json: Value = serde_json::from_str(&fs::read_to_string(output)?)?;
+        assert_eq!(
+            json[0],
+            serde_json::json!({"id": 1, "zip": "01234", "active": true, "score": 2.5})
+        );
+        assert_eq!(
+            json[1],
+            serde_json::json!({"id": 2, "zip": null, "active": false, "score": null})
+        );
+
+        fs::write(&schema, r#"{"zip": "boolean"}"#)?;
+        let types = CsvTypes::load(Some(schema.to_str().unwrap()))?;
+        let output = dir.join("rcli-infer.ndjson").to_string_lossy().to_string();
+        let dialect = CsvDialect::default();
+        let input = input.to_str().unwrap();
+        let result = process_csv(
+            input,
+            output,
+            OutputFormat::Ndjson,
+            &dialect,
+            None,
+            None,
+            Some(&types),
+            1,
+            None,
+        );
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_sql() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir();
+        let input = dir.join("rcli-sql.csv");
+        fs::write(&input, "id,name,score\n1,O'Brien,2.5\n2,,3\n")?;
+        let output = dir.join("players.sql");
+        process_csv(
+            input.to_str().unwrap(),
+            output.to_string_lossy().to_string(),
+            OutputFormat::Sql,
+            &CsvDialect::default(),
+            None,
+            None,
+            None,
+            1,
+            None,
+        )?;
+        let sql = fs::read_to_string(output)?;
+        assert!(sql.starts_with("CREATE TABLE \"players\" (\n  \"id\" INTEGER NOT NULL,\n"));
+        assert!(sql.contains("\"name\" TEXT,\n  \"score\" REAL NOT NULL\n);"));
+        assert!(sql.contains("VALUES (1, 'O''Brien', 2.5);"));
+        assert!(sql.contains("VALUES (2, NULL, 3);"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_glob() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join("rcli-csv-glob");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("a.csv"), "id,name\n1,x\n")?;
+        fs::write(dir.join("b.csv"), "id,name\n2,y\n3,z\n")?;
+        let pattern = dir.join("*.csv").to_string_lossy().to_string();
+        let output = dir.join("combined.ndjson");
+        let rows = process_csv(
+            &pattern,
+            output.to_string_lossy().to_string(),
+            OutputFormat::Ndjson,
+            &CsvDialect::default(),
+            None,
+            Some("source"),
+            None,
+            1,
+            None,
+        )?;
+        assert_eq!(rows, 3);
+        let lines = fs::read_to_string(&output)?;
+        let last: Value = serde_json::from_str(lines.lines().last().unwrap())?;
+        assert_eq!(last["id"], "3");
+        assert!(last["source"].as_str().unwrap().ends_with("b.csv"));
+
+        fs::write(dir.join("c.csv"), "id,title\n4,w\n")?;
+        let output = output.to_string_lossy().to_string();
+        let dialect = CsvDialect::default();
+        let result = process_csv(
+            &pattern,
+            output,
+            OutputFormat::Json,
+            &dialect,
+            None,
+            None,
+            None,
+            1,
+            None,
+        );
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_csv_convert_dir() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join("rcli-convert-dir");
+        let _ = fs::remove_dir_all(&dir);
+        let (input, output) = (dir.join("in"), dir.join("out"));
+        fs::create_dir_all(input.join("nested"))?;
+        fs::copy("assets/juventus.csv", input.join("a.csv"))?;
+        fs::write(input.join("nested/b.csv"), "id,name\n1,x\n2,y\n")?;
+        fs::write(input.join("nested/broken.csv"), "id,name\n1,x,extra\n")?;
+        let results =
+            process_csv_convert_dir(&input, &output, OutputFormat::Ndjson, |_, _, _| {}).await?;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[1].rows, 2);
+        assert!(results[2].error.is_some());
+        let lines = fs::read_to_string(output.join("nested/b.ndjson"))?;
+        assert_eq!(
+            lines,
+            "{\"id\":\"1\",\"name\":\"x\"}\n{\"id\":\"2\",\"name\":\"y\"}\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use csv::WriterBuilder;
+use serde_json::{Map, Value};
+
+use crate::{get_reader, get_writer, CsvDialect, Input, RcliError, Result};
+
+/// Convert a JSON array of objects, or NDJSON, into CSV. Nested objects become dotted
+/// columns like `address.city`; the columns are every key in the order first seen.
+/// A row where a dotted key like `"a.b"` clashes with a nested `{"a":{"b":..}}` is an
+/// error rather than losing one of the values. Returns the number of rows written.
+pub fn process_csv_from_json(
+    input: impl Into<Input>,
+    output: &str,
+    dialect: &CsvDialect,
+) -> Result<usize> {
+    let mut text = String::new();
+    get_reader(input)?.read_to_string(&mut text)?;
+    let mut items = Vec::new();
+    for value in serde_json::Deserializer::from_str(&text).into_iter::<Value>() {
+        match value? {
+            Value::Array(values) => items.extend(values),
+            value => items.push(value),
+        }
+    }
+    let mut headers: Vec<String> = Vec::new();
+    let mut rows = Vec::with_capacity(items.len());
+    for (i, item) in items.into_iter().enumerate() {
+        let Value::Object(object) = item else {
+            return Err(RcliError::format(format!(
+                "Expected a JSON object per row, got: {}",
+                item
+            )));
+        };
+        let mut row = Vec::new();
+        flatten_object("", object, &mut row);
+        let mut cells = HashMap::with_capacity(row.len());
+        for (key, value) in row {
+            if !headers.contains(&key) {
+                headers.push(key.clone());
+            }
+            if cells.contains_key(&key) {
+                return Err(RcliError::format(format!(
+                    "Row {} has the column {} both as a dotted key and a nested object",
+                    i + 1,
+                    key
+                )));
+            }
+            cells.insert(key, value);
+        }
+        rows.push(cells);
+    }
+    let mut writer = WriterBuilder::new()
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .from_writer(get_writer(output)?);
+    if dialect.has_headers {
+        writer.write_record(&headers)?;
+    }
+    for row in &rows {
+        // keys missing from a row are left empty
+        writer.write_record(
+            headers
+                .iter()
+                .map(|key| row.get(key).map(String::as_str).unwrap_or_default()),
+        )?;
+    }
+    writer.flush()?;
+    Ok(rows.len())
+}
+
+fn flatten_object(prefix: &str, object: Map<String, Value>, row: &mut Vec<(String, String)>) {
+    for (key, value) in object {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Object(nested) if !nested.is_empty() => flatten_object(&key, nested, row),
+            value => row.push((key, cell(value))),
+        }
+    }
+}
+
+/// Strings as they are, null as an empty cell, arrays and the rest as compact JSON
+fn cell(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_csv_from_json_ndjson() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir();
+        let input = dir.join("rcli-from-json.ndjson");
+        let output = dir.join("rcli-from-json.csv");
+        std::fs::write(
+            &input,
+            concat!(
+                r#"{"name":"Ann","address":{"city":"Turin","zip":"10121"},"tags":["a","b"]}"#,
+                "\n",
+                r#"{"name":"Bob, Jr.","age":30,"address":{"city":null}}"#,
+                "\n",
+            ),
+        )?;
+        let rows = process_csv_from_json(
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            &CsvDialect::default(),
+        )?;
+        assert_eq!(rows, 2);
+        assert_eq!(
+            std::fs::read_to_string(&output)?,
+            concat!(
+                "name,address.city,address.zip,tags,age\n",
+                "Ann,Turin,10121,\"[\"\"a\"\",\"\"b\"\"]\",\n",
+                "\"Bob, Jr.\",,,,30\n",
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_from_json_round_trip() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir();
+        let json = dir.join("rcli-round-trip.json");
+        let csv = dir.join("rcli-round-trip.csv");
+        crate::process_csv(
+            "assets/juventus.csv",
+            json.to_string_lossy().to_string(),
+            crate::cli::OutputFormat::Json,
+            &CsvDialect::default(),
+            None,
+            None,
+            None,
+            1,
+            None,
+        )?;
+        process_csv_from_json(
+            json.to_str().unwrap(),
+            csv.to_str().unwrap(),
+            &CsvDialect::default(),
+        )?;
+        let original = std::fs::read_to_string("assets/juventus.csv")?;
+        assert_eq!(
+            std::fs::read_to_string(&csv)?.lines().collect::<Vec<_>>(),
+            original.lines().collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_from_json_rejects_scalars() {
+        let path = std::env::temp_dir().join("rcli-from-json-scalars.json");
+        std::fs::write(&path, "[1, 2]").unwrap();
+        assert!(
+            process_csv_from_json(path.to_str().unwrap(), "-", &CsvDialect::default()).is_err()
+        );
+    }
+
+    #[test]
+    fn test_process_csv_from_json_rejects_clashing_keys() {
+        let path = std::env::temp_dir().join("rcli-from-json-clash.json");
+        std::fs::write(&path, r#"[{"a.b":1,"a":{"b":2}}]"#).unwrap();
+        let result = process_csv_from_json(path.to_str().unwrap(), "-", &CsvDialect::default());
+        assert!(matches!(result, Err(RcliError::Format(_))));
+    }
+}
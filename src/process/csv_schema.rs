@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use csv::{Reader, StringRecord};
+use serde_json::{json, Map, Value};
+
+use crate::cli::SchemaFormat;
+use crate::{get_reader, read_file, Input, RcliError, Result};
+
+/// Column types inferred from CSV cells, ordered from most to least specific
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Boolean,
+    Integer,
+    Number,
+    Date,
+    DateTime,
+    String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub column_type: ColumnType,
+    /// At least one row left the cell empty
+    pub nullable: bool,
+}
+
+impl ColumnType {
+    fn detect(value: &str) -> Self {
+        if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+            ColumnType::Boolean
+        } else if value.parse::<i64>().is_ok() {
+            ColumnType::Integer
+        } else if value.parse::<f64>().is_ok_and(f64::is_finite) {
+            ColumnType::Number
+        } else if NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() {
+            ColumnType::Date
+        } else if DateTime::parse_from_rfc3339(value).is_ok()
+            || NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").is_ok()
+        {
+            ColumnType::DateTime
+        } else {
+            ColumnType::String
+        }
+    }
+
+    /// A JSON Schema type name, `date` and `date-time` also accepted
+    fn from_schema_name(name: &str) -> Result<Self> {
+        match name {
+            "boolean" => Ok(ColumnType::Boolean),
+            "integer" => Ok(ColumnType::Integer),
+            "number" => Ok(ColumnType::Number),
+            "date" => Ok(ColumnType::Date),
+            "date-time" => Ok(ColumnType::DateTime),
+            "string" => Ok(ColumnType::String),
+            _ => Err(RcliError::format(format!("Unknown column type: {}", name))),
+        }
+    }
+
+    /// The native JSON value of a cell, `None` when it doesn't parse as this type
+    pub(super) fn json_value(self, value: &str) -> Option<Value> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Some(Value::Null);
+        }
+        match self {
+            ColumnType::Boolean => match value.to_ascii_lowercase().as_str() {
+                "true" => Some(Value::Bool(true)),
+                "false" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            ColumnType::Integer => value.parse::<i64>().ok().map(Value::from),
+            ColumnType::Number => value
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number),
+            ColumnType::Date | ColumnType::DateTime | ColumnType::String => Some(json!(value)),
+        }
+    }
+
+    /// The narrowest type able to hold values of both types
+    fn merge(self, other: Self) -> Self {
+        use ColumnType::*;
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Integer, Number) | (Number, Integer) => Number,
+            (Date, DateTime) | (DateTime, Date) => DateTime,
+            _ => String,
+        }
+    }
+}
+
+/// Column types used to emit native JSON values: inferred from the data, except for the
+/// columns a schema file names
+#[derive(Debug, Default, Clone)]
+pub struct CsvTypes {
+    overrides: HashMap<String, ColumnType>,
+}
+
+impl CsvTypes {
+    /// Load forced types from `schema`, either a `{"column": "integer"}` object or the JSON
+    /// Schema printed by `csv schema`
+    pub fn load(schema: Option<&str>) -> Result<Self> {
+        let Some(schema) = schema else {
+            return Ok(CsvTypes::default());
+        };
+        let value: Value = serde_json::from_slice(&read_file(schema)?)?;
+        let properties = value.pointer("/items/properties").unwrap_or(&value);
+        let Some(properties) = properties.as_object() else {
+            return Err(RcliError::format(format!(
+                "{}: expected an object of column types",
+                schema
+            )));
+        };
+        let mut overrides = HashMap::new();
+        for (column, ty) in properties {
+            // a JSON Schema property is {"type": "integer"} or {"type": ["integer", "null"]}
+            let ty = ty.get("type").unwrap_or(ty);
+            let name = match ty {
+                Value::String(name) => Some(name.as_str()),
+                Value::Array(names) => names
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .find(|name| *name != "null"),
+                _ => None,
+            };
+            let name = name.ok_or_else(|| {
+                RcliError::format(format!("{}: no type given for column {}", schema, column))
+            })?;
+            overrides.insert(column.clone(), ColumnType::from_schema_name(name)?);
+        }
+        Ok(CsvTypes { overrides })
+    }
+
+    /// The type of every column, forced or inferred from `records`
+    pub(super) fn resolve(
+        &self,
+        headers: &StringRecord,
+        records: &[StringRecord],
+    ) -> Result<Vec<ColumnType>> {
+        let columns = infer_columns(headers, records.iter().cloned().map(Ok::<_, RcliError>))?;
+        Ok(columns
+            .into_iter()
+            .map(|c| {
+                self.overrides
+                    .get(&c.name)
+                    .copied()
+                    .unwrap_or(c.column_type)
+            })
+            .collect())
+    }
+}
+
+/// Scan every row of `input` and infer a type for each column
+pub fn infer_csv_schema(input: impl Into<Input>) -> Result<Vec<ColumnSchema>> {
+    let mut reader = Reader::from_reader(get_reader(input)?);
+    let headers = reader.headers()?.clone();
+    infer_columns(&headers, reader.records())
+}
+
+/// Infer a type for each of the `headers` columns from `records`
+pub(super) fn infer_columns<E>(
+    headers: &StringRecord,
+    records: impl IntoIterator<Item = std::result::Result<StringRecord, E>>,
+) -> Result<Vec<ColumnSchema>>
+where
+    RcliError: From<E>,
+{
+    let mut types: Vec<Option<ColumnType>> = vec![None; headers.len()];
+    let mut nullable = vec![false; headers.len()];
+    for record in records {
+        let record = record?;
+        for (i, value) in record.iter().enumerate().take(headers.len()) {
+            let value = value.trim();
+            if value.is_empty() {
+                nullable[i] = true;
+                continue;
+            }
+            let detected = ColumnType::detect(value);
+            types[i] = Some(types[i].map_or(detected, |t| t.merge(detected)));
+        }
+        // short rows leave the trailing columns empty
+        for flag in nullable.iter_mut().skip(record.len()) {
+            *flag = true;
+        }
+    }
+    Ok(headers
+        .iter()
+        .zip(types)
+        .zip(nullable)
+        .map(|((name, column_type), nullable)| ColumnSchema {
+            name: name.to_string(),
+            // a column without a single value carries no type information
+            column_type: column_type.unwrap_or(ColumnType::String),
+            nullable,
+        })
+        .collect())
+}
+
+pub fn process_csv_schema(input: impl Into<Input>, format: SchemaFormat) -> Result<String> {
+    let columns = infer_csv_schema(input)?;
+    let schema = match format {
+        SchemaFormat::JsonSchema => json_schema(&columns),
+        SchemaFormat::Arrow => arrow_schema(&columns),
+    };
+    Ok(serde_json::to_string_pretty(&schema)?)
+}
+
+/// A draft 2020-12 schema for the rows as `csv -f json --infer-types` emits them: an array
+/// of objects. Without `--infer-types` every value is a string and the types don't hold
+fn json_schema(columns: &[ColumnSchema]) -> Value {
+    let mut properties = Map::new();
+    for column in columns {
+        let (ty, format) = match column.column_type {
+            ColumnType::Boolean => ("boolean", None),
+            ColumnType::Integer => ("integer", None),
+            ColumnType::Number => ("number", None),
+            ColumnType::Date => ("string", Some("date")),
+            ColumnType::DateTime => ("string", Some("date-time")),
+            ColumnType::String => ("string", None),
+        };
+        let mut property = Map::new();
+        property.insert(
+            "type".to_string(),
+            if column.nullable {
+                json!([ty, "null"])
+            } else {
+                json!(ty)
+            },
+        );
+        if let Some(format) = format {
+            property.insert("format".to_string(), json!(format));
+        }
+        properties.insert(column.name.clone(), Value::Object(property));
+    }
+    let required: Vec<&str> = columns
+        .iter()
+        .filter(|c| !c.nullable)
+        .map(|c| c.name.as_str())
+        .collect();
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        }
+    })
+}
+
+/// The schema half of Arrow's JSON format, as read by the Arrow integration tooling
+fn arrow_schema(columns: &[ColumnSchema]) -> Value {
+    let fields: Vec<Value> = columns
+        .iter()
+        .map(|column| {
+            let ty = match column.column_type {
+                ColumnType::Boolean => json!({ "name": "bool" }),
+                ColumnType::Integer => json!({ "name": "int", "bitWidth": 64, "isSigned": true }),
+                ColumnType::Number => json!({ "name": "floatingpoint", "precision": "DOUBLE" }),
+                ColumnType::Date => json!({ "name": "date", "unit": "DAY" }),
+                ColumnType::DateTime => {
+                    json!({ "name": "timestamp", "unit": "MICROSECOND", "timezone": "UTC" })
+                }
+                ColumnType::String => json!({ "name": "utf8" }),
+            };
+            json!({
+                "name": column.name,
+                "nullable": column.nullable,
+                "type": ty,
+                "children": [],
+            })
+        })
+        .collect();
+    json!({ "fields": fields })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_csv_schema() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join("rcli-schema.csv");
+        std::fs::write(
+            &path,
+            "id,score,active,born,seen,name\n\
+             1,2,true,2000-01-02,2024-01-01T00:00:00Z,a\n\
+             2,2.5,FALSE,2001-03-04,2024-01-02,\n",
+        )?;
+        let columns = infer_csv_schema(&path)?;
+        let types: Vec<ColumnType> = columns.iter().map(|c| c.column_type).collect();
+        assert_eq!(
+            types,
+            [
+                ColumnType::Integer,
+                ColumnType::Number,
+                ColumnType::Boolean,
+                ColumnType::Date,
+                ColumnType::DateTime,
+                ColumnType::String,
+            ]
+        );
+        assert!(columns[5].nullable && !columns[0].nullable);
+
+        let schema: Value =
+            serde_json::from_str(&process_csv_schema(&path, SchemaFormat::JsonSchema)?)?;
+        assert_eq!(
+            schema["items"]["properties"]["name"]["type"],
+            json!(["string", "null"])
+        );
+        assert_eq!(schema["items"]["required"].as_array().unwrap().len(), 5);
+        let schema: Value = serde_json::from_str(&process_csv_schema(&path, SchemaFormat::Arrow)?)?;
+        assert_eq!(schema["fields"][0]["type"]["bitWidth"], 64);
+        Ok(())
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fs,
+    path::PathBuf,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+};
+
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+
+use crate::{RcliError, Result};
+
+static SPILLS: AtomicUsize = AtomicUsize::new(0);
+
+type Records<'a> = Box<dyn Iterator<Item = Result<StringRecord>> + 'a>;
+
+/// A `column[:asc|desc]` sort order
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+/// How rows are ordered and deduplicated before they are written
+#[derive(Debug, Clone)]
+pub struct CsvSort {
+    pub sort_by: Option<SortKey>,
+    /// Drop records identical to one already written
+    pub dedup: bool,
+    /// Bytes of rows sorted in memory before sorted runs are spilled to temporary files
+    pub memory: usize,
+}
+
+impl FromStr for SortKey {
+    type Err = RcliError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (column, descending) = match s.rsplit_once(':') {
+            Some((column, "desc")) => (column, true),
+            Some((column, "asc")) => (column, false),
+            _ => (s, false),
+        };
+        if column.is_empty() {
+            return Err(RcliError::format(format!("Invalid sort key: {}", s)));
+        }
+        Ok(Self {
+            column: column.to_string(),
+            descending,
+        })
+    }
+}
+
+impl Default for CsvSort {
+    fn default() -> Self {
+        Self {
+            sort_by: None,
+            dedup: false,
+            memory: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl CsvSort {
+    /// Sort and deduplicate `records`. Without a sort key duplicates are dropped as they
+    /// stream by, which keeps every distinct record in memory; with one, input larger
+    /// than `memory` is sorted in runs on disk that are merged back as they are read.
+    pub(super) fn apply<'a>(
+        &self,
+        headers: &StringRecord,
+        records: Records<'a>,
+    ) -> Result<Records<'a>> {
+        let Some(key) = &self.sort_by else {
+            if !self.dedup {
+                return Ok(records);
+            }
+            let mut seen = HashSet::new();
+            return Ok(Box::new(records.filter(move |record| match record {
+                Ok(record) => seen.insert(record.iter().map(String::from).collect::<Vec<_>>()),
+                Err(_) => true,
+            })));
+        };
+        let order = RowOrder {
+            index: headers
+                .iter()
+                .position(|header| header == key.column)
+                .ok_or_else(|| RcliError::format(format!("No column named {}", key.column)))?,
+            descending: key.descending,
+            dedup: self.dedup,
+        };
+        let mut spills = Vec::new();
+        let mut chunk = Vec::new();
+        let mut size = 0;
+        for record in records {
+            let record = record?;
+            size += record.as_slice().len() + record.len() * std::mem::size_of::<usize>();
+            chunk.push(record);
+            if size >= self.memory {
+                let path = std::env::temp_dir().join(format!(
+                    "rcli-sort-{}-{}.csv",
+                    std::process::id(),
+                    SPILLS.fetch_add(1, AtomicOrdering::Relaxed)
+                ));
+                spills.push(SpillFile::write(
+                    path,
+                    order.sort(std::mem::take(&mut chunk)),
+                )?);
+                size = 0;
+            }
+        }
+        let chunk = order.sort(chunk);
+        if spills.is_empty() {
+            return Ok(Box::new(chunk.into_iter().map(Ok)));
+        }
+        let mut runs = spills
+            .iter()
+            .map(|spill| spill.read())
+            .collect::<Result<Vec<_>>>()?;
+        runs.push(Box::new(chunk.into_iter().map(Ok)));
+        let heads = runs
+            .iter_mut()
+            .map(|run| run.next().transpose())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Box::new(MergeRuns {
+            order,
+            runs,
+            heads,
+            last: None,
+            _spills: spills,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RowOrder {
+    index: usize,
+    descending: bool,
+    dedup: bool,
+}
+
+impl RowOrder {
+    fn compare(&self, a: &StringRecord, b: &StringRecord) -> Ordering {
+        // one total order over mixed columns: missing fields, then numbers, then text,
+        // otherwise a sort or a merge of spilled runs could order the same rows differently
+        let (x, y) = (a.get(self.index), b.get(self.index));
+        let number = |field: Option<&str>| field.and_then(|f| f.parse::<f64>().ok());
+        let by_key = match ((x, number(x)), (y, number(y))) {
+            ((None, _), (None, _)) => Ordering::Equal,
+            ((None, _), _) => Ordering::Less,
+            (_, (None, _)) => Ordering::Greater,
+            ((_, Some(x)), (_, Some(y))) => x.total_cmp(&y),
+            ((_, Some(_)), _) => Ordering::Less,
+            (_, (_, Some(_))) => Ordering::Greater,
+            ((x, None), (y, None)) => x.cmp(&y),
+        };
+        let by_key = if self.descending {
+            by_key.reverse()
+        } else {
+            by_key
+        };
+        // duplicates have to end up next to each other, otherwise equal keys keep input order
+        if self.dedup {
+            by_key.then_with(|| a.iter().cmp(b.iter()))
+        } else {
+            by_key
+        }
+    }
+
+    fn sort(&self, mut records: Vec<StringRecord>) -> Vec<StringRecord> {
+        records.sort_by(|a, b| self.compare(a, b));
+        if self.dedup {
+            records.dedup();
+        }
+        records
+    }
+}
+
+/// A sorted run written to a temporary file, removed when dropped
+struct SpillFile(PathBuf);
+
+impl SpillFile {
+    fn write(path: PathBuf, records: Vec<StringRecord>) -> Result<Self> {
+        let spill = Self(path);
+        let mut writer = WriterBuilder::new().flexible(true).from_path(&spill.0)?;
+        for record in &records {
+            writer.write_record(record)?;
+        }
+        writer.flush()?;
+        Ok(spill)
+    }
+
+    fn read(&self) -> Result<Records<'static>> {
+        let reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(&self.0)?;
+        Ok(Box::new(reader.into_records().map(|record| Ok(record?))))
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Merges sorted runs, taking from the earliest run on ties so the sort stays stable
+struct MergeRuns {
+    order: RowOrder,
+    runs: Vec<Records<'static>>,
+    heads: Vec<Option<StringRecord>>,
+    last: Option<StringRecord>,
+    _spills: Vec<SpillFile>,
+}
+
+impl Iterator for MergeRuns {
+    type Item = Result<StringRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut next: Option<usize> = None;
+            for (i, head) in self.heads.iter().enumerate() {
+                let Some(head) = head else { continue };
+                let smaller = match next.and_then(|j| self.heads[j].as_ref()) {
+                    Some(best) => self.order.compare(head, best) == Ordering::Less,
+                    None => true,
+                };
+                if smaller {
+                    next = Some(i);
+                }
+            }
+            let i = next?;
+            let record = match self.runs[i].next() {
+                Some(Ok(record)) => self.heads[i].replace(record),
+                Some(Err(e)) => return Some(Err(e)),
+                None => self.heads[i].take(),
+            }
+            .expect("the run has a head");
+            if self.order.dedup {
+                if self.last.as_ref() == Some(&record) {
+                    continue;
+                }
+                self.last = Some(record.clone());
+            }
+            return Some(Ok(record));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(rows: &[&[&str]]) -> Vec<StringRecord> {
+        rows.iter()
+            .map(|row| StringRecord::from(row.to_vec()))
+            .collect()
+    }
+
+    fn apply(sort: &CsvSort, records: Vec<StringRecord>) -> Result<Vec<StringRecord>> {
+        let headers = StringRecord::from(vec!["name", "age"]);
+        sort.apply(&headers, Box::new(records.into_iter().map(Ok)))?
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_key_from_str() -> Result<()> {
+        let key: SortKey = "age:desc".parse()?;
+        assert_eq!((key.column.as_str(), key.descending), ("age", true));
+        // only a known direction is split off
+        let key: SortKey = "a:b".parse()?;
+        assert_eq!((key.column.as_str(), key.descending), ("a:b", false));
+        assert!(":desc".parse::<SortKey>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_sort_in_memory() -> Result<()> {
+        let input = rows(&[&["c", "9"], &["a", "10"], &["b", "9"], &["a", "10"]]);
+        let sort = CsvSort {
+            sort_by: Some("age:desc".parse()?),
+            ..Default::default()
+        };
+        // numeric and stable: 10 before 9, equal ages keep their input order
+        assert_eq!(
+            apply(&sort, input.clone())?,
+            rows(&[&["a", "10"], &["a", "10"], &["c", "9"], &["b", "9"]])
+        );
+        let sort = CsvSort {
+            dedup: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            apply(&sort, input)?,
+            rows(&[&["c", "9"], &["a", "10"], &["b", "9"]])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_sort_mixed_column() -> Result<()> {
+        let input = rows(&[&["a", "b"], &["b", "10"], &["c", "9"], &["d"], &["e", "a"]]);
+        let sort = CsvSort {
+            sort_by: Some("age".parse()?),
+            ..Default::default()
+        };
+        // missing fields first, then numbers in numeric order, then text
+        assert_eq!(
+            apply(&sort, input)?,
+            rows(&[&["d"], &["c", "9"], &["b", "10"], &["e", "a"], &["a", "b"]])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_sort_external_merge() -> Result<()> {
+        let input: Vec<_> = (0..500)
+            .map(|i| StringRecord::from(vec![format!("n{}", i % 7), (i % 37).to_string()]))
+            .collect();
+        for dedup in [false, true] {
+            let sort = CsvSort {
+                sort_by: Some("age".parse()?),
+                dedup,
+                ..Default::default()
+            };
+            let expected = apply(&sort, input.clone())?;
+            let spilled = apply(
+                &CsvSort {
+                    memory: 256,
+                    ..sort
+                },
+                input.clone(),
+            )?;
+            assert_eq!(spilled, expected);
+        }
+        let unique = apply(
+            &CsvSort {
+                sort_by: Some("age".parse()?),
+                dedup: true,
+                memory: 256,
+            },
+            input,
+        )?;
+        assert_eq!(unique.len(), 7 * 37);
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_sort_unknown_column() {
+        let sort = CsvSort {
+            sort_by: Some("email".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(apply(&sort, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use std::net::SocketAddr;
+
+use hickory_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    error::ResolveErrorKind,
+    proto::rr::RecordType,
+    TokioAsyncResolver,
+};
+use serde::Serialize;
+
+use crate::{DnsRecordType, RcliError, Result};
+
+/// One answer record, with its data in zone file notation
+#[derive(Debug, Serialize)]
+pub struct DnsRecord {
+    pub name: String,
+    pub ttl: u32,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub data: String,
+}
+
+impl From<DnsRecordType> for RecordType {
+    fn from(record_type: DnsRecordType) -> Self {
+        match record_type {
+            DnsRecordType::A => RecordType::A,
+            DnsRecordType::Aaaa => RecordType::AAAA,
+            DnsRecordType::Cname => RecordType::CNAME,
+            DnsRecordType::Mx => RecordType::MX,
+            DnsRecordType::Ns => RecordType::NS,
+            DnsRecordType::Txt => RecordType::TXT,
+            DnsRecordType::Soa => RecordType::SOA,
+            DnsRecordType::Srv => RecordType::SRV,
+            DnsRecordType::Ptr => RecordType::PTR,
+            DnsRecordType::Caa => RecordType::CAA,
+        }
+    }
+}
+
+/// Query `name` for `record_type`, asking `server` (plain UDP/TCP) or the system resolvers
+pub async fn process_dns_lookup(
+    name: &str,
+    record_type: DnsRecordType,
+    server: Option<SocketAddr>,
+) -> Result<Vec<DnsRecord>> {
+    let resolver = match server {
+        Some(addr) => {
+            let servers = NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+            TokioAsyncResolver::tokio(
+                ResolverConfig::from_parts(None, vec![], servers),
+                ResolverOpts::default(),
+            )
+        }
+        None => TokioAsyncResolver::tokio_from_system_conf()
+            .map_err(|e| RcliError::Unsupported(format!("No system resolver: {}", e)))?,
+    };
+    let lookup = match resolver.lookup(name, record_type.into()).await {
+        Ok(lookup) => lookup,
+        Err(e) => match e.kind() {
+            ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+                return Err(RcliError::NotFound(format!(
+                    "{} record for {} ({})",
+                    record_type, name, response_code
+                )))
+            }
+            _ => return Err(std::io::Error::other(e).into()),
+        },
+    };
+    Ok(lookup
+        .record_iter()
+        .map(|record| DnsRecord {
+            name: record.name().to_string(),
+            ttl: record.ttl(),
+            record_type: record.record_type().to_string(),
+            data: record
+                .data()
+                .map(|data| data.to_string())
+                .unwrap_or_default(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_process_dns_lookup_localhost() -> Result<()> {
+        // localhost is answered by the resolver itself, the server is never asked
+        let server = Some("127.0.0.1:1".parse().unwrap());
+        let records = process_dns_lookup("localhost", DnsRecordType::Aaaa, server).await?;
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            (records[0].record_type.as_str(), records[0].data.as_str()),
+            ("AAAA", "::1")
+        );
+        Ok(())
+    }
+}
@@ -1,49 +1,217 @@
 use rand::seq::SliceRandom;
 use rand::Rng;
 
-const UPPER: &[u8] = b"ABCDEFGHIJKLMNPQRSTUVWXYZ";
-const LOWERCASE: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
-const NUMBERS: &[u8] = b"123456789";
-const SYMBOLS: &[u8] = b"!@#$%^&*_";
+use crate::{secure_rng, RcliError, RcliRng, Result};
 
-pub fn process_genpass(
+const UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const NUMBERS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%^&*_";
+/// Characters easily mistaken for one another when read or typed
+const AMBIGUOUS: &str = "0O1lI";
+
+/// Which characters a password is drawn from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordCharset {
+    pub upper: bool,
+    pub lowercase: bool,
+    pub numbers: bool,
+    pub symbols: bool,
+    /// leave out characters that look alike, see `AMBIGUOUS`
+    pub no_ambiguous: bool,
+    /// use exactly these characters instead of the classes above
+    pub custom: Option<String>,
+    /// never use these characters
+    pub exclude: String,
+    /// at least this many characters of each class; enabled classes always get one
+    pub min_upper: u8,
+    pub min_lowercase: u8,
+    pub min_numbers: u8,
+    pub min_symbols: u8,
+}
+
+impl PasswordCharset {
+    /// The given character classes, nothing excluded
+    pub fn new(upper: bool, lowercase: bool, numbers: bool, symbols: bool) -> Self {
+        Self {
+            upper,
+            lowercase,
+            numbers,
+            symbols,
+            no_ambiguous: false,
+            custom: None,
+            exclude: String::new(),
+            min_upper: 0,
+            min_lowercase: 0,
+            min_numbers: 0,
+            min_symbols: 0,
+        }
+    }
+
+    /// Every class the password draws from, after exclusions, with how many characters
+    /// of it the password needs at least
+    fn classes(&self) -> Result<Vec<(Vec<char>, usize)>> {
+        let dropped =
+            |c: char| self.exclude.contains(c) || (self.no_ambiguous && AMBIGUOUS.contains(c));
+        let keep = |c: &char| !dropped(*c);
+        let mins = [
+            self.min_upper,
+            self.min_lowercase,
+            self.min_numbers,
+            self.min_symbols,
+        ];
+        if let Some(custom) = &self.custom {
+            if mins.iter().any(|min| *min > 0) {
+                return Err(RcliError::Unsupported(
+                    "Minimum counts apply to the character classes, not a custom charset"
+                        .to_string(),
+                ));
+            }
+            let mut chars: Vec<char> = custom.chars().filter(keep).collect();
+            chars.sort_unstable();
+            chars.dedup();
+            if chars.is_empty() {
+                return Err(RcliError::Unsupported(
+                    "No characters left in the custom charset".to_string(),
+                ));
+            }
+            return Ok(vec![(chars, 1)]);
+        }
+        let enabled = [
+            (self.upper, "uppercase", UPPER),
+            (self.lowercase, "lowercase", LOWERCASE),
+            (self.numbers, "numbers", NUMBERS),
+            (self.symbols, "symbols", SYMBOLS),
+        ];
+        let mut classes = Vec::new();
+        for ((on, name, chars), min) in enabled.into_iter().zip(mins) {
+            if !on {
+                if min > 0 {
+                    return Err(RcliError::Unsupported(format!(
+                        "A minimum of {} {} needs {} enabled",
+                        min, name, name
+                    )));
+                }
+                continue;
+            }
+            let chars: Vec<char> = chars.chars().filter(keep).collect();
+            if chars.is_empty() {
+                return Err(RcliError::Unsupported(format!(
+                    "Every {} character is excluded",
+                    name
+                )));
+            }
+            classes.push((chars, min.max(1) as usize));
+        }
+        if classes.is_empty() {
+            return Err(RcliError::Unsupported(
+                "Enable at least one character class".to_string(),
+            ));
+        }
+        Ok(classes)
+    }
+}
+
+impl Default for PasswordCharset {
+    fn default() -> Self {
+        Self::new(true, true, true, true)
+    }
+}
+
+/// A random password of `length` characters with the minimum count of every enabled
+/// class: the minimums are placed first, the rest is drawn from all classes, then shuffled
+pub fn process_genpass(length: u8, charset: &PasswordCharset) -> Result<String> {
+    process_genpass_with(length, charset, &mut secure_rng()?)
+}
+
+/// [`process_genpass`] drawing from `rng`, e.g. a seeded one for reproducible passwords
+pub fn process_genpass_with(
     length: u8,
-    upper: bool,
-    lowercase: bool,
-    numbers: bool,
-    symbols: bool,
-) -> anyhow::Result<String> {
-    let mut rng = rand::thread_rng();
+    charset: &PasswordCharset,
+    rng: &mut RcliRng,
+) -> Result<String> {
+    let classes = charset.classes()?;
+    let required: usize = classes.iter().map(|(_, min)| min).sum();
+    if (length as usize) < required {
+        return Err(RcliError::Unsupported(format!(
+            "A {} character password can't hold the {} characters the classes require",
+            length, required
+        )));
+    }
     let mut password = Vec::new();
-    let mut chars = Vec::new();
-    if upper {
-        chars.extend_from_slice(UPPER);
-        password.push(*UPPER.choose(&mut rng).expect("UPPER won't be empty"));
-    }
-    if lowercase {
-        chars.extend_from_slice(LOWERCASE);
-        password.push(
-            *LOWERCASE
-                .choose(&mut rng)
-                .expect("LOWERCASE won't be empty"),
-        );
-    }
-    if numbers {
-        chars.extend_from_slice(NUMBERS);
-        password.push(*NUMBERS.choose(&mut rng).expect("NUMBERS won't be empty"));
-    }
-    if symbols {
-        chars.extend_from_slice(SYMBOLS);
-        password.push(*SYMBOLS.choose(&mut rng).expect("SYMBOLS won't be empty"));
-    }
-    for _ in 0..(length - password.len() as u8) {
+    for (class, min) in &classes {
+        for _ in 0..*min {
+            password.push(*class.choose(rng).expect("classes are never empty"));
+        }
+    }
+    let chars: Vec<char> = classes.into_iter().flat_map(|(chars, _)| chars).collect();
+    for _ in 0..(length as usize - password.len()) {
         let idx = rng.gen_range(0..chars.len());
         password.push(chars[idx]);
     }
     // shuffle the password
-    password.shuffle(&mut rng);
+    password.shuffle(rng);
+
+    Ok(password.into_iter().collect())
+}
 
-    let password = String::from_utf8(password)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(password)
+    #[test]
+    fn test_process_genpass_charset() -> Result<()> {
+        let charset = PasswordCharset {
+            no_ambiguous: true,
+            exclude: "!@".to_string(),
+            ..Default::default()
+        };
+        let password = process_genpass(64, &charset)?;
+        assert_eq!(password.chars().count(), 64);
+        assert!(!password.contains(|c| AMBIGUOUS.contains(c) || c == '!' || c == '@'));
+
+        let custom = PasswordCharset {
+            custom: Some("abcé".to_string()),
+            exclude: "c".to_string(),
+            ..Default::default()
+        };
+        let password = process_genpass(20, &custom)?;
+        assert!(password.chars().all(|c| "abé".contains(c)));
+
+        let none_left = PasswordCharset {
+            exclude: NUMBERS.to_string(),
+            ..Default::default()
+        };
+        assert!(process_genpass(16, &none_left).is_err());
+        assert!(process_genpass(3, &PasswordCharset::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_genpass_minimums() -> Result<()> {
+        let charset = PasswordCharset {
+            min_upper: 4,
+            min_numbers: 5,
+            min_symbols: 3,
+            ..Default::default()
+        };
+        for _ in 0..20 {
+            let password = process_genpass(13, &charset)?;
+            let count = |class: &str| password.chars().filter(|c| class.contains(*c)).count();
+            assert!(count(UPPER) >= 4 && count(NUMBERS) >= 5 && count(SYMBOLS) >= 3);
+            assert!(count(LOWERCASE) >= 1);
+        }
+        // 4 + 5 + 3 + one lowercase fill 13 characters exactly, a fifth uppercase doesn't fit
+        let too_many = PasswordCharset {
+            min_upper: 5,
+            ..charset.clone()
+        };
+        assert!(process_genpass(13, &too_many).is_err());
+        let no_symbols = PasswordCharset {
+            symbols: false,
+            ..charset
+        };
+        assert!(process_genpass(16, &no_symbols).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,88 @@
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine as _,
+};
+use data_encoding::HEXLOWER;
+use rand::{Rng, RngCore};
+use zeroize::Zeroizing;
+
+use crate::{rng, secure_rng, RandFormat, RcliError, Result};
+
+/// `len` random bytes, suitable for keys, salts and nonces
+pub fn process_rand_bytes(len: usize) -> Result<Zeroizing<Vec<u8>>> {
+    let mut bytes = Zeroizing::new(vec![0u8; len]);
+    secure_rng()?.fill_bytes(&mut bytes);
+    Ok(bytes)
+}
+
+/// Random bytes as text in `format`; raw bytes are returned as they are
+pub fn encode_rand_bytes(bytes: &[u8], format: RandFormat) -> Vec<u8> {
+    match format {
+        RandFormat::Hex => HEXLOWER.encode(bytes).into_bytes(),
+        RandFormat::Base64 => STANDARD.encode(bytes).into_bytes(),
+        RandFormat::Base64Url => URL_SAFE_NO_PAD.encode(bytes).into_bytes(),
+        RandFormat::Raw => bytes.to_vec(),
+    }
+}
+
+/// `count` integers drawn uniformly from `min..=max`. Not secret, so `--seed` applies.
+pub fn process_rand_int(min: i64, max: i64, count: usize) -> Result<Vec<i64>> {
+    if min > max {
+        return Err(RcliError::Unsupported(format!(
+            "--min {} is larger than --max {}",
+            min, max
+        )));
+    }
+    let mut rng = rng();
+    Ok((0..count).map(|_| rng.gen_range(min..=max)).collect())
+}
+
+/// A url-safe token carrying `len` random bytes, e.g. `sk_` followed by base64url
+pub fn process_rand_token(len: usize, prefix: &str) -> Result<String> {
+    let bytes = process_rand_bytes(len)?;
+    Ok(format!("{}{}", prefix, URL_SAFE_NO_PAD.encode(&bytes[..])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_rand_bytes() -> Result<()> {
+        let bytes = process_rand_bytes(16)?;
+        assert_eq!(bytes.len(), 16);
+        assert_ne!(bytes, process_rand_bytes(16)?);
+        assert_eq!(encode_rand_bytes(&[0xde, 0xad], RandFormat::Hex), b"dead");
+        assert_eq!(
+            encode_rand_bytes(&[0xfb, 0xff], RandFormat::Base64),
+            b"+/8="
+        );
+        assert_eq!(
+            encode_rand_bytes(&[0xfb, 0xff], RandFormat::Base64Url),
+            b"-_8"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_rand_int() -> Result<()> {
+        let ints = process_rand_int(-2, 2, 200)?;
+        assert_eq!(ints.len(), 200);
+        assert!(ints.iter().all(|i| (-2..=2).contains(i)));
+        // both ends of the range are reachable
+        assert!(ints.contains(&-2) && ints.contains(&2));
+        assert_eq!(process_rand_int(7, 7, 3)?, vec![7, 7, 7]);
+        assert!(process_rand_int(3, 1, 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_rand_token() -> Result<()> {
+        let token = process_rand_token(24, "sk_")?;
+        assert_eq!(token.len(), 3 + 32);
+        assert!(token[3..]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use uuid::{Builder, Uuid, Variant};
+
+use crate::{rng, RcliError, Result, UuidVersion};
+
+/// What can be read back out of a UUID
+#[derive(Debug)]
+pub struct UuidInfo {
+    pub uuid: Uuid,
+    pub version: usize,
+    pub variant: &'static str,
+    /// creation time, for the time based versions (v1, v6, v7)
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+pub fn process_uuid(version: UuidVersion, count: usize) -> Vec<Uuid> {
+    let mut rng = rng();
+    let mut uuids: Vec<Uuid> = (0..count)
+        .map(|_| match version {
+            UuidVersion::V4 => {
+                let mut bytes = [0u8; 16];
+                rng.fill_bytes(&mut bytes);
+                Builder::from_random_bytes(bytes).into_uuid()
+            }
+            UuidVersion::V7 => {
+                let mut bytes = [0u8; 10];
+                rng.fill_bytes(&mut bytes);
+                let millis = Utc::now().timestamp_millis() as u64;
+                Builder::from_unix_timestamp_millis(millis, &bytes).into_uuid()
+            }
+        })
+        .collect();
+    if let UuidVersion::V7 = version {
+        // several v7 ids share a millisecond; sorting keeps the batch strictly increasing
+        uuids.sort();
+    }
+    uuids
+}
+
+pub fn process_uuid_decode(input: &str) -> Result<UuidInfo> {
+    let uuid = Uuid::parse_str(input.trim())
+        .map_err(|e| RcliError::format(format!("Invalid UUID {}: {}", input.trim(), e)))?;
+    let variant = match uuid.get_variant() {
+        Variant::NCS => "NCS",
+        Variant::RFC4122 => "RFC 9562",
+        Variant::Microsoft => "Microsoft",
+        _ => "future",
+    };
+    let timestamp = uuid.get_timestamp().and_then(|ts| {
+        let (secs, nanos) = ts.to_unix();
+        DateTime::from_timestamp(secs as i64, nanos)
+    });
+    Ok(UuidInfo {
+        uuid,
+        version: uuid.get_version_num(),
+        variant,
+        timestamp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_uuid() {
+        let uuids = process_uuid(UuidVersion::V4, 3);
+        assert_eq!(uuids.len(), 3);
+        assert!(uuids.iter().all(|u| u.get_version_num() == 4));
+        let uuids = process_uuid(UuidVersion::V7, 50);
+        assert!(uuids.iter().all(|u| u.get_version_num() == 7));
+        assert!(uuids.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_process_uuid_decode() -> Result<()> {
+        // RFC 9562 appendix A.6 test vector
+        let info = process_uuid_decode("017F22E2-79B0-7CC3-98C4-DC0C0C07398F")?;
+        assert_eq!(info.version, 7);
+        assert_eq!(info.variant, "RFC 9562");
+        assert_eq!(
+            info.timestamp.map(|t| t.to_rfc3339()),
+            Some("2022-02-22T19:22:22+00:00".to_string())
+        );
+        let info = process_uuid_decode("919108f7-52d1-4320-9bac-f847db4148a8")?;
+        assert_eq!((info.version, info.timestamp), (4, None));
+        assert!(process_uuid_decode("not-a-uuid").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use std::{fs::File, io::Read, path::Path};
+
+use data_encoding::HEXLOWER;
+use md5::Md5;
+use serde::Serialize;
+use sha2::{Digest, Sha256, Sha512};
+use tokio::io::AsyncReadExt;
+
+use crate::{get_async_reader, get_reader, HashAlgorithm, Input, RcliError, Result};
+
+const HASH_CHUNK: usize = 64 * 1024;
+
+#[derive(Debug, Serialize)]
+pub struct HashCheckResult {
+    pub file: String,
+    pub ok: bool,
+    /// set when the file could not be read at all
+    pub error: Option<String>,
+}
+
+pub fn process_hash(input: impl Into<Input>, algorithm: HashAlgorithm) -> Result<String> {
+    let mut reader = get_reader(input)?;
+    hash_reader(&mut reader, algorithm)
+}
+
+/// [`process_hash`] reading the input asynchronously, so the runtime isn't blocked
+pub async fn process_hash_async(
+    input: impl Into<Input>,
+    algorithm: HashAlgorithm,
+) -> Result<String> {
+    let mut reader = get_async_reader(input).await?;
+    let mut hasher = StreamHasher::new(algorithm);
+    let mut buf = vec![0u8; HASH_CHUNK];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Verify a `sha256sum` style manifest: one `<hex digest>  <file>` entry per line
+pub fn process_hash_check(
+    manifest: &str,
+    algorithm: HashAlgorithm,
+) -> Result<Vec<HashCheckResult>> {
+    let mut reader = get_reader(manifest)?;
+    let mut content = String::new();
+    reader.read_to_string(&mut content)?;
+    let mut results = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (expected, file) = line.split_once(char::is_whitespace).ok_or_else(|| {
+            RcliError::format(format!("Invalid manifest line {}: {}", n + 1, line))
+        })?;
+        // `*` marks binary mode in coreutils manifests, which makes no difference here
+        let file = file.trim_start().trim_start_matches('*');
+        let result = match hash_file(file, algorithm) {
+            Ok(actual) => HashCheckResult {
+                file: file.to_string(),
+                ok: actual.eq_ignore_ascii_case(expected),
+                error: None,
+            },
+            Err(e) => HashCheckResult {
+                file: file.to_string(),
+                ok: false,
+                error: Some(e.to_string()),
+            },
+        };
+        results.push(result);
+    }
+    Ok(results)
+}
+
+fn hash_file(path: impl AsRef<Path>, algorithm: HashAlgorithm) -> Result<String> {
+    let mut file = File::open(path)?;
+    hash_reader(&mut file, algorithm)
+}
+
+pub(super) fn hash_reader(reader: &mut dyn Read, algorithm: HashAlgorithm) -> Result<String> {
+    let mut hasher = StreamHasher::new(algorithm);
+    let mut buf = vec![0u8; HASH_CHUNK];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Any of the supported digests, fed block by block
+enum StreamHasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Md5(Md5),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl StreamHasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => StreamHasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha512 => StreamHasher::Sha512(Sha512::new()),
+            HashAlgorithm::Md5 => StreamHasher::Md5(Md5::new()),
+            HashAlgorithm::Blake3 => StreamHasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            StreamHasher::Sha256(hasher) => hasher.update(data),
+            StreamHasher::Sha512(hasher) => hasher.update(data),
+            StreamHasher::Md5(hasher) => hasher.update(data),
+            StreamHasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// Lowercase hex digest
+    fn finalize(self) -> String {
+        let digest = match self {
+            StreamHasher::Sha256(hasher) => hasher.finalize().to_vec(),
+            StreamHasher::Sha512(hasher) => hasher.finalize().to_vec(),
+            StreamHasher::Md5(hasher) => hasher.finalize().to_vec(),
+            StreamHasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+        };
+        HEXLOWER.encode(&digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_reader() -> Result<()> {
+        let data = b"abc";
+        assert_eq!(
+            hash_reader(&mut &data[..], HashAlgorithm::Sha256)?,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hash_reader(&mut &data[..], HashAlgorithm::Md5)?,
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            hash_reader(&mut &data[..], HashAlgorithm::Blake3)?,
+            blake3::hash(data).to_hex().to_string()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_hash_async() -> Result<()> {
+        for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Blake3] {
+            assert_eq!(
+                process_hash_async("Cargo.toml", algorithm).await?,
+                process_hash("Cargo.toml", algorithm)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_hash_check() -> Result<()> {
+        let digest = process_hash("Cargo.toml", HashAlgorithm::Sha256)?;
+        let manifest = std::env::temp_dir().join("rcli-SHA256SUMS");
+        std::fs::write(
+            &manifest,
+            format!("{}  Cargo.toml\n{}  nonexistent\n", digest, digest),
+        )?;
+        let results = process_hash_check(manifest.to_str().unwrap(), HashAlgorithm::Sha256)?;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].ok);
+        assert!(!results[1].ok);
+        assert!(results[1].error.is_some());
+        Ok(())
+    }
+}
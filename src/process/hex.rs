@@ -0,0 +1,107 @@
+use std::io::{self, Read};
+
+use crate::{get_reader, hexdump, Input, RcliError, Result};
+
+const BYTES_PER_LINE: usize = 16;
+/// Largest output a dump may describe, so a bogus offset can't zero-fill gigabytes
+const MAX_REVERT_LEN: usize = 256 * 1024 * 1024;
+
+/// xxd-style dump of `length` bytes (all by default) starting at `offset`
+pub fn process_hex_dump(
+    input: impl Into<Input>,
+    offset: u64,
+    length: Option<u64>,
+) -> Result<String> {
+    let mut reader = get_reader(input)?;
+    // stdin can't seek, so skip by reading
+    io::copy(&mut (&mut reader).take(offset), &mut io::sink())?;
+    let mut data = Vec::new();
+    match length {
+        Some(length) => reader.take(length).read_to_end(&mut data)?,
+        None => reader.read_to_end(&mut data)?,
+    };
+    Ok(hexdump(&data, offset as usize))
+}
+
+/// Rebuild binary data from a dump. Bytes land at the offset of their line, so gaps are
+/// zero-filled and a dump taken with `--offset` is restored at that position.
+pub fn process_hex_revert(input: impl Into<Input>) -> Result<Vec<u8>> {
+    let mut reader = get_reader(input)?;
+    let mut dump = String::new();
+    reader.read_to_string(&mut dump)?;
+    revert_dump(&dump)
+}
+
+pub(super) fn revert_dump(dump: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    for (n, line) in dump.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = || RcliError::format(format!("Invalid dump line {}: {}", n + 1, line));
+        let (offset, rest) = line.split_once(':').ok_or_else(invalid)?;
+        let offset = usize::from_str_radix(offset.trim(), 16).map_err(|_| invalid())?;
+        let bytes = parse_hex_column(rest).ok_or_else(invalid)?;
+        let end = offset
+            .checked_add(bytes.len())
+            .filter(|end| *end <= MAX_REVERT_LEN)
+            .ok_or_else(|| {
+                RcliError::format(format!(
+                    "Dump line {} is at offset {:x}, beyond the {} MiB limit",
+                    n + 1,
+                    offset,
+                    MAX_REVERT_LEN >> 20
+                ))
+            })?;
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[offset..end].copy_from_slice(&bytes);
+    }
+    Ok(data)
+}
+
+/// Read hex pairs up to the double space that separates them from the ascii column
+fn parse_hex_column(rest: &str) -> Option<Vec<u8>> {
+    let hex = rest.strip_prefix(' ').unwrap_or(rest);
+    let hex = hex.split("  ").next().unwrap_or_default();
+    let digits: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) || digits.len() > BYTES_PER_LINE * 2 {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_dump_revert() -> Result<()> {
+        let data: Vec<u8> = (0..=255u8).chain(*b"  tail").collect();
+        let path = std::env::temp_dir().join("rcli-hex.bin");
+        std::fs::write(&path, &data)?;
+        let dump = process_hex_dump(&path, 0, None)?;
+        let dump_path = std::env::temp_dir().join("rcli-hex.dump");
+        std::fs::write(&dump_path, &dump)?;
+        assert_eq!(process_hex_revert(&dump_path)?, data);
+
+        let dump = process_hex_dump(&path, 0x20, Some(3))?;
+        assert_eq!(
+            dump,
+            "00000020: 2021 22                                   !\"\n"
+        );
+        std::fs::write(&dump_path, &dump)?;
+        let reverted = process_hex_revert(&dump_path)?;
+        assert_eq!(reverted.len(), 0x23);
+        assert_eq!(&reverted[0x20..], b" !\"");
+
+        // offsets that overflow or would need gigabytes of zero fill are rejected
+        assert!(revert_dump("ffffffffffffffff: 4142  AB\n").is_err());
+        assert!(revert_dump("ffffffff: 4142  AB\n").is_err());
+        Ok(())
+    }
+}
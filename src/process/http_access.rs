@@ -0,0 +1,252 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use axum::{
+    extract::{Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, COOKIE, SET_COOKIE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use glob::Pattern;
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+
+use super::{http_admin::ADMIN_PREFIX, http_serve::HtpServeState};
+use crate::{Result, Secret};
+
+pub const ACCESS_FILE: &str = ".rcli-access.toml";
+const TOKEN_HEADER: &str = "x-auth-token";
+const TOKEN_COOKIE: &str = "rcli_token";
+
+/// Rules declared in a `.rcli-access.toml` file, applied to its directory and all subdirectories
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccessRules {
+    /// glob patterns of file and directory names that are never served, nor is anything below them
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// whether directory listings are shown
+    pub listing: Option<bool>,
+    pub auth: Option<BasicAuth>,
+    /// set by `resolve` when a directory on the way to the target matches a deny pattern
+    /// declared above it
+    #[serde(skip)]
+    in_denied_dir: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BasicAuth {
+    pub username: String,
+    pub password: Secret,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AccessDecision {
+    Allow,
+    Denied,
+    Unauthorized,
+    ListingHidden,
+}
+
+impl AccessRules {
+    /// Merge the access files from `root` down to the directory holding `target`.
+    /// Files are re-read on every request so edits apply without a restart.
+    pub async fn resolve(root: &Path, target: &Path) -> Result<Self> {
+        let mut rules = AccessRules::default();
+        let rel = target.strip_prefix(root).unwrap_or(target);
+        let mut dir = root.to_path_buf();
+        rules.merge(Self::load(&dir).await?);
+        let components: Vec<_> = rel.components().collect();
+        // the last component is the target itself unless it's a directory
+        let dirs = if target.is_dir() {
+            &components[..]
+        } else {
+            &components[..components.len().saturating_sub(1)]
+        };
+        for c in dirs {
+            // deny patterns cover everything below a matching directory, not just its name
+            if rules.is_denied(&c.as_os_str().to_string_lossy()) {
+                rules.in_denied_dir = true;
+            }
+            dir.push(c);
+            rules.merge(Self::load(&dir).await?);
+        }
+        Ok(rules)
+    }
+
+    async fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(ACCESS_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = tokio::fs::read_to_string(path).await?;
+        Ok(Some(toml::from_str(&content)?))
+    }
+
+    fn merge(&mut self, child: Option<Self>) {
+        let Some(child) = child else {
+            return;
+        };
+        self.deny.extend(child.deny);
+        if child.listing.is_some() {
+            self.listing = child.listing;
+        }
+        if child.auth.is_some() {
+            self.auth = child.auth;
+        }
+    }
+
+    pub fn is_denied(&self, name: &str) -> bool {
+        name == ACCESS_FILE
+            || self
+                .deny
+                .iter()
+                .filter_map(|p| Pattern::new(p).ok())
+                .any(|p| p.matches(name))
+    }
+
+    pub fn check(&self, target: &Path, headers: &HeaderMap) -> AccessDecision {
+        let name = target
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if self.in_denied_dir || self.is_denied(&name) {
+            return AccessDecision::Denied;
+        }
+        if let Some(auth) = &self.auth {
+            if !auth.authorized(headers) {
+                return AccessDecision::Unauthorized;
+            }
+        }
+        if target.is_dir() && self.listing == Some(false) {
+            return AccessDecision::ListingHidden;
+        }
+        AccessDecision::Allow
+    }
+}
+
+impl BasicAuth {
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        let expected = STANDARD.encode(format!("{}:{}", self.username, self.password.expose_str()));
+        headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Basic "))
+            .map(|v| v.trim().as_bytes().ct_eq(expected.as_bytes()).into())
+            .unwrap_or(false)
+    }
+}
+
+/// With `--token`, every request outside the admin UI needs the token as `?token=`, an
+/// `X-Auth-Token` header, or the cookie set after a successful `?token=` visit, so links in
+/// directory listings keep working once a shared link has been opened.
+pub(crate) async fn require_token(
+    State(state): State<Arc<HtpServeState>>,
+    Query(query): Query<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = &state.access_token else {
+        return next.run(request).await;
+    };
+    if request.uri().path().starts_with(ADMIN_PREFIX) {
+        return next.run(request).await;
+    }
+    let expected = expected.expose_str();
+    let matches = |token: &str| -> bool { token.as_bytes().ct_eq(expected.as_bytes()).into() };
+    let headers = request.headers();
+    let from_query = query.get("token").is_some_and(|t| matches(t));
+    let authorized = from_query
+        || headers
+            .get(TOKEN_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(matches)
+        || cookie(headers, TOKEN_COOKIE).is_some_and(matches);
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let mut response = next.run(request).await;
+    if from_query {
+        let cookie = format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Strict",
+            TOKEN_COOKIE, expected
+        );
+        if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(SET_COOKIE, cookie);
+        }
+    }
+    response
+}
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_access_rules_deny() {
+        let rules = AccessRules {
+            deny: vec!["*.key".to_string()],
+            ..Default::default()
+        };
+        assert!(rules.is_denied("server.key"));
+        assert!(rules.is_denied(ACCESS_FILE));
+        assert!(!rules.is_denied("Cargo.toml"));
+    }
+
+    #[test]
+    fn test_access_rules_auth() {
+        let rules = AccessRules {
+            auth: Some(BasicAuth {
+                username: "rcli".to_string(),
+                password: Secret::new("secret".to_string()),
+            }),
+            ..Default::default()
+        };
+        let target = Path::new("Cargo.toml");
+        let mut headers = HeaderMap::new();
+        assert_eq!(rules.check(target, &headers), AccessDecision::Unauthorized);
+        let value = format!("Basic {}", STANDARD.encode("rcli:secret"));
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(&value).unwrap());
+        assert_eq!(rules.check(target, &headers), AccessDecision::Allow);
+    }
+
+    #[test]
+    fn test_access_rules_merge() {
+        let mut rules = AccessRules {
+            deny: vec!["*.key".to_string()],
+            listing: Some(false),
+            ..Default::default()
+        };
+        rules.merge(Some(AccessRules {
+            deny: vec!["*.pem".to_string()],
+            listing: Some(true),
+            ..Default::default()
+        }));
+        assert!(rules.is_denied("a.key"));
+        assert!(rules.is_denied("a.pem"));
+        assert_eq!(rules.listing, Some(true));
+    }
+
+    #[test]
+    fn test_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.insert(COOKIE, HeaderValue::from_static("a=1; rcli_token=abc"));
+        assert_eq!(cookie(&headers, TOKEN_COOKIE), Some("abc"));
+        assert_eq!(cookie(&headers, "missing"), None);
+    }
+}
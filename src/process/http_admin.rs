@@ -0,0 +1,354 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{ready, Context, Poll},
+    time::{Instant, SystemTime},
+};
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use http_body::{Frame, SizeHint};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use super::http_serve::HtpServeState;
+
+pub const ADMIN_PREFIX: &str = "/_rcli";
+const RECENT_REQUESTS: usize = 100;
+
+/// Runtime switches that can be flipped from the admin UI without a restart
+#[derive(Debug)]
+pub struct ServeSettings {
+    pub listing: AtomicBool,
+    pub uploads: AtomicBool,
+}
+
+#[derive(Debug)]
+pub struct ServeStats {
+    started: Instant,
+    requests: AtomicU64,
+    pub(super) bytes_sent: AtomicU64,
+    recent: Mutex<VecDeque<RequestLog>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestLog {
+    pub timestamp: u64,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub bytes: u64,
+    pub duration_ms: u128,
+}
+
+#[derive(Debug, Serialize)]
+struct StatusResponse {
+    dir: String,
+    port: u16,
+    uptime_secs: u64,
+    requests: u64,
+    bytes_sent: u64,
+    bytes_per_sec: f64,
+    listing: bool,
+    uploads: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SettingsRequest {
+    listing: Option<bool>,
+    uploads: Option<bool>,
+}
+
+impl ServeStats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_REQUESTS)),
+        }
+    }
+
+    fn record(&self, log: RequestLog) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(log.bytes, Ordering::Relaxed);
+        let mut recent = self.recent.lock().expect("stats lock poisoned");
+        if recent.len() == RECENT_REQUESTS {
+            recent.pop_front();
+        }
+        recent.push_back(log);
+    }
+}
+
+impl Default for ServeStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn admin_router() -> Router<Arc<HtpServeState>> {
+    Router::new()
+        .route("/", get(admin_page))
+        .route("/api/status", get(admin_status))
+        .route("/api/requests", get(admin_requests))
+        .route("/api/settings", axum::routing::post(admin_settings))
+}
+
+/// Middleware recording every request outside the admin UI for the dashboard
+pub async fn track_requests(
+    State(state): State<Arc<HtpServeState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let start = Instant::now();
+    let response = next.run(request).await;
+    if path.starts_with(ADMIN_PREFIX) {
+        return response;
+    }
+    let log = RequestLog {
+        timestamp: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        method,
+        path,
+        status: response.status().as_u16(),
+        bytes: 0,
+        duration_ms: start.elapsed().as_millis(),
+    };
+    response.map(|body| Body::new(TrackedBody { body, state, log }))
+}
+
+/// A response body counting the bytes actually sent, as streamed bodies have no size up
+/// front. The request is recorded once the body is done with, or the client went away.
+struct TrackedBody {
+    body: Body,
+    state: Arc<HtpServeState>,
+    log: RequestLog,
+}
+
+impl HttpBody for TrackedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let frame = ready!(Pin::new(&mut self.body).poll_frame(cx));
+        if let Some(data) = frame.as_ref().and_then(|f| f.as_ref().ok()?.data_ref()) {
+            self.log.bytes += data.len() as u64;
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+impl Drop for TrackedBody {
+    fn drop(&mut self) {
+        self.state.stats.record(self.log.clone());
+    }
+}
+
+/// The admin token is only taken from the `Authorization` header, never the query string,
+/// where it would end up in access logs, browser history and Referer headers
+fn authorized(state: &HtpServeState, headers: &HeaderMap) -> bool {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|token| {
+            token
+                .as_bytes()
+                .ct_eq(state.admin_token.expose_str().as_bytes())
+                .into()
+        })
+}
+
+/// The page holds no data, it reads the token from the URL fragment, which browsers never
+/// send, and calls the API with it
+async fn admin_page() -> Response {
+    Html(ADMIN_HTML).into_response()
+}
+
+async fn admin_status(State(state): State<Arc<HtpServeState>>, headers: HeaderMap) -> Response {
+    if !authorized(&state, &headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let uptime = state.stats.started.elapsed();
+    let bytes_sent = state.stats.bytes_sent.load(Ordering::Relaxed);
+    Json(StatusResponse {
+        dir: state.path.display().to_string(),
+        port: state.port,
+        uptime_secs: uptime.as_secs(),
+        requests: state.stats.requests.load(Ordering::Relaxed),
+        bytes_sent,
+        bytes_per_sec: bytes_sent as f64 / uptime.as_secs_f64().max(1.0),
+        listing: state.settings.listing.load(Ordering::Relaxed),
+        uploads: state.settings.uploads.load(Ordering::Relaxed),
+    })
+    .into_response()
+}
+
+async fn admin_requests(State(state): State<Arc<HtpServeState>>, headers: HeaderMap) -> Response {
+    if !authorized(&state, &headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let recent = state.stats.recent.lock().expect("stats lock poisoned");
+    let logs: Vec<RequestLog> = recent.iter().rev().cloned().collect();
+    Json(logs).into_response()
+}
+
+async fn admin_settings(
+    State(state): State<Arc<HtpServeState>>,
+    headers: HeaderMap,
+    Json(settings): Json<SettingsRequest>,
+) -> Response {
+    if !authorized(&state, &headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    if let Some(listing) = settings.listing {
+        state.settings.listing.store(listing, Ordering::Relaxed);
+    }
+    if let Some(uploads) = settings.uploads {
+        state.settings.uploads.store(uploads, Ordering::Relaxed);
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
+
+const ADMIN_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>rcli admin</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+td, th { padding: 2px 10px; text-align: left; border-bottom: 1px solid #ddd; }
+</style>
+</head>
+<body>
+<h1>rcli http serve</h1>
+<table id="status"></table>
+<p>
+<label><input type="checkbox" id="listing"> directory listing</label>
+<label><input type="checkbox" id="uploads"> uploads (WebDAV)</label>
+</p>
+<h2>Recent requests</h2>
+<table>
+<thead><tr><th>time</th><th>method</th><th>path</th><th>status</th><th>bytes</th><th>ms</th></tr></thead>
+<tbody id="requests"></tbody>
+</table>
+<script>
+const token = new URLSearchParams(location.hash.slice(1)).get("token") || sessionStorage.getItem("token") || "";
+sessionStorage.setItem("token", token);
+history.replaceState(null, "", location.pathname);
+const headers = { "Authorization": "Bearer " + token, "Content-Type": "application/json" };
+const api = (path, opts) => fetch("/_rcli/api/" + path, Object.assign({ headers }, opts));
+const cell = (tag, text) => { const c = document.createElement(tag); c.textContent = text; return c; };
+
+async function refresh() {
+  const status = await (await api("status")).json();
+  const table = document.getElementById("status");
+  table.replaceChildren();
+  for (const key of ["dir", "port", "uptime_secs", "requests", "bytes_sent", "bytes_per_sec"]) {
+    const row = document.createElement("tr");
+    row.append(cell("th", key), cell("td", key === "bytes_per_sec" ? status[key].toFixed(1) : status[key]));
+    table.append(row);
+  }
+  document.getElementById("listing").checked = status.listing;
+  document.getElementById("uploads").checked = status.uploads;
+
+  const logs = await (await api("requests")).json();
+  const body = document.getElementById("requests");
+  body.replaceChildren();
+  for (const log of logs) {
+    const row = document.createElement("tr");
+    const time = new Date(log.timestamp * 1000).toLocaleTimeString();
+    row.append(cell("td", time), cell("td", log.method), cell("td", log.path),
+      cell("td", log.status), cell("td", log.bytes), cell("td", log.duration_ms));
+    body.append(row);
+  }
+}
+
+for (const name of ["listing", "uploads"]) {
+  document.getElementById(name).addEventListener("change", async (e) => {
+    await api("settings", { method: "POST", body: JSON.stringify({ [name]: e.target.checked }) });
+    refresh();
+  });
+}
+refresh();
+setInterval(refresh, 2000);
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::http_serve::tests::test_state;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_serve_stats_keeps_recent_requests() {
+        let stats = ServeStats::new();
+        for i in 0..RECENT_REQUESTS + 5 {
+            stats.record(RequestLog {
+                timestamp: 0,
+                method: "GET".to_string(),
+                path: format!("/{}", i),
+                status: 200,
+                bytes: 10,
+                duration_ms: 0,
+            });
+        }
+        let recent = stats.recent.lock().unwrap();
+        assert_eq!(recent.len(), RECENT_REQUESTS);
+        assert_eq!(recent.front().unwrap().path, "/5");
+        assert_eq!(
+            stats.bytes_sent.load(Ordering::Relaxed),
+            10 * (RECENT_REQUESTS as u64 + 5)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admin_token_from_header_only() {
+        let state = test_state(".".into());
+        let status = |auth: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(auth) = auth {
+                headers.insert(AUTHORIZATION, HeaderValue::from_static(auth));
+            }
+            admin_requests(State(state.clone()), headers)
+        };
+        assert_eq!(status(Some("Bearer token")).await.status(), StatusCode::OK);
+        assert_eq!(
+            status(Some("Bearer wrong")).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        // a `?token=` query is never looked at
+        assert_eq!(status(None).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(admin_page().await.status(), StatusCode::OK);
+    }
+}
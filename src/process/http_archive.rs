@@ -0,0 +1,305 @@
+use std::{
+    fs::File,
+    io::{Seek, Write},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use async_zip::{tokio::write::ZipFileWriter, Compression, ZipDateTime, ZipEntryBuilder};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap,
+    },
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::Deserialize;
+use tokio::io::AsyncWrite;
+use tokio_util::compat::FuturesAsyncWriteCompatExt;
+use tracing::{info, warn};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use super::{
+    http_access::{AccessDecision, AccessRules},
+    http_serve::{HtpServeState, HttpError},
+    SHARE_DIR,
+};
+use crate::{rng, Result};
+
+pub(crate) const ARCHIVE_PATH: &str = "/_archive";
+
+#[derive(Debug, Deserialize)]
+struct ArchiveRequest {
+    paths: Vec<String>,
+}
+
+/// `POST /_archive` with `{"paths": [...]}` or the form posted by the directory listing.
+/// Paths are relative to the served directory and each must be a file the caller could
+/// download on its own.
+pub(crate) async fn archive_handler(
+    State(state): State<Arc<HtpServeState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, HttpError> {
+    let paths = parse_request(&headers, &body)?;
+    if paths.is_empty() {
+        return Err(HttpError::BadRequest("No paths selected".to_string()));
+    }
+    let mut files: Vec<PathBuf> = Vec::with_capacity(paths.len());
+    for path in paths {
+        let rel = Path::new(&path);
+        // one-time shares would survive being zipped, so they are never archived
+        if !rel.components().all(|c| matches!(c, Component::Normal(_)))
+            || rel.starts_with(SHARE_DIR)
+        {
+            return Err(HttpError::Forbidden);
+        }
+        let p = state.path.join(rel);
+        if !p.is_file() || state.is_excluded(&p) {
+            return Err(HttpError::NotFound(path));
+        }
+        let rules = AccessRules::resolve(&state.path, &p)
+            .await
+            .map_err(|_| HttpError::Internal)?;
+        match rules.check(&p, &headers) {
+            AccessDecision::Allow => {}
+            AccessDecision::Denied => return Err(HttpError::NotFound(path)),
+            AccessDecision::Unauthorized => return Err(HttpError::Unauthorized),
+            AccessDecision::ListingHidden => return Err(HttpError::Forbidden),
+        }
+        if !files.iter().any(|f| f == rel) {
+            files.push(rel.to_path_buf());
+        }
+    }
+    info!("Archiving {} files", files.len());
+
+    // the zip is spooled to disk rather than memory, then streamed back from there
+    let root = state.path.clone();
+    let spool = std::env::temp_dir().join(format!("rcli-archive-{:016x}.zip", rng().next_u64()));
+    let file = tokio::task::spawn_blocking(move || -> Result<File> {
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&spool)?;
+        // the open handle keeps the data around until the response has been sent
+        let _ = std::fs::remove_file(&spool);
+        write_zip(&root, &files, &mut file)?;
+        file.rewind()?;
+        Ok(file)
+    })
+    .await
+    .map_err(|_| HttpError::Internal)?
+    .map_err(|_| HttpError::Internal)?;
+
+    let len = file.metadata().map_err(|_| HttpError::Internal)?.len();
+    let body = state.body(tokio::fs::File::from_std(file));
+    Response::builder()
+        .header(CONTENT_TYPE, "application/zip")
+        .header(CONTENT_LENGTH, len)
+        .header(CONTENT_DISPOSITION, "attachment; filename=\"archive.zip\"")
+        .body(body)
+        .map_err(|_| HttpError::Internal)
+}
+
+fn parse_request(headers: &HeaderMap, body: &[u8]) -> Result<Vec<String>, HttpError> {
+    let is_form = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+    if is_form {
+        // checkboxes post one `paths=` pair per selected file
+        let fields: Vec<(String, String)> =
+            serde_urlencoded::from_bytes(body).map_err(|e| HttpError::BadRequest(e.to_string()))?;
+        Ok(fields
+            .into_iter()
+            .filter(|(name, _)| name == "paths")
+            .map(|(_, value)| value)
+            .collect())
+    } else {
+        let request: ArchiveRequest =
+            serde_json::from_slice(body).map_err(|e| HttpError::BadRequest(e.to_string()))?;
+        Ok(request.paths)
+    }
+}
+
+/// `GET <dir>?download=zip`: the whole directory as a zip, streamed while it is written
+/// so large trees start downloading at once. Files the caller couldn't download on their
+/// own (denied, excluded, one-time shares, other credentials) are left out.
+pub(crate) async fn zip_dir_response(
+    state: &HtpServeState,
+    dir: PathBuf,
+    headers: &HeaderMap,
+) -> Result<Response, HttpError> {
+    let files = collect_files(state, &dir, headers)
+        .await
+        .map_err(|_| HttpError::Internal)?;
+    info!("Streaming {} files from {:?} as zip", files.len(), dir);
+    let name = dir
+        .canonicalize()
+        .ok()
+        .and_then(|dir| {
+            dir.file_name()
+                .map(|n| n.to_string_lossy().replace('"', "_"))
+        })
+        .unwrap_or_else(|| "archive".to_string());
+
+    let (writer, reader) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        if let Err(e) = stream_zip(&dir, &files, writer).await {
+            // the status has been sent already, the client gets a truncated archive
+            warn!("Zip download of {:?} failed: {}", dir, e);
+        }
+    });
+    Response::builder()
+        .header(CONTENT_TYPE, "application/zip")
+        .header(
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.zip\"", name),
+        )
+        .body(state.body(reader))
+        .map_err(|_| HttpError::Internal)
+}
+
+/// Every file under `dir` the caller may download, relative to `dir` and sorted
+async fn collect_files(
+    state: &HtpServeState,
+    dir: &Path,
+    headers: &HeaderMap,
+) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        let rules = AccessRules::resolve(&state.path, &current).await?;
+        let mut entries = tokio::fs::read_dir(&current).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let is_share = path
+                .strip_prefix(&state.path)
+                .is_ok_and(|rel| rel.starts_with(SHARE_DIR));
+            if is_share || state.is_excluded(&path) {
+                continue;
+            }
+            // symlinked directories aren't followed, they could loop
+            if entry.file_type().await?.is_dir() {
+                if rules.check(&path, headers) == AccessDecision::Allow {
+                    dirs.push(path);
+                }
+            } else if path.is_file() && rules.check(&path, headers) == AccessDecision::Allow {
+                if let Ok(rel) = path.strip_prefix(dir) {
+                    files.push(rel.to_path_buf());
+                }
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Async counterpart of [`write_zip`] for writers that can't seek, e.g. a response body
+async fn stream_zip<W: AsyncWrite + Unpin>(
+    root: &Path,
+    files: &[PathBuf],
+    writer: W,
+) -> Result<()> {
+    let mut zip = ZipFileWriter::with_tokio(writer);
+    for rel in files {
+        let mut file = tokio::fs::File::open(root.join(rel)).await?;
+        let modified: DateTime<Utc> = file.metadata().await?.modified()?.into();
+        let entry = ZipEntryBuilder::new(entry_name(rel).into(), Compression::Deflate)
+            .last_modification_date(ZipDateTime::from_chrono(&modified));
+        let mut entry = zip.write_entry_stream(entry).await?.compat_write();
+        tokio::io::copy(&mut file, &mut entry).await?;
+        entry.into_inner().close().await?;
+    }
+    zip.close().await?;
+    Ok(())
+}
+
+/// Zip `files`, given relative to `root`, keeping their relative paths as entry names
+pub(crate) fn write_zip<W: Write + Seek>(root: &Path, files: &[PathBuf], writer: W) -> Result<W> {
+    let mut zip = ZipWriter::new(writer);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for rel in files {
+        zip.start_file(entry_name(rel), options)?;
+        std::io::copy(&mut File::open(root.join(rel))?, &mut zip)?;
+    }
+    Ok(zip.finish()?)
+}
+
+/// Zip entry names always use `/`, whatever the platform
+pub(super) fn entry_name(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{http_access::ACCESS_FILE, http_serve::tests::test_state};
+    use axum::{http::StatusCode, response::IntoResponse};
+    use std::io::{Cursor, Read};
+
+    #[tokio::test]
+    async fn test_archive_handler() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join("rcli-http-archive");
+        std::fs::create_dir_all(dir.join("docs"))?;
+        std::fs::write(dir.join(ACCESS_FILE), "deny = [\"*.key\"]\n")?;
+        std::fs::write(dir.join("a.txt"), "hello")?;
+        std::fs::write(dir.join("docs/b.txt"), "world")?;
+        std::fs::write(dir.join("server.key"), "secret")?;
+        let state = test_state(dir.clone());
+        let archive = |body: &'static str| {
+            archive_handler(
+                State(state.clone()),
+                HeaderMap::new(),
+                Bytes::from_static(body.as_bytes()),
+            )
+        };
+
+        let response = archive(r#"{"paths":["a.txt","docs/b.txt"]}"#).await;
+        let response = response.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let mut zip = zip::ZipArchive::new(Cursor::new(body))?;
+        let mut content = String::new();
+        zip.by_name("docs/b.txt")?.read_to_string(&mut content)?;
+        assert_eq!(content, "world");
+        assert_eq!(zip.len(), 2);
+
+        let status = |response: Result<Response, HttpError>| response.into_response().status();
+        assert_eq!(
+            status(archive(r#"{"paths":["server.key"]}"#).await),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(archive(r#"{"paths":["../a.txt"]}"#).await),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(archive(r#"{"paths":[]}"#).await),
+            StatusCode::BAD_REQUEST
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            "application/x-www-form-urlencoded".parse().unwrap(),
+        );
+        let response = archive_handler(
+            State(state),
+            headers,
+            Bytes::from_static(b"paths=a.txt&paths=docs%2Fb.txt"),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        Ok(())
+    }
+}
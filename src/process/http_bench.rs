@@ -0,0 +1,125 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use reqwest::Url;
+
+use crate::{RcliError, Result};
+
+/// What a load run measured
+#[derive(Debug)]
+pub struct HttpBenchReport {
+    pub requests: usize,
+    /// requests that got no response at all: refused, reset, timed out...
+    pub errors: usize,
+    /// responses by status code
+    pub statuses: BTreeMap<u16, usize>,
+    pub elapsed: Duration,
+    /// latency of every answered request, sorted
+    pub latencies: Vec<Duration>,
+}
+
+impl HttpBenchReport {
+    pub fn throughput(&self) -> f64 {
+        self.requests as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Nearest-rank percentile of the latencies, `None` when nothing was answered
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let rank = (p / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        Some(self.latencies[rank.clamp(1, self.latencies.len()) - 1])
+    }
+}
+
+/// GET `url` `requests` times from `concurrency` workers sharing one connection pool.
+/// Latency covers the whole response, body included.
+pub async fn process_http_bench(
+    url: &str,
+    concurrency: usize,
+    requests: usize,
+) -> Result<HttpBenchReport> {
+    let url = Url::parse(url).map_err(|e| RcliError::format(format!("{}: {}", url, e)))?;
+    let client = reqwest::Client::new();
+    let issued = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+    let workers: Vec<_> = (0..concurrency.clamp(1, requests.max(1)))
+        .map(|_| {
+            let (client, url, issued) = (client.clone(), url.clone(), issued.clone());
+            tokio::spawn(async move {
+                let mut results = Vec::new();
+                while issued.fetch_add(1, Ordering::Relaxed) < requests {
+                    let sent = Instant::now();
+                    let status = match client.get(url.clone()).send().await {
+                        Ok(response) => {
+                            let status = response.status().as_u16();
+                            response.bytes().await.map(|_| status)
+                        }
+                        Err(e) => Err(e),
+                    };
+                    results.push(status.map(|status| (status, sent.elapsed())));
+                }
+                results
+            })
+        })
+        .collect();
+
+    let mut report = HttpBenchReport {
+        requests,
+        errors: 0,
+        statuses: BTreeMap::new(),
+        elapsed: Duration::ZERO,
+        latencies: Vec::with_capacity(requests),
+    };
+    for worker in workers {
+        let results = worker
+            .await
+            .map_err(|e| RcliError::Unsupported(format!("Benchmark worker failed: {}", e)))?;
+        for result in results {
+            match result {
+                Ok((status, latency)) => {
+                    *report.statuses.entry(status).or_default() += 1;
+                    report.latencies.push(latency);
+                }
+                Err(_) => report.errors += 1,
+            }
+        }
+    }
+    report.elapsed = start.elapsed();
+    report.latencies.sort_unstable();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_process_http_bench() -> anyhow::Result<()> {
+        use axum::{routing::get, Router};
+        let app = Router::new().route("/", get(|| async { "ok" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let report = process_http_bench(&format!("http://{}/", addr), 4, 50).await?;
+        assert_eq!(report.requests, 50);
+        assert_eq!(report.errors, 0);
+        assert_eq!(report.statuses[&200], 50);
+        assert_eq!(report.latencies.len(), 50);
+        assert!(report.percentile(50.0) <= report.percentile(99.0));
+        assert_eq!(report.percentile(100.0), report.latencies.last().copied());
+
+        let missing = process_http_bench(&format!("http://{}/missing", addr), 2, 4).await?;
+        assert_eq!(missing.statuses[&404], 4);
+        assert!(process_http_bench("not a url", 1, 1).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use std::net::SocketAddr;
+
+use axum::{
+    body::Bytes,
+    http::{header::CONTENT_TYPE, HeaderMap, Method, StatusCode, Uri},
+    Router,
+};
+use chrono::Local;
+use serde_json::Value;
+use tracing::info;
+
+use crate::Result;
+
+/// Answer every request on `port` with `status`, handing each one to `on_request`
+/// formatted as it arrives, e.g. to see what a webhook actually sends
+pub async fn process_http_echo(
+    port: u16,
+    status: StatusCode,
+    on_request: impl Fn(String) + Clone + Send + Sync + 'static,
+) -> Result<()> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!("Echoing requests on {}", addr);
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    axum::serve(listener, echo_router(status, on_request)).await?;
+    Ok(())
+}
+
+fn echo_router(
+    status: StatusCode,
+    on_request: impl Fn(String) + Clone + Send + Sync + 'static,
+) -> Router {
+    Router::new().fallback(
+        move |method: Method, uri: Uri, headers: HeaderMap, body: Bytes| {
+            let on_request = on_request.clone();
+            async move {
+                on_request(format_request(&method, &uri, &headers, &body));
+                status
+            }
+        },
+    )
+}
+
+/// The request line, headers and body; JSON bodies are pretty-printed
+fn format_request(method: &Method, uri: &Uri, headers: &HeaderMap, body: &[u8]) -> String {
+    let mut out = format!(
+        "--- {} {} {}\n",
+        Local::now().format("%H:%M:%S%.3f"),
+        method,
+        uri
+    );
+    for (name, value) in headers {
+        out.push_str(&format!(
+            "{}: {}\n",
+            name,
+            String::from_utf8_lossy(value.as_bytes())
+        ));
+    }
+    if body.is_empty() {
+        return out;
+    }
+    out.push('\n');
+    let is_json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("json"));
+    let pretty = is_json
+        .then(|| serde_json::from_slice::<Value>(body).ok())
+        .flatten()
+        .and_then(|json| serde_json::to_string_pretty(&json).ok());
+    match (pretty, std::str::from_utf8(body)) {
+        (Some(pretty), _) => out.push_str(&pretty),
+        (None, Ok(text)) => out.push_str(text),
+        (None, Err(_)) => out.push_str(&format!("<{} bytes of binary data>", body.len())),
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_request() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        let uri: Uri = "/hooks/github?delivery=1".parse().unwrap();
+        let out = format_request(&Method::POST, &uri, &headers, br#"{"action":"opened"}"#);
+        assert!(out.contains(" POST /hooks/github?delivery=1\n"));
+        assert!(out.contains("content-type: application/json\n"));
+        assert!(out.ends_with("{\n  \"action\": \"opened\"\n}\n"));
+
+        let out = format_request(&Method::PUT, &uri, &HeaderMap::new(), &[0xff, 0xfe]);
+        assert!(out.ends_with("<2 bytes of binary data>\n"));
+    }
+
+    #[tokio::test]
+    async fn test_echo_router() {
+        use tower::ServiceExt;
+
+        let request = axum::http::Request::builder()
+            .method(Method::DELETE)
+            .uri("/any/path")
+            .body(axum::body::Body::from("bye"))
+            .unwrap();
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let on_request = {
+            let seen = seen.clone();
+            move |request| seen.lock().unwrap().push(request)
+        };
+        let response = echo_router(StatusCode::ACCEPTED, on_request)
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert!(seen[0].contains(" DELETE /any/path\n"));
+        assert!(seen[0].ends_with("\nbye\n"));
+    }
+}
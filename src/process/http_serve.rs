@@ -1,54 +1,399 @@
-use anyhow::Result;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{
+        header::{CONTENT_LENGTH, HOST, LOCATION, STRICT_TRANSPORT_SECURITY, WWW_AUTHENTICATE},
+        HeaderMap, HeaderValue, StatusCode, Uri,
+    },
+    middleware,
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
     Router,
 };
-use std::{net::SocketAddr, path::PathBuf, sync::Arc};
-use tokio::fs;
+use axum_server::tls_rustls::RustlsConfig;
+use glob::Pattern;
+use pulldown_cmark::{html, Options, Parser};
+use serde::Deserialize;
+use std::{
+    io::Cursor,
+    net::SocketAddr,
+    path::{Component, PathBuf},
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncReadExt},
+};
 
-use tower_http::services::ServeDir;
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower_http::{set_header::SetResponseHeaderLayer, timeout::TimeoutLayer};
 use tracing::info;
 
+use super::{
+    http_access::{require_token, AccessDecision, AccessRules},
+    http_admin::{admin_router, track_requests, ServeSettings, ServeStats, ADMIN_PREFIX},
+    http_archive::{archive_handler, zip_dir_response, ARCHIVE_PATH},
+    http_throttle::{throttled_body, Throttle},
+    http_webdav::webdav,
+    SHARE_DIR,
+};
+use crate::{ByteRate, Result, Secret};
+
+const DEFAULT_INDEX: &str = "index.html";
+const MARKDOWN_STYLE: &str = "body{max-width:50em;margin:2em auto;padding:0 1em;\
+font:16px/1.6 system-ui,sans-serif;color:#222}pre,code{background:#f4f4f4;border-radius:3px}\
+pre{padding:.8em;overflow:auto}code{padding:.1em .3em}pre code{padding:0}\
+table{border-collapse:collapse}th,td{border:1px solid #ccc;padding:.3em .6em}\
+blockquote{margin-left:0;padding-left:1em;border-left:3px solid #ccc;color:#555}img{max-width:100%}";
+
+#[derive(Debug, Clone)]
+pub struct HttpServeConfig {
+    pub path: PathBuf,
+    pub port: u16,
+    /// token guarding the `/_rcli` admin UI
+    pub admin_token: Secret,
+    /// token every non-admin request must carry, see `require_token`
+    pub access_token: Option<Secret>,
+    pub tls: Option<TlsConfig>,
+    /// serve `index.html` (or `index`) for paths that don't exist, for single-page apps
+    pub spa: bool,
+    /// file served at directory roots instead of the HTML listing
+    pub index: Option<String>,
+    /// requests handled at once, the others wait for a free slot
+    pub max_connections: Option<usize>,
+    /// requests taking longer are answered with 408 Request Timeout
+    pub timeout: Option<Duration>,
+    /// answer WebDAV requests so the directory can be mounted as a network drive;
+    /// uploads start enabled
+    pub webdav: bool,
+    /// serve `.md` files as HTML pages instead of plain text
+    pub render_markdown: bool,
+    /// names kept out of listings and answered with 404, dotfiles by default
+    pub hide: Vec<Pattern>,
+    /// more names treated like hidden ones, e.g. `*.key`
+    pub exclude: Vec<Pattern>,
+    /// bandwidth of each download, to try clients on a slow network
+    pub rate_limit: Option<ByteRate>,
+    /// bandwidth shared by all downloads at once
+    pub total_rate_limit: Option<ByteRate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// plain HTTP port answering every request with a redirect to HTTPS
+    pub redirect_port: Option<u16>,
+    /// `Strict-Transport-Security` max-age in seconds
+    pub hsts: Option<u64>,
+}
+
 #[derive(Debug)]
-struct HtpServeState {
-    path: PathBuf,
-}
-pub async fn process_http_serve(path: PathBuf, port: u16) -> Result<()> {
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    info!("Serving {:?} on {}", path, addr);
-    let state = HtpServeState { path: path.clone() };
-    let dir_service = ServeDir::new(path);
-    let router = Router::new()
-        .nest_service("/tower", dir_service)
-        .route("/*path", get(file_handler))
-        .with_state(Arc::new(state));
-
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, router).await?;
-    // let server = rouille::Server::new(format!("
+pub(crate) struct HtpServeState {
+    pub(crate) path: PathBuf,
+    pub(crate) port: u16,
+    pub(crate) admin_token: Secret,
+    pub(crate) access_token: Option<Secret>,
+    pub(crate) spa: bool,
+    pub(crate) index: Option<String>,
+    pub(crate) render_markdown: bool,
+    pub(crate) hide: Vec<Pattern>,
+    pub(crate) exclude: Vec<Pattern>,
+    pub(crate) rate_limit: Option<ByteRate>,
+    pub(crate) total_throttle: Option<Arc<Throttle>>,
+    pub(crate) settings: ServeSettings,
+    pub(crate) stats: ServeStats,
+}
+
+impl HttpServeConfig {
+    fn scheme(&self) -> &'static str {
+        if self.tls.is_some() {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    /// Link to the admin UI, token included
+    pub fn admin_url(&self) -> String {
+        format!(
+            "{}://localhost:{}{}#token={}",
+            self.scheme(),
+            self.port,
+            ADMIN_PREFIX,
+            self.admin_token.expose_str()
+        )
+    }
+
+    /// Link that lets its holder browse the served directory, when an access token is set
+    pub fn share_url(&self) -> Option<String> {
+        self.access_token.as_ref().map(|token| {
+            format!(
+                "{}://localhost:{}/?token={}",
+                self.scheme(),
+                self.port,
+                token.expose_str()
+            )
+        })
+    }
+}
+
+impl HtpServeState {
+    pub(crate) fn new(config: HttpServeConfig) -> Self {
+        Self {
+            path: config.path,
+            port: config.port,
+            admin_token: config.admin_token,
+            access_token: config.access_token,
+            spa: config.spa,
+            index: config.index,
+            render_markdown: config.render_markdown,
+            hide: config.hide,
+            exclude: config.exclude,
+            rate_limit: config.rate_limit,
+            total_throttle: config
+                .total_rate_limit
+                .map(|rate| Arc::new(Throttle::new(rate))),
+            settings: ServeSettings {
+                listing: true.into(),
+                uploads: config.webdav.into(),
+            },
+            stats: ServeStats::new(),
+        }
+    }
+
+    /// The file shown at directory roots, if any: `--index`, else `index.html` for SPAs
+    fn index_file(&self) -> Option<&str> {
+        self.index.as_deref().or(self.spa.then_some(DEFAULT_INDEX))
+    }
+
+    /// The per-response and the shared throttle, each only when its limit is set
+    fn throttles(&self) -> Vec<Arc<Throttle>> {
+        self.rate_limit
+            .map(|rate| Arc::new(Throttle::new(rate)))
+            .into_iter()
+            .chain(self.total_throttle.clone())
+            .collect()
+    }
+
+    /// A response body streaming `reader` within the configured rate limits
+    pub(crate) fn body<R>(&self, reader: R) -> Body
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+    {
+        throttled_body(reader, self.throttles())
+    }
+
+    /// A response body for content already in memory, sent in one piece unless rate limited
+    fn content_body(&self, content: String) -> Body {
+        let throttles = self.throttles();
+        if throttles.is_empty() {
+            return Body::from(content);
+        }
+        throttled_body(Cursor::new(content.into_bytes()), throttles)
+    }
+
+    /// Whether a directory entry named `name` matches `--hide` or `--exclude`
+    pub(crate) fn is_excluded_name(&self, name: &str) -> bool {
+        self.hide
+            .iter()
+            .chain(&self.exclude)
+            .any(|pattern| pattern.matches(name))
+    }
+
+    /// Whether `path`, or any directory it is in below the served one, is excluded
+    pub(crate) fn is_excluded(&self, path: &std::path::Path) -> bool {
+        let Ok(rel) = path.strip_prefix(&self.path) else {
+            return false;
+        };
+        // one-time shares live in a dot directory, but their links must keep working
+        let hide = rel.parent() != Some(std::path::Path::new(SHARE_DIR));
+        rel.components().any(|c| match c {
+            Component::Normal(name) => {
+                let name = name.to_string_lossy();
+                self.exclude.iter().any(|pattern| pattern.matches(&name))
+                    || hide && self.hide.iter().any(|pattern| pattern.matches(&name))
+            }
+            _ => false,
+        })
+    }
+}
+
+pub async fn process_http_serve(config: HttpServeConfig) -> Result<()> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+    info!("Serving {:?} on {}", config.path, addr);
+    let tls = config.tls.clone();
+    let (max_connections, timeout) = (config.max_connections, config.timeout);
+    let webdav_enabled = config.webdav;
+    let state = Arc::new(HtpServeState::new(config));
+    let mut router = Router::new()
+        .nest(ADMIN_PREFIX, admin_router())
+        .route("/", get(root_handler))
+        .route(ARCHIVE_PATH, post(archive_handler))
+        .route("/*path", get(file_handler));
+    if webdav_enabled {
+        router = router.layer(middleware::from_fn_with_state(state.clone(), webdav));
+    }
+    let mut router = router
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            track_requests,
+        ))
+        .with_state(state);
+    router = with_limits(router, max_connections, timeout);
+
+    let Some(tls) = tls else {
+        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        axum::serve(listener, router).await?;
+        return Ok(());
+    };
+    if let Some(max_age) = tls.hsts {
+        router = router.layer(SetResponseHeaderLayer::if_not_present(
+            STRICT_TRANSPORT_SECURITY,
+            HeaderValue::from_str(&format!("max-age={}", max_age))?,
+        ));
+    }
+    // an error only means a provider is already installed
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let rustls_config = RustlsConfig::from_pem_file(&tls.cert, &tls.key).await?;
+    let https = axum_server::bind_rustls(addr, rustls_config).serve(router.into_make_service());
+    match tls.redirect_port {
+        Some(redirect_port) => {
+            let redirect_addr = SocketAddr::from(([0, 0, 0, 0], redirect_port));
+            info!("Redirecting http://{} to https", redirect_addr);
+            let listener = tokio::net::TcpListener::bind(&redirect_addr).await?;
+            let redirect = axum::serve(listener, redirect_router(addr.port()).into_make_service());
+            tokio::try_join!(https, redirect)?;
+        }
+        None => https.await?,
+    }
     Ok(())
 }
 
+/// Bound how many requests run at once and how long each may take, so a few slow or
+/// stalled clients can't tie up the server
+fn with_limits(
+    mut router: Router,
+    max_connections: Option<usize>,
+    timeout: Option<Duration>,
+) -> Router {
+    if let Some(timeout) = timeout {
+        router = router.layer(TimeoutLayer::new(timeout));
+    }
+    // one limit shared by every route, `ConcurrencyLimitLayer` would count each separately
+    if let Some(max) = max_connections {
+        router = router.layer(GlobalConcurrencyLimitLayer::new(max));
+    }
+    router
+}
+
+fn redirect_router(https_port: u16) -> Router {
+    Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+        let host = headers
+            .get(HOST)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("localhost");
+        match HeaderValue::from_str(&https_location(host, &uri, https_port)) {
+            Ok(location) => (StatusCode::MOVED_PERMANENTLY, [(LOCATION, location)]).into_response(),
+            Err(_) => StatusCode::BAD_REQUEST.into_response(),
+        }
+    })
+}
+
+/// Rewrite a plain HTTP request target to the same host and path on the HTTPS port
+fn https_location(host: &str, uri: &Uri, https_port: u16) -> String {
+    // drop the plain HTTP port, keeping bracketed IPv6 hosts intact
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    if https_port == 443 {
+        format!("https://{}{}", host, path)
+    } else {
+        format!("https://{}:{}{}", host, https_port, path)
+    }
+}
+
+/// The served directory itself, which is where shared `?token=` links point
+async fn root_handler(
+    state: State<Arc<HtpServeState>>,
+    query: Query<FileQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, HttpError> {
+    file_handler(state, Path(String::new()), query, headers).await
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct FileQuery {
+    /// `zip` downloads a directory as one archive instead of listing it
+    download: Option<String>,
+}
+
 async fn file_handler(
     State(state): State<Arc<HtpServeState>>,
     Path(path): Path<String>,
+    Query(query): Query<FileQuery>,
+    headers: HeaderMap,
 ) -> Result<impl IntoResponse, HttpError> {
-    let p = std::path::Path::new(&state.path).join(path.clone());
+    let zip = match query.download.as_deref() {
+        None => false,
+        Some("zip") => true,
+        Some(other) => {
+            return Err(HttpError::BadRequest(format!(
+                "Unsupported download format: {}, expected zip",
+                other
+            )))
+        }
+    };
+    // `..` and absolute paths would escape the served directory
+    let rel = std::path::Path::new(&path);
+    if !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(HttpError::NotFound(path.clone()));
+    }
+    let mut p = state.path.join(rel);
+    if p.is_dir() && !zip {
+        if let Some(index) = state.index_file().map(|index| p.join(index)) {
+            if index.is_file() {
+                p = index;
+            }
+        }
+    } else if (!p.exists() || state.is_excluded(&p)) && state.spa {
+        // client-side routes all load the app, which then renders the path itself
+        p = state.path.join(state.index_file().unwrap_or(DEFAULT_INDEX));
+    }
     info!("Reading file: {:?}", p);
-    if !p.exists() {
+    // excluded files are indistinguishable from missing ones
+    if !p.exists() || state.is_excluded(&p) {
         return Err(HttpError::NotFound(path.clone()));
     }
+    let rules = AccessRules::resolve(&state.path, &p)
+        .await
+        .map_err(|_| HttpError::Internal)?;
+    match rules.check(&p, &headers) {
+        AccessDecision::Allow => {}
+        // denied files are indistinguishable from missing ones
+        AccessDecision::Denied => return Err(HttpError::NotFound(path.clone())),
+        AccessDecision::Unauthorized => return Err(HttpError::Unauthorized),
+        AccessDecision::ListingHidden => return Err(HttpError::Forbidden),
+    }
     // if p is a directory, generate a directory listing
     if p.is_dir() {
-        match process_dir(p).await {
+        if !state.settings.listing.load(Ordering::Relaxed) {
+            return Err(HttpError::Forbidden);
+        }
+        if zip {
+            return Ok(zip_dir_response(&state, p, &headers).await);
+        }
+        match process_dir(&state, p, &rules).await {
             Ok(content) => {
                 return Ok(Response::builder()
                     .status(StatusCode::OK)
                     .header("Content-Type", "text/html")
-                    .body(content)
+                    .header(CONTENT_LENGTH, content.len())
+                    .body(state.content_body(content))
                     .map_err(|_| HttpError::Internal));
             }
             Err(_) => {
@@ -57,50 +402,142 @@ async fn file_handler(
         }
     }
 
-    // return (StatusCode::OK, content);
-    match tokio::fs::read_to_string(p).await {
-        Ok(content) => {
-            let response = Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", "text/plain")
-                .body(content)
-                .map_err(|_| HttpError::Internal)?;
-
-            Ok(Ok(response))
-        }
-        Err(_) => Err(HttpError::Internal),
+    if zip {
+        return Err(HttpError::BadRequest(
+            "Only directories can be downloaded as zip".to_string(),
+        ));
     }
+    let mut file = fs::File::open(&p).await.map_err(|_| HttpError::Internal)?;
+    // shares created by `genpass --share` can only be downloaded once; checked on the
+    // file actually opened, which may be the SPA index instead of a missing share
+    if p.strip_prefix(&state.path)
+        .is_ok_and(|rel| rel.starts_with(SHARE_DIR))
+    {
+        fs::remove_file(&p).await.map_err(|_| HttpError::Internal)?;
+    }
+    let is_markdown = state.render_markdown && p.extension().is_some_and(|ext| ext == "md");
+    let is_html = is_markdown
+        || p.extension()
+            .is_some_and(|ext| ext == "html" || ext == "htm");
+    let (len, body) = if is_markdown {
+        let mut markdown = String::new();
+        file.read_to_string(&mut markdown)
+            .await
+            .map_err(|_| HttpError::Internal)?;
+        let title = p.file_name().unwrap_or_default().to_string_lossy();
+        let content = render_markdown(&markdown, &title);
+        (content.len() as u64, state.content_body(content))
+    } else {
+        let len = file
+            .metadata()
+            .await
+            .map_err(|_| HttpError::Internal)?
+            .len();
+        (len, state.body(file))
+    };
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(
+            "Content-Type",
+            if is_html { "text/html" } else { "text/plain" },
+        )
+        .header(CONTENT_LENGTH, len)
+        .body(body)
+        .map_err(|_| HttpError::Internal)?;
+    Ok(Ok(response))
+}
+
+/// A standalone HTML page for a markdown document, GitHub flavored extensions included
+fn render_markdown(markdown: &str, title: &str) -> String {
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_FOOTNOTES,
+    );
+    let mut body = String::new();
+    html::push_html(&mut body, parser);
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title>\
+         <style>{}</style></head><body>{}</body></html>",
+        escape_html(title),
+        MARKDOWN_STYLE,
+        body
+    )
+}
+
+pub(super) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
-async fn process_dir(path: impl AsRef<std::path::Path>) -> Result<String> {
+async fn process_dir(
+    state: &HtpServeState,
+    path: impl AsRef<std::path::Path>,
+    rules: &AccessRules,
+) -> Result<String> {
     let mut content = String::new();
-    content.push_str("<html><body><ul>");
+    content.push_str(&format!(
+        "<html><body><form method=\"post\" action=\"{}\"><ul>",
+        ARCHIVE_PATH
+    ));
     let mut entries = fs::read_dir(path).await?;
     // Iterate over directory entries using StreamExt
     while let Some(entry) = entries.next_entry().await? {
         let entry_path = entry.path();
         let name = entry_path.file_name().unwrap().to_str().unwrap();
+        if rules.is_denied(name) || state.is_excluded_name(name) {
+            continue;
+        }
+        // only files can be picked for the zip download
+        let checkbox = match entry_path.strip_prefix(&state.path) {
+            Ok(rel) if entry_path.is_file() => format!(
+                "<input type=\"checkbox\" name=\"paths\" value=\"{}\"> ",
+                escape_html(&rel.display().to_string())
+            ),
+            _ => String::new(),
+        };
+        // file names may hold `"`, `<` and `&`, which must not turn into markup
         content.push_str(&format!(
-            "<li><a href=\"{}\">{}</a></li>",
-            entry_path.display().to_string().trim_start_matches('.'),
-            name
+            "<li>{}<a href=\"{}\">{}</a></li>",
+            checkbox,
+            escape_html(entry_path.display().to_string().trim_start_matches('.')),
+            escape_html(name)
         ));
     }
 
-    content.push_str("</ul></body></html>");
+    content.push_str("</ul><button type=\"submit\">Download selected as zip</button></form>");
+    content.push_str("<p><a href=\"?download=zip\">Download this folder as zip</a></p>");
+    content.push_str("</body></html>");
 
     Ok(content)
 }
 
 #[derive(Debug)]
-enum HttpError {
+pub(crate) enum HttpError {
+    BadRequest(String),
     NotFound(String),
+    Unauthorized,
+    Forbidden,
     Internal,
 }
 
 impl IntoResponse for HttpError {
     fn into_response(self) -> axum::response::Response {
         let (code, msg) = match self {
+            HttpError::Unauthorized => {
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    [(WWW_AUTHENTICATE, "Basic realm=\"rcli\"")],
+                    "Unauthorized",
+                )
+                    .into_response();
+            }
+            HttpError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            HttpError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden".to_string()),
             HttpError::NotFound(resource) => (
                 StatusCode::NOT_FOUND,
                 format!("{} not found", resource).to_string(),
@@ -115,19 +552,395 @@ impl IntoResponse for HttpError {
 }
 
 #[cfg(test)]
-mod tests {
+pub(super) mod tests {
     use super::*;
+    use crate::process::http_access::ACCESS_FILE;
     use axum::http::StatusCode;
     use std::path::PathBuf;
 
+    pub(crate) fn test_config(path: PathBuf) -> HttpServeConfig {
+        HttpServeConfig {
+            path,
+            port: 8080,
+            admin_token: Secret::new("token".to_string()),
+            access_token: None,
+            tls: None,
+            spa: false,
+            index: None,
+            max_connections: None,
+            timeout: None,
+            webdav: false,
+            render_markdown: false,
+            hide: Vec::new(),
+            exclude: Vec::new(),
+            rate_limit: None,
+            total_rate_limit: None,
+        }
+    }
+
+    pub(crate) fn test_state(path: PathBuf) -> Arc<HtpServeState> {
+        Arc::new(HtpServeState::new(test_config(path)))
+    }
+
     #[tokio::test]
     async fn test_file_handler() {
-        let state = Arc::new(HtpServeState {
-            path: PathBuf::from("."),
-        });
-        let result = file_handler(State(state), Path("Cargo.toml".to_string())).await;
+        let state = test_state(PathBuf::from("."));
+        let result = file_handler(
+            State(state),
+            Path("Cargo.toml".to_string()),
+            Query(FileQuery::default()),
+            HeaderMap::new(),
+        )
+        .await;
         assert!(result.is_ok());
         let response = result.unwrap().into_response();
         assert_eq!(response.status(), StatusCode::OK);
+
+        for path in ["../Cargo.toml", "/etc/hostname"] {
+            let result = file_handler(
+                State(test_state(PathBuf::from("src"))),
+                Path(path.to_string()),
+                Query(FileQuery::default()),
+                HeaderMap::new(),
+            )
+            .await;
+            assert!(matches!(result, Err(HttpError::NotFound(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_handler_streams_bytes() -> anyhow::Result<()> {
+        use tower::ServiceExt;
+
+        let dir = std::env::temp_dir().join("rcli-http-stream");
+        std::fs::create_dir_all(&dir)?;
+        // not UTF-8, served as is
+        let data: Vec<u8> = (0..=255).cycle().take(40_000).collect();
+        std::fs::write(dir.join("data.bin"), &data)?;
+        let state = test_state(dir);
+        let app = Router::new()
+            .route("/*path", get(file_handler))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                track_requests,
+            ))
+            .with_state(state.clone());
+        let request = axum::http::Request::builder()
+            .uri("/data.bin")
+            .body(Body::empty())?;
+        let response = app.oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_LENGTH], "40000");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        assert_eq!(body, data);
+        // counted as sent, though a streamed body has no size up front
+        assert_eq!(state.stats.bytes_sent.load(Ordering::Relaxed), 40_000);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_dir_escapes_names() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join("rcli-http-escape");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("a\"><script>.txt"), "")?;
+        let state = test_state(dir.clone());
+        let listing = process_dir(&state, &dir, &AccessRules::default()).await?;
+        assert!(!listing.contains("<script>"), "{}", listing);
+        assert!(listing.contains("value=\"a&quot;&gt;&lt;script&gt;.txt\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let page = render_markdown("# Title\n\n| a |\n|---|\n| 1 |\n", "<notes>.md");
+        assert!(page.contains("<title>&lt;notes&gt;.md</title>"));
+        assert!(page.contains("<h1>Title</h1>"));
+        assert!(page.contains("<table>"));
+    }
+
+    #[tokio::test]
+    async fn test_file_handler_access_rules() {
+        let dir = std::env::temp_dir().join("rcli-http-access");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join(ACCESS_FILE),
+            "deny = [\"*.key\"]\nlisting = false\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("server.key"), "secret").unwrap();
+        std::fs::create_dir_all(dir.join("docs/keys.key")).unwrap();
+        std::fs::write(dir.join("docs/keys.key/file.txt"), "secret").unwrap();
+        let state = test_state(dir);
+        // a denied directory hides everything in it
+        for path in ["server.key", "docs/keys.key", "docs/keys.key/file.txt"] {
+            let response = file_handler(
+                State(state.clone()),
+                Path(path.to_string()),
+                Query(FileQuery::default()),
+                HeaderMap::new(),
+            )
+            .await
+            .into_response();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+        }
+        let response = file_handler(
+            State(state),
+            Path("".to_string()),
+            Query(FileQuery::default()),
+            HeaderMap::new(),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_file_handler_hide_and_exclude() {
+        let dir = std::env::temp_dir().join("rcli-http-exclude");
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(dir.join(".env"), "SECRET=1").unwrap();
+        std::fs::write(dir.join(".git").join("config"), "[core]").unwrap();
+        std::fs::write(dir.join("db.secret"), "secret").unwrap();
+        std::fs::write(dir.join("notes.txt"), "notes").unwrap();
+        let shared = crate::process_share_secret(b"s3cret", &dir).unwrap();
+        let state = Arc::new(HtpServeState::new(HttpServeConfig {
+            hide: vec![Pattern::new(".*").unwrap()],
+            exclude: vec![Pattern::new("*.secret").unwrap()],
+            ..test_config(dir)
+        }));
+        let get = |path: &str| {
+            file_handler(
+                State(state.clone()),
+                Path(path.to_string()),
+                Query(FileQuery::default()),
+                HeaderMap::new(),
+            )
+        };
+        for path in [".env", ".git/config", "db.secret", SHARE_DIR] {
+            let response = get(path).await.into_response();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+        }
+        let response = get("notes.txt").await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get(&shared.path).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get("").await.into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let listing = String::from_utf8_lossy(&body);
+        assert!(listing.contains("notes.txt"));
+        for name in [".env", ".git", "db.secret", SHARE_DIR] {
+            assert!(!listing.contains(name), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_handler_one_time_share() {
+        let dir = std::env::temp_dir().join("rcli-http-share");
+        let shared = crate::process_share_secret(b"s3cret", &dir).unwrap();
+        let state = test_state(dir);
+        let response = file_handler(
+            State(state.clone()),
+            Path(shared.path.clone()),
+            Query(FileQuery::default()),
+            HeaderMap::new(),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = file_handler(
+            State(state),
+            Path(shared.path),
+            Query(FileQuery::default()),
+            HeaderMap::new(),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_file_handler_zip_download() {
+        let dir = std::env::temp_dir().join("rcli-http-zip");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("docs/nested")).unwrap();
+        std::fs::create_dir_all(dir.join("private")).unwrap();
+        std::fs::write(dir.join("docs").join(ACCESS_FILE), "deny = [\"*.key\"]\n").unwrap();
+        std::fs::write(dir.join("docs/a.txt"), "hello").unwrap();
+        std::fs::write(dir.join("docs/nested/b.txt"), "world").unwrap();
+        std::fs::write(dir.join("docs/server.key"), "secret").unwrap();
+        std::fs::write(dir.join("docs/notes.secret"), "secret").unwrap();
+        std::fs::write(
+            dir.join("private").join(ACCESS_FILE),
+            "[auth]\nusername = \"a\"\npassword = \"b\"\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("private/c.txt"), "private").unwrap();
+        crate::process_share_secret(b"s3cret", &dir).unwrap();
+        let state = Arc::new(HtpServeState::new(HttpServeConfig {
+            exclude: vec![Pattern::new("*.secret").unwrap()],
+            ..test_config(dir)
+        }));
+        let get = |path: &str, download: &str| {
+            file_handler(
+                State(state.clone()),
+                Path(path.to_string()),
+                Query(FileQuery {
+                    download: Some(download.to_string()),
+                }),
+                HeaderMap::new(),
+            )
+        };
+        let entries = |body: axum::body::Bytes| {
+            let zip = zip::ZipArchive::new(Cursor::new(body)).unwrap();
+            let mut names = zip.file_names().map(String::from).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        let response = get("docs", "zip").await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["Content-Disposition"],
+            "attachment; filename=\"docs.zip\""
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(entries(body), ["a.txt", "nested/b.txt"]);
+
+        // shares and directories behind other credentials are left out of the root
+        let response = get("", "zip").await.into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(entries(body), ["docs/a.txt", "docs/nested/b.txt"]);
+
+        let response = get("private", "zip").await.into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = get("docs/a.txt", "zip").await.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = get("docs", "tar").await.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        // nothing outside the served directory is zipped up
+        let response = get("../rcli-http-zip/docs", "zip").await.into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_settings_toggles() {
+        let dir = std::env::temp_dir().join("rcli-http-listing");
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = test_state(dir.clone());
+        state.settings.listing.store(false, Ordering::Relaxed);
+        let response = file_handler(
+            State(state),
+            Path("".to_string()),
+            Query(FileQuery::default()),
+            HeaderMap::new(),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_file_handler_spa_index() {
+        let dir = std::env::temp_dir().join("rcli-http-spa");
+        std::fs::create_dir_all(dir.join("docs")).unwrap();
+        std::fs::write(dir.join("index.html"), "<html>app</html>").unwrap();
+        std::fs::write(dir.join("docs/README.md"), "docs").unwrap();
+        let state = Arc::new(HtpServeState::new(HttpServeConfig {
+            spa: true,
+            ..test_config(dir)
+        }));
+        let get = |path: &str| {
+            file_handler(
+                State(state.clone()),
+                Path(path.to_string()),
+                Query(FileQuery::default()),
+                HeaderMap::new(),
+            )
+        };
+        let body = |response: Response| async {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX);
+            String::from_utf8(body.await.unwrap().to_vec()).unwrap()
+        };
+
+        let response = get("users/42").await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Content-Type"], "text/html");
+        assert_eq!(body(response).await, "<html>app</html>");
+        assert_eq!(
+            body(get("").await.into_response()).await,
+            "<html>app</html>"
+        );
+        // directories without an index file still get a listing
+        assert!(body(get("docs").await.into_response())
+            .await
+            .contains("README.md"));
+
+        let state = Arc::new(HtpServeState::new(HttpServeConfig {
+            index: Some("README.md".to_string()),
+            ..test_config(state.path.clone())
+        }));
+        let response = file_handler(
+            State(state.clone()),
+            Path("docs".to_string()),
+            Query(FileQuery::default()),
+            HeaderMap::new(),
+        )
+        .await
+        .into_response();
+        assert_eq!(body(response).await, "docs");
+        let response = file_handler(
+            State(state),
+            Path("missing".to_string()),
+            Query(FileQuery::default()),
+            HeaderMap::new(),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_https_location() {
+        let uri: Uri = "/a/b.txt?x=1".parse().unwrap();
+        assert_eq!(
+            https_location("example.com:8080", &uri, 443),
+            "https://example.com/a/b.txt?x=1"
+        );
+        assert_eq!(
+            https_location("[::1]:8080", &uri, 8443),
+            "https://[::1]:8443/a/b.txt?x=1"
+        );
+        assert_eq!(
+            https_location("[::1]", &Uri::from_static("/"), 8443),
+            "https://[::1]:8443/"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_limits() {
+        use tower::ServiceExt;
+
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            "done"
+        };
+        let router = Router::new().route("/", get(slow));
+        let request = || axum::http::Request::new(axum::body::Body::empty());
+        let limited = with_limits(router.clone(), Some(1), Some(Duration::from_millis(50)));
+        let response = limited.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        let response = with_limits(router, Some(1), None)
+            .oneshot(request())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
     }
 }
@@ -0,0 +1,225 @@
+use std::{
+    fmt, io,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::body::{Body, Bytes, HttpBody};
+use http_body::Frame;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::mpsc,
+    time::Instant,
+};
+use tokio_util::io::ReaderStream;
+
+use crate::{RcliError, Result};
+
+/// Largest piece of a body sent at once, smaller for slow rates so they stay smooth
+const MAX_CHUNK: usize = 16 * 1024;
+
+/// A bandwidth like `1MBps`, `512KB/s` or `1.5MiBps`, in bytes per second
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteRate(pub u64);
+
+impl FromStr for ByteRate {
+    type Err = RcliError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || RcliError::format(format!("Invalid rate: {}, expected e.g. 1MBps", s));
+        let rate = s.trim();
+        let rate = rate
+            .strip_suffix("ps")
+            .or_else(|| rate.strip_suffix("/s"))
+            .unwrap_or(rate);
+        let rate = rate.strip_suffix('B').unwrap_or(rate);
+        let (rate, base) = match rate.strip_suffix('i') {
+            Some(rate) => (rate, 1024u64),
+            None => (rate, 1000),
+        };
+        let (number, multiplier) = match rate.chars().last().map(|c| c.to_ascii_uppercase()) {
+            Some('K') => (&rate[..rate.len() - 1], base),
+            Some('M') => (&rate[..rate.len() - 1], base.pow(2)),
+            Some('G') => (&rate[..rate.len() - 1], base.pow(3)),
+            _ if base == 1000 => (rate, 1),
+            // a bare `i` without a unit before it
+            _ => return Err(invalid()),
+        };
+        let bytes = number.trim().parse::<f64>().map_err(|_| invalid())? * multiplier as f64;
+        if !bytes.is_finite() || bytes < 1.0 {
+            return Err(invalid());
+        }
+        Ok(Self(bytes as u64))
+    }
+}
+
+impl fmt::Display for ByteRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            n if n >= 1_000_000 => write!(f, "{:.1} MB/s", n as f64 / 1e6),
+            n if n >= 1_000 => write!(f, "{:.1} KB/s", n as f64 / 1e3),
+            n => write!(f, "{} B/s", n),
+        }
+    }
+}
+
+/// Paces whatever is sent through it to a `ByteRate`. Each caller reserves the time
+/// its bytes take, so one throttle shared by several bodies splits the rate between them.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    rate: ByteRate,
+    next: Mutex<Instant>,
+}
+
+impl Throttle {
+    pub(crate) fn new(rate: ByteRate) -> Self {
+        Self {
+            rate,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until `len` more bytes may be sent
+    async fn wait(&self, len: usize) {
+        let start = {
+            let mut next = self.next.lock().unwrap();
+            // time left unused while idle doesn't add up to a burst later
+            let start = (*next).max(Instant::now());
+            *next = start + Duration::from_secs_f64(len as f64 / self.rate.0 as f64);
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+
+    fn chunk_size(&self) -> usize {
+        (self.rate.0 as usize / 10).clamp(1, MAX_CHUNK)
+    }
+}
+
+/// A response body streaming `reader`, paced by every one of `throttles`. A read error
+/// fails the body, so the client sees a broken transfer instead of a short file.
+pub(crate) fn throttled_body<R>(reader: R, throttles: Vec<Arc<Throttle>>) -> Body
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    let Some(chunk) = throttles.iter().map(|t| t.chunk_size()).min() else {
+        return Body::from_stream(ReaderStream::new(reader));
+    };
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut reader = reader;
+        let mut buf = vec![0u8; chunk];
+        loop {
+            let n = match reader.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    break;
+                }
+            };
+            for throttle in &throttles {
+                throttle.wait(n).await;
+            }
+            // the client went away
+            if tx
+                .send(Ok(Bytes::copy_from_slice(&buf[..n])))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+    Body::new(ChannelBody(rx))
+}
+
+/// The chunks, or the read error, the task pacing a throttled body hands over
+struct ChannelBody(mpsc::Receiver<io::Result<Bytes>>);
+
+impl HttpBody for ChannelBody {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<io::Result<Frame<Bytes>>>> {
+        self.0
+            .poll_recv(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map(Frame::data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_rate_from_str() -> Result<()> {
+        for (rate, bytes) in [
+            ("1MBps", 1_000_000),
+            ("512KB/s", 512_000),
+            ("1.5MiBps", 1_572_864),
+            ("64kB", 64_000),
+            ("300", 300),
+        ] {
+            assert_eq!(rate.parse::<ByteRate>()?, ByteRate(bytes), "{}", rate);
+        }
+        for rate in ["", "fast", "0.1Bps", "1iBps", "-1MBps"] {
+            assert!(rate.parse::<ByteRate>().is_err(), "{}", rate);
+        }
+        assert_eq!(ByteRate(1_500_000).to_string(), "1.5 MB/s");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_throttled_body() -> anyhow::Result<()> {
+        // 1000 byte chunks, one every 100ms
+        let data = vec![7u8; 3000];
+        let shared = Arc::new(Throttle::new(ByteRate(10_000)));
+        let start = Instant::now();
+        let body = throttled_body(std::io::Cursor::new(data.clone()), vec![shared.clone()]);
+        let received = axum::body::to_bytes(body, usize::MAX).await?;
+        assert_eq!(received, data);
+        // the first chunk goes right away, the rest at the rate
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(190), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+
+        // two bodies sharing the throttle take twice as long together
+        let start = Instant::now();
+        let [a, b] = [0, 1].map(|_| {
+            let body = throttled_body(std::io::Cursor::new(data.clone()), vec![shared.clone()]);
+            axum::body::to_bytes(body, usize::MAX)
+        });
+        let (a, b) = tokio::join!(a, b);
+        assert_eq!((a?.len(), b?.len()), (data.len(), data.len()));
+        assert!(start.elapsed() >= Duration::from_millis(490));
+        Ok(())
+    }
+
+    /// A source that fails on every read, like a file on a disk that went away
+    struct Broken;
+
+    impl AsyncRead for Broken {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            _: &mut tokio::io::ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Poll::Ready(Err(io::Error::other("disk gone")))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_throttled_body_read_error() {
+        let throttle = Arc::new(Throttle::new(ByteRate(1_000_000)));
+        let reader = std::io::Cursor::new(vec![7u8; 100]).chain(Broken);
+        let body = throttled_body(reader, vec![throttle]);
+        assert!(axum::body::to_bytes(body, usize::MAX).await.is_err());
+    }
+}
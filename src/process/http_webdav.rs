@@ -0,0 +1,513 @@
+use std::{
+    future::poll_fn,
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    sync::{atomic::Ordering, Arc},
+    time::SystemTime,
+};
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use tokio::{fs, io::AsyncWriteExt};
+use tracing::info;
+
+use super::{
+    http_access::{AccessDecision, AccessRules},
+    http_admin::ADMIN_PREFIX,
+    http_serve::{escape_html, HtpServeState, HttpError},
+};
+
+const ALLOWED: &str = "OPTIONS, GET, PUT, PROPFIND, MKCOL, MOVE, DELETE";
+/// Everything but RFC 3986 unreserved characters, so names survive as href segments
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// WebDAV class 1 on top of the file routes: PROPFIND, PUT, MKCOL, MOVE and DELETE are
+/// answered here, everything else goes on to GET. Writes follow the uploads switch.
+pub(crate) async fn webdav(
+    State(state): State<Arc<HtpServeState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.uri().path().starts_with(ADMIN_PREFIX) {
+        return next.run(request).await;
+    }
+    let (method, uri, headers) = (request.method(), request.uri(), request.headers());
+    let result = match method.as_str() {
+        "OPTIONS" => Ok((
+            StatusCode::OK,
+            [("Allow", ALLOWED), ("DAV", "1"), ("MS-Author-Via", "DAV")],
+        )
+            .into_response()),
+        "PROPFIND" => propfind(&state, uri, headers).await,
+        "PUT" => put(&state, request).await,
+        "MKCOL" | "MOVE" | "DELETE" => write(&state, method, uri, headers).await,
+        _ => return next.run(request).await,
+    };
+    result.unwrap_or_else(|e| e.into_response())
+}
+
+/// The served file an href points to, refusing anything outside the served directory
+fn resolve(root: &Path, href: &str) -> Result<(PathBuf, PathBuf), HttpError> {
+    let decoded = percent_decode_str(href)
+        .decode_utf8()
+        .map_err(|_| HttpError::BadRequest(format!("Invalid path: {}", href)))?;
+    let rel = PathBuf::from(decoded.trim_matches('/'));
+    if !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(HttpError::Forbidden);
+    }
+    Ok((root.join(&rel), rel))
+}
+
+async fn check_access(
+    state: &HtpServeState,
+    target: &Path,
+    headers: &HeaderMap,
+) -> Result<AccessRules, HttpError> {
+    let not_found = || {
+        HttpError::NotFound(
+            target
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        )
+    };
+    if state.is_excluded(target) {
+        return Err(not_found());
+    }
+    let rules = AccessRules::resolve(&state.path, target)
+        .await
+        .map_err(|_| HttpError::Internal)?;
+    match rules.check(target, headers) {
+        AccessDecision::Allow => Ok(rules),
+        // denied files are indistinguishable from missing ones
+        AccessDecision::Denied => Err(not_found()),
+        AccessDecision::Unauthorized => Err(HttpError::Unauthorized),
+        AccessDecision::ListingHidden => Err(HttpError::Forbidden),
+    }
+}
+
+/// Properties of the target and, unless `Depth: 0`, of its children. The request body
+/// is ignored and the same live properties are always returned.
+async fn propfind(
+    state: &HtpServeState,
+    uri: &Uri,
+    headers: &HeaderMap,
+) -> Result<Response, HttpError> {
+    let (target, rel) = resolve(&state.path, uri.path())?;
+    if !target.exists() {
+        return Err(HttpError::NotFound(uri.path().to_string()));
+    }
+    let rules = check_access(state, &target, headers).await?;
+    let mut body = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    body.push_str(&prop_response(&target, &rel).await?);
+    let shallow = headers
+        .get("Depth")
+        .is_some_and(|depth| depth.as_bytes() == b"0");
+    if target.is_dir() && !shallow {
+        if !state.settings.listing.load(Ordering::Relaxed) {
+            return Err(HttpError::Forbidden);
+        }
+        let mut entries = fs::read_dir(&target)
+            .await
+            .map_err(|_| HttpError::Internal)?;
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|_| HttpError::Internal)?
+        {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if rules.is_denied(&name) || state.is_excluded_name(&name) {
+                continue;
+            }
+            body.push_str(&prop_response(&entry.path(), &rel.join(&name)).await?);
+        }
+    }
+    body.push_str("</D:multistatus>\n");
+    Ok((
+        StatusCode::MULTI_STATUS,
+        [("Content-Type", "application/xml; charset=utf-8")],
+        body,
+    )
+        .into_response())
+}
+
+async fn prop_response(path: &Path, rel: &Path) -> Result<String, HttpError> {
+    let meta = fs::metadata(path).await.map_err(|_| HttpError::Internal)?;
+    let mut href: String = rel
+        .components()
+        .map(|c| {
+            format!(
+                "/{}",
+                utf8_percent_encode(&c.as_os_str().to_string_lossy(), SEGMENT)
+            )
+        })
+        .collect();
+    if meta.is_dir() {
+        href.push('/');
+    }
+    let name = rel
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut props = format!("<D:displayname>{}</D:displayname>", escape_html(&name));
+    if meta.is_dir() {
+        props.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        props.push_str(&format!(
+            "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>",
+            meta.len()
+        ));
+    }
+    if let Ok(modified) = meta.modified() {
+        props.push_str(&format!(
+            "<D:getlastmodified>{}</D:getlastmodified>",
+            http_date(modified)
+        ));
+    }
+    Ok(format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>{}</D:prop>\
+         <D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n",
+        escape_html(&href),
+        props
+    ))
+}
+
+async fn write(
+    state: &HtpServeState,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+) -> Result<Response, HttpError> {
+    if !state.settings.uploads.load(Ordering::Relaxed) {
+        return Err(HttpError::Forbidden);
+    }
+    let (target, rel) = resolve(&state.path, uri.path())?;
+    // the served directory itself can't be created, moved or deleted
+    if rel.as_os_str().is_empty() {
+        return Err(HttpError::Forbidden);
+    }
+    let rules = check_access(state, &target, headers).await?;
+    let parent_exists = target.parent().is_some_and(|p| p.is_dir());
+    let status = match method.as_str() {
+        "MKCOL" if target.exists() => StatusCode::METHOD_NOT_ALLOWED,
+        "MKCOL" if !parent_exists => StatusCode::CONFLICT,
+        "MKCOL" => {
+            info!("Creating directory: {:?}", target);
+            fs::create_dir(&target)
+                .await
+                .map_err(|_| HttpError::Internal)?;
+            StatusCode::CREATED
+        }
+        _ if !target.exists() => return Err(HttpError::NotFound(uri.path().to_string())),
+        "DELETE" => {
+            check_tree(state, &target, &rules).await?;
+            info!("Deleting: {:?}", target);
+            remove(&target).await?;
+            StatusCode::NO_CONTENT
+        }
+        _ => {
+            check_tree(state, &target, &rules).await?;
+            move_to(state, &target, headers).await?
+        }
+    };
+    Ok(status.into_response())
+}
+
+/// Write the request body to the target file, streamed so large files don't sit in memory
+async fn put(state: &HtpServeState, request: Request) -> Result<Response, HttpError> {
+    if !state.settings.uploads.load(Ordering::Relaxed) {
+        return Err(HttpError::Forbidden);
+    }
+    let (target, rel) = resolve(&state.path, request.uri().path())?;
+    if rel.as_os_str().is_empty() || target.is_dir() {
+        return Err(HttpError::Forbidden);
+    }
+    check_access(state, &target, request.headers()).await?;
+    if !target.parent().is_some_and(|p| p.is_dir()) {
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+    let existed = target.exists();
+    info!("Uploading file: {:?}", target);
+    let mut file = fs::File::create(&target)
+        .await
+        .map_err(|_| HttpError::Internal)?;
+    let mut body: Body = request.into_body();
+    while let Some(frame) = poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+        let frame = frame.map_err(|e| HttpError::BadRequest(e.to_string()))?;
+        if let Ok(data) = frame.into_data() {
+            file.write_all(&data)
+                .await
+                .map_err(|_| HttpError::Internal)?;
+        }
+    }
+    // tokio writes in the background, the upload is only complete once flushed
+    file.flush().await.map_err(|_| HttpError::Internal)?;
+    Ok(if existed {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    }
+    .into_response())
+}
+
+/// MOVE to the `Destination` header, replacing an existing target unless `Overwrite: F`
+async fn move_to(
+    state: &HtpServeState,
+    source: &Path,
+    headers: &HeaderMap,
+) -> Result<StatusCode, HttpError> {
+    let destination = headers
+        .get("Destination")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<Uri>().ok())
+        .ok_or_else(|| HttpError::BadRequest("Missing Destination header".to_string()))?;
+    let (target, rel) = resolve(&state.path, destination.path())?;
+    if rel.as_os_str().is_empty() || target.starts_with(source) {
+        return Err(HttpError::Forbidden);
+    }
+    let rules = check_access(state, &target, headers).await?;
+    if !target.parent().is_some_and(|p| p.is_dir()) {
+        return Ok(StatusCode::CONFLICT);
+    }
+    let existed = target.exists();
+    if existed {
+        let overwrite = headers
+            .get("Overwrite")
+            .is_none_or(|v| !v.as_bytes().eq_ignore_ascii_case(b"F"));
+        if !overwrite {
+            return Ok(StatusCode::PRECONDITION_FAILED);
+        }
+        check_tree(state, &target, &rules).await?;
+        remove(&target).await?;
+    }
+    info!("Moving {:?} to {:?}", source, target);
+    fs::rename(source, &target)
+        .await
+        .map_err(|_| HttpError::Internal)?;
+    Ok(if existed {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    })
+}
+
+/// Refuse to delete or move a directory holding anything the client can't see: hidden,
+/// excluded or denied names, access files included, would go along with it unnoticed
+async fn check_tree(
+    state: &HtpServeState,
+    dir: &Path,
+    rules: &AccessRules,
+) -> Result<(), HttpError> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir).await.map_err(|_| HttpError::Internal)?;
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|_| HttpError::Internal)?
+        {
+            // an access file below is denied too, so its rules never need merging here
+            let name = entry.file_name().to_string_lossy().to_string();
+            if rules.is_denied(&name) || state.is_excluded_name(&name) {
+                return Err(HttpError::Forbidden);
+            }
+            // links are removed or moved as links, what they point to is left alone
+            let file_type = entry.file_type().await.map_err(|_| HttpError::Internal)?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn remove(path: &Path) -> Result<(), HttpError> {
+    let removed = if path.is_dir() {
+        fs::remove_dir_all(path).await
+    } else {
+        fs::remove_file(path).await
+    };
+    removed.map_err(|_| HttpError::Internal)
+}
+
+/// RFC 7231 IMF-fixdate, the format `getlastmodified` uses
+fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        process::{http_access::ACCESS_FILE, http_serve::tests::test_config},
+        HttpServeConfig,
+    };
+    use axum::{middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_webdav() {
+        let dir = std::env::temp_dir().join("rcli-http-webdav");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a b.txt"), "hello").unwrap();
+        let state = Arc::new(HtpServeState::new(HttpServeConfig {
+            webdav: true,
+            ..test_config(dir.clone())
+        }));
+        let app = Router::new()
+            .route("/", get(|| async { "root" }))
+            .route("/*path", get(|| async { "file" }))
+            .layer(middleware::from_fn_with_state(state.clone(), webdav))
+            .with_state(state.clone());
+        let send = |method: &str, uri: &str, headers: &[(&str, &str)]| {
+            let mut request = axum::http::Request::builder().method(method).uri(uri);
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = send("MKCOL", "/docs", &[]).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let put = |uri: &str| {
+            let request = axum::http::Request::builder()
+                .method("PUT")
+                .uri(uri)
+                .body(Body::from("uploaded"))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+        assert_eq!(
+            put("/docs/new.txt").await.unwrap().status(),
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            std::fs::read(dir.join("docs/new.txt")).unwrap(),
+            b"uploaded"
+        );
+        assert_eq!(
+            put("/docs/new.txt").await.unwrap().status(),
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            put("/missing/new.txt").await.unwrap().status(),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            put("/../new.txt").await.unwrap().status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            send("MKCOL", "/missing/docs", &[]).await.unwrap().status(),
+            StatusCode::CONFLICT
+        );
+        let response = send("PROPFIND", "/", &[("Depth", "1")]).await.unwrap();
+        assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("<D:href>/docs/</D:href>"));
+        assert!(body.contains("<D:href>/a%20b.txt</D:href>"));
+        assert!(body.contains("<D:getcontentlength>5</D:getcontentlength>"));
+
+        let response = send("MOVE", "/a%20b.txt", &[("Destination", "/docs/c.txt")])
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(std::fs::read(dir.join("docs/c.txt")).unwrap(), b"hello");
+        assert_eq!(
+            send("MOVE", "/docs", &[("Destination", "/docs/inner")])
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            send("DELETE", "/docs", &[]).await.unwrap().status(),
+            StatusCode::NO_CONTENT
+        );
+        assert!(!dir.join("docs").exists());
+        assert_eq!(
+            send("DELETE", "/../etc", &[]).await.unwrap().status(),
+            StatusCode::FORBIDDEN
+        );
+        // GET still reaches the file routes
+        assert_eq!(
+            send("GET", "/x", &[]).await.unwrap().status(),
+            StatusCode::OK
+        );
+
+        state.settings.uploads.store(false, Ordering::Relaxed);
+        assert_eq!(
+            send("MKCOL", "/docs", &[]).await.unwrap().status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            put("/docs/new.txt").await.unwrap().status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webdav_keeps_unseen_entries() {
+        let dir = std::env::temp_dir().join("rcli-http-webdav-unseen");
+        let _ = std::fs::remove_dir_all(&dir);
+        for sub in ["hidden/deep", "denied", "guarded", "plain/deep"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        std::fs::write(dir.join(ACCESS_FILE), "deny = [\"*.key\"]\n").unwrap();
+        std::fs::write(dir.join("hidden/deep/.env"), "").unwrap();
+        std::fs::write(dir.join("denied/server.key"), "").unwrap();
+        std::fs::write(dir.join("guarded").join(ACCESS_FILE), "").unwrap();
+        std::fs::write(dir.join("plain/deep/a.txt"), "").unwrap();
+        let state = Arc::new(HtpServeState::new(HttpServeConfig {
+            webdav: true,
+            hide: vec![glob::Pattern::new(".*").unwrap()],
+            ..test_config(dir.clone())
+        }));
+        let app = Router::new()
+            .route("/*path", get(|| async { "file" }))
+            .layer(middleware::from_fn_with_state(state.clone(), webdav))
+            .with_state(state);
+        let send = |method: &str, uri: &str, destination: Option<&str>| {
+            let mut request = axum::http::Request::builder().method(method).uri(uri);
+            if let Some(destination) = destination {
+                request = request.header("Destination", destination);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+        for name in ["hidden", "denied", "guarded"] {
+            let uri = format!("/{}", name);
+            let response = send("DELETE", &uri, None).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", name);
+            let response = send("MOVE", &uri, Some("/moved")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", name);
+            assert!(dir.join(name).is_dir(), "{}", name);
+            // nor can a MOVE overwrite such a directory
+            let response = send("MOVE", "/plain/deep/a.txt", Some(&uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", name);
+        }
+        let response = send("DELETE", "/plain", None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+}
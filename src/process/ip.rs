@@ -0,0 +1,81 @@
+use std::net::IpAddr;
+
+use if_addrs::IfAddr;
+use serde::Serialize;
+
+use crate::{RcliError, Result};
+
+/// An address assigned to a network interface of this host
+#[derive(Debug, Serialize)]
+pub struct InterfaceAddr {
+    pub name: String,
+    pub ip: IpAddr,
+    pub prefix: u8,
+    pub loopback: bool,
+}
+
+/// Every interface address, IPv4 before IPv6, loopback last
+pub fn process_ip_interfaces() -> Result<Vec<InterfaceAddr>> {
+    let mut addrs: Vec<_> = if_addrs::get_if_addrs()?
+        .into_iter()
+        .map(|iface| {
+            let prefix = match &iface.addr {
+                IfAddr::V4(addr) => addr.prefixlen,
+                IfAddr::V6(addr) => addr.prefixlen,
+            };
+            InterfaceAddr {
+                loopback: iface.is_loopback(),
+                ip: iface.ip(),
+                name: iface.name,
+                prefix,
+            }
+        })
+        .collect();
+    addrs.sort_by_key(|addr| (addr.loopback, addr.ip.is_ipv6(), addr.name.clone()));
+    Ok(addrs)
+}
+
+/// The address this host is seen from on the internet, as reported by `url`, a service
+/// answering with the bare IP in its body such as https://api.ipify.org
+pub async fn process_public_ip(url: &str) -> Result<IpAddr> {
+    let body = reqwest::get(url).await?.error_for_status()?.text().await?;
+    body.trim()
+        .parse()
+        .map_err(|_| RcliError::format(format!("{} answered with no IP address", url)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_ip_interfaces() -> Result<()> {
+        let addrs = process_ip_interfaces()?;
+        let loopback = addrs.iter().find(|addr| addr.ip.is_loopback()).unwrap();
+        assert!(loopback.loopback);
+        // loopback addresses sort after the rest
+        assert!(addrs
+            .iter()
+            .skip_while(|addr| !addr.loopback)
+            .all(|addr| addr.loopback));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_public_ip() -> anyhow::Result<()> {
+        use axum::{routing::get, Router};
+        let app = Router::new()
+            .route("/", get(|| async { "203.0.113.7\n" }))
+            .route("/html", get(|| async { "<html>" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let ip = process_public_ip(&format!("http://{}/", addr)).await?;
+        assert_eq!(ip, "203.0.113.7".parse::<IpAddr>()?);
+        assert!(process_public_ip(&format!("http://{}/html", addr))
+            .await
+            .is_err());
+        Ok(())
+    }
+}
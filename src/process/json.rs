@@ -0,0 +1,149 @@
+use std::io::Read;
+
+use serde_json::Value;
+
+use crate::{get_reader, Input, RcliError, Result};
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    /// negative indices count from the end, like python
+    Index(i64),
+    All,
+}
+
+pub fn process_json_fmt(input: impl Into<Input>, minify: bool, indent: usize) -> Result<String> {
+    format_json(&read_json(input)?, minify, indent)
+}
+
+pub(super) fn format_json(value: &Value, minify: bool, indent: usize) -> Result<String> {
+    if minify {
+        return Ok(serde_json::to_string(value)?);
+    }
+    let indent = " ".repeat(indent);
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut buf = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    serde::Serialize::serialize(value, &mut serializer)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Extract the values at a jq-like path: `.items[0].name`, `.["a.b"]`, `.items[-1]`,
+/// `.items[].id` (or `[*]`) for every element
+pub fn process_json_get(input: impl Into<Input>, path: &str) -> Result<Vec<Value>> {
+    select_path(&read_json(input)?, path)
+}
+
+pub(super) fn select_path(value: &Value, path: &str) -> Result<Vec<Value>> {
+    let segments = parse_path(path)?;
+    let mut current = vec![value];
+    for segment in &segments {
+        current = current
+            .into_iter()
+            .flat_map(|v| select(v, segment))
+            .collect();
+    }
+    Ok(current.into_iter().cloned().collect())
+}
+
+fn read_json(input: impl Into<Input>) -> Result<Value> {
+    let mut reader = get_reader(input)?;
+    let mut buf = String::new();
+    reader.read_to_string(&mut buf)?;
+    Ok(serde_json::from_str(&buf)?)
+}
+
+fn select<'a>(value: &'a Value, segment: &Segment) -> Vec<&'a Value> {
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(map)) => map.get(key).into_iter().collect(),
+        (Segment::Index(i), Value::Array(items)) => {
+            let i = if *i < 0 { items.len() as i64 + i } else { *i };
+            usize::try_from(i)
+                .ok()
+                .and_then(|i| items.get(i))
+                .into_iter()
+                .collect()
+        }
+        (Segment::All, Value::Array(items)) => items.iter().collect(),
+        (Segment::All, Value::Object(map)) => map.values().collect(),
+        _ => vec![],
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>> {
+    let invalid = || RcliError::format(format!("Invalid path: {}", path));
+    let mut segments = Vec::new();
+    let mut rest = path.trim();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = if let Some(quoted) = after.strip_prefix('"') {
+                // a quoted key may contain `]`, so look for the closing `"]`
+                quoted.find("\"]").map(|i| i + 2).ok_or_else(invalid)?
+            } else {
+                after.find(']').ok_or_else(invalid)?
+            };
+            let inner = after[..end].trim();
+            segments.push(match inner {
+                "" | "*" => Segment::All,
+                quoted if quoted.starts_with('"') => {
+                    Segment::Key(serde_json::from_str(quoted).map_err(|_| invalid())?)
+                }
+                index => Segment::Index(index.parse().map_err(|_| invalid())?),
+            });
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end > 0 {
+                segments.push(Segment::Key(after[..end].to_string()));
+            }
+            rest = &after[end..];
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path() -> Result<()> {
+        assert_eq!(
+            parse_path(".items[0].name")?,
+            vec![
+                Segment::Key("items".into()),
+                Segment::Index(0),
+                Segment::Key("name".into())
+            ]
+        );
+        assert_eq!(
+            parse_path(r#".["a.b"][-1][]"#)?,
+            vec![Segment::Key("a.b".into()), Segment::Index(-1), Segment::All]
+        );
+        assert_eq!(parse_path(".")?, vec![]);
+        assert!(parse_path("items").is_err());
+        assert!(parse_path(".items[x]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_json_get() -> Result<()> {
+        let path = std::env::temp_dir().join("rcli-json-get.json");
+        std::fs::write(
+            &path,
+            r#"{"items":[{"name":"a","id":1},{"name":"b","id":2}]}"#,
+        )?;
+        let input = &path;
+        assert_eq!(process_json_get(input, ".items[0].name")?, vec!["a"]);
+        assert_eq!(process_json_get(input, ".items[-1].id")?, vec![2]);
+        assert_eq!(process_json_get(input, ".items[].id")?, vec![1, 2]);
+        assert!(process_json_get(input, ".missing")?.is_empty());
+        assert_eq!(
+            process_json_fmt(input, true, 2)?,
+            r#"{"items":[{"name":"a","id":1},{"name":"b","id":2}]}"#
+        );
+        Ok(())
+    }
+}
@@ -1,22 +1,109 @@
-use std::time::SystemTime;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{
+    decode, decode_header, encode, errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header,
+    TokenData, Validation,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{process_uuid, read_file, RcliError, Result, Secret, UuidVersion};
 
-use chrono::Duration;
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
 const JWTSECRET: &str = "rclijwtsecret";
+const ACCESS_TYP: &str = "access";
+const REFRESH_TYP: &str = "refresh";
+/// Audience of refresh tokens. Validation rejects tokens with an `aud` it wasn't told to
+/// expect, so this keeps `jwt verify` and other resource servers from taking them as access tokens
+const REFRESH_AUD: &str = "rcli-refresh";
+// RFC 7518 requires HMAC keys to be at least as long as the hash output
+const MIN_HS256_SECRET_LEN: usize = 32;
+/// Seconds of clock skew tolerated on exp and nbf, jsonwebtoken's own default
+pub const JWT_DEFAULT_LEEWAY: u64 = 60;
+
+const HMAC_ALGORITHMS: &[Algorithm] = &[Algorithm::HS256, Algorithm::HS384, Algorithm::HS512];
+const RSA_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+];
+const EC_ALGORITHMS: &[Algorithm] = &[Algorithm::ES256, Algorithm::ES384];
+const ED_ALGORITHMS: &[Algorithm] = &[Algorithm::EdDSA];
+
+/// One verification key. Its type pins the algorithms it may be used with, so an
+/// RSA public key can never be abused as an HMAC secret.
+pub struct JwtKey {
+    kid: Option<String>,
+    key: DecodingKey,
+    algorithms: &'static [Algorithm],
+    /// length of an HMAC secret, for the weak-secret warning
+    secret_len: Option<usize>,
+}
+
+/// Every key a token may be signed with. During a rotation it holds both the old and the
+/// new key, so tokens issued before the switch keep validating until they expire.
+pub struct JwtKeySet {
+    keys: Vec<JwtKey>,
+}
 
-pub fn process_jwt_sign(sub: &str, aud: &str, exp: Duration) -> anyhow::Result<String> {
-    // get system current timestamp
-    let now = SystemTime::now();
-    // get the duration from the current time
-    let exp = now
-        .checked_add(std::time::Duration::from_secs(exp.num_seconds() as u64))
-        .unwrap();
+#[derive(Debug, Serialize)]
+pub struct JwtReport {
+    pub header: Value,
+    pub claims: Value,
+    pub signature_valid: bool,
+    pub checks: Vec<JwtCheck>,
+    /// seconds until the token expires, negative if already expired
+    pub expires_in: Option<i64>,
+    pub warnings: Vec<String>,
+    /// RFC 7662 response from the authorization server, when introspected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub introspection: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JwtCheck {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// Tokens in the shape of an OAuth2 token response (RFC 6749 section 5.1)
+#[derive(Debug, Serialize)]
+pub struct JwtTokens {
+    pub access_token: String,
+    /// only set when a new pair is issued, `jwt refresh` keeps the refresh token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub token_type: &'static str,
+    /// access token lifetime in seconds
+    pub expires_in: i64,
+}
+
+/// Sign a token expiring `exp` from now. `nbf` delays when it becomes valid, and `iat`
+/// is always set to now.
+pub fn process_jwt_sign(
+    sub: &str,
+    aud: &str,
+    exp: Duration,
+    nbf: Option<Duration>,
+    jti: Option<&str>,
+) -> Result<String> {
     // create a claim
     let claims = Claims {
         sub: sub.to_string(),
         company: aud.to_string(),
-        exp: exp.duration_since(SystemTime::UNIX_EPOCH)?.as_secs() as usize,
+        exp: expires_at(exp)?,
+        nbf: nbf.map(expires_at).transpose()?,
+        iat: Some(expires_at(Duration::zero())?),
+        jti: jti.map(str::to_string),
     };
     let token = encode(
         &Header::default(),
@@ -27,20 +114,437 @@ pub fn process_jwt_sign(sub: &str, aud: &str, exp: Duration) -> anyhow::Result<S
     Ok(token)
 }
 
-pub fn process_jwt_verify(token: &str) -> anyhow::Result<bool> {
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWTSECRET.as_ref()),
-        &Validation::new(Algorithm::HS256),
+/// Mint an access token and the refresh token it's linked to: the access token's `rti`
+/// claim holds the refresh token's `jti`. Both are signed with the built-in secret.
+pub fn process_jwt_issue(
+    sub: &str,
+    aud: &str,
+    access_exp: Duration,
+    refresh_exp: Duration,
+) -> Result<JwtTokens> {
+    let refresh = PairClaims {
+        sub: sub.to_string(),
+        company: aud.to_string(),
+        exp: expires_at(refresh_exp)?,
+        jti: new_jti(),
+        typ: REFRESH_TYP.to_string(),
+        aud: Some(REFRESH_AUD.to_string()),
+        rti: None,
+    };
+    let mut tokens = issue_access(&refresh, access_exp)?;
+    tokens.refresh_token = Some(encode(
+        &Header::default(),
+        &refresh,
+        &EncodingKey::from_secret(JWTSECRET.as_ref()),
+    )?);
+    Ok(tokens)
+}
+
+/// Validate a refresh token from `jwt issue` and mint a new access token linked to it.
+/// Like `jwt issue`, this only knows the built-in secret, which both checks the refresh
+/// token and signs the new access token.
+pub fn process_jwt_refresh(refresh_token: &str, access_exp: Duration) -> Result<JwtTokens> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_audience(&[REFRESH_AUD]);
+    let (data, _) = JwtKeySet::default().decode::<PairClaims>(refresh_token, &validation)?;
+    if data.claims.typ != REFRESH_TYP {
+        return Err(RcliError::Unsupported(format!(
+            "Not a refresh token: typ is {}",
+            data.claims.typ
+        )));
+    }
+    issue_access(&data.claims, access_exp)
+}
+
+fn issue_access(refresh: &PairClaims, exp: Duration) -> Result<JwtTokens> {
+    let claims = PairClaims {
+        sub: refresh.sub.clone(),
+        company: refresh.company.clone(),
+        exp: expires_at(exp)?,
+        jti: new_jti(),
+        typ: ACCESS_TYP.to_string(),
+        aud: None,
+        rti: Some(refresh.jti.clone()),
+    };
+    let access_token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(JWTSECRET.as_ref()),
     )?;
+    Ok(JwtTokens {
+        access_token,
+        refresh_token: None,
+        token_type: "Bearer",
+        expires_in: exp.num_seconds(),
+    })
+}
+
+/// Unix time `exp` from now, as the `exp` claim stores it. A negative `exp` gives an
+/// already expired token, handy for testing how services reject them.
+fn expires_at(exp: Duration) -> Result<usize> {
+    Utc::now()
+        .checked_add_signed(exp)
+        .and_then(|exp| usize::try_from(exp.timestamp()).ok())
+        .ok_or_else(|| RcliError::Unsupported("Expiration is out of range".to_string()))
+}
+
+fn new_jti() -> String {
+    process_uuid(UuidVersion::V4, 1)[0].to_string()
+}
+
+/// Check the signature, exp and nbf, allowing `leeway` seconds of clock skew
+pub fn process_jwt_verify(token: &str, keys: &JwtKeySet, leeway: u64) -> Result<bool> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.validate_nbf = true;
+    validation.leeway = leeway;
+    keys.decode::<Claims>(token, &validation)?;
     Ok(true)
 }
 
+impl JwtKey {
+    fn secret(kid: Option<String>, secret: &[u8]) -> Self {
+        Self {
+            kid,
+            key: DecodingKey::from_secret(secret),
+            algorithms: HMAC_ALGORITHMS,
+            secret_len: Some(secret.len()),
+        }
+    }
+
+    /// Load an RSA, EC or Ed25519 public key from PEM, whichever it turns out to be
+    fn public_key(kid: Option<String>, pem: &[u8]) -> Result<Self> {
+        let (key, algorithms) = if let Ok(key) = DecodingKey::from_rsa_pem(pem) {
+            (key, RSA_ALGORITHMS)
+        } else if let Ok(key) = DecodingKey::from_ec_pem(pem) {
+            (key, EC_ALGORITHMS)
+        } else if let Ok(key) = DecodingKey::from_ed_pem(pem) {
+            (key, ED_ALGORITHMS)
+        } else {
+            return Err(RcliError::invalid_key(
+                "Unsupported public key, expected an RSA, EC or Ed25519 PEM",
+            ));
+        };
+        Ok(Self {
+            kid,
+            key,
+            algorithms,
+            secret_len: None,
+        })
+    }
+
+    fn label(&self, index: usize) -> String {
+        match &self.kid {
+            Some(kid) => format!("kid {}", kid),
+            None => format!("#{}", index + 1),
+        }
+    }
+}
+
+impl JwtKeySet {
+    /// Collect keys from `--secret`, `--public-key` and every file of a keys directory.
+    /// Files in the directory are named by their kid: PEM files are public keys, anything
+    /// else is an HMAC secret.
+    pub fn load(
+        secrets: &[Secret],
+        public_keys: &[PathBuf],
+        keys_dir: Option<&Path>,
+    ) -> Result<Self> {
+        let mut keys: Vec<JwtKey> = secrets
+            .iter()
+            .map(|s| JwtKey::secret(None, s.expose_str().as_bytes()))
+            .collect();
+        for path in public_keys {
+            let kid = path.file_stem().map(|s| s.to_string_lossy().to_string());
+            keys.push(
+                JwtKey::public_key(kid, &read_file(path)?)
+                    .map_err(|e| RcliError::invalid_key(format!("{}: {}", path.display(), e)))?,
+            );
+        }
+        if let Some(dir) = keys_dir {
+            let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<_, _>>()?;
+            paths.retain(|p| p.is_file());
+            paths.sort();
+            for path in paths {
+                let kid = path.file_stem().map(|s| s.to_string_lossy().to_string());
+                let content = fs::read(&path)?;
+                if content.trim_ascii_start().starts_with(b"-----BEGIN") {
+                    keys.push(JwtKey::public_key(kid, &content).map_err(|e| {
+                        RcliError::invalid_key(format!("{}: {}", path.display(), e))
+                    })?);
+                } else {
+                    keys.push(JwtKey::secret(kid, content.trim_ascii()));
+                }
+            }
+        }
+        if keys.is_empty() {
+            return Ok(Self::default());
+        }
+        Ok(Self { keys })
+    }
+
+    /// The keys to try for a token: those named by its `kid`, or all of them in order
+    /// when the kid is missing or unknown
+    fn candidates(&self, kid: Option<&str>) -> Vec<(usize, &JwtKey)> {
+        let named: Vec<(usize, &JwtKey)> = self
+            .keys
+            .iter()
+            .enumerate()
+            .filter(|(_, k)| kid.is_some() && k.kid.as_deref() == kid)
+            .collect();
+        if named.is_empty() {
+            self.keys.iter().enumerate().collect()
+        } else {
+            named
+        }
+    }
+
+    fn accepts(&self, alg: Algorithm) -> bool {
+        self.keys.iter().any(|k| k.algorithms.contains(&alg))
+    }
+
+    /// Decode with the first candidate key whose signature matches. Once a signature
+    /// matches, claim errors (expired, wrong audience...) are final.
+    fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> Result<(TokenData<T>, usize)> {
+        let header = decode_header(token)?;
+        let mut validation = validation.clone();
+        validation.algorithms = vec![header.alg];
+        let mut last_error = None;
+        for (index, key) in self.candidates(header.kid.as_deref()) {
+            if !key.algorithms.contains(&header.alg) {
+                continue;
+            }
+            match decode::<T>(token, &key.key, &validation) {
+                Ok(data) => return Ok((data, index)),
+                Err(e) => match e.kind() {
+                    ErrorKind::InvalidSignature
+                    | ErrorKind::InvalidKeyFormat
+                    | ErrorKind::InvalidRsaKey(_)
+                    | ErrorKind::InvalidEcdsaKey
+                    | ErrorKind::Crypto(_) => last_error = Some(e),
+                    _ => return Err(e.into()),
+                },
+            }
+        }
+        Err(match last_error {
+            Some(e) => RcliError::from(e),
+            None => RcliError::Unsupported(format!("No configured key accepts {:?}", header.alg)),
+        })
+    }
+}
+
+impl Default for JwtKeySet {
+    /// The built-in secret `jwt sign` uses
+    fn default() -> Self {
+        Self {
+            keys: vec![JwtKey::secret(None, JWTSECRET.as_bytes())],
+        }
+    }
+}
+
+/// Inspect the token offline and report every validation rule instead of failing fast.
+/// exp and nbf are allowed `leeway` seconds of clock skew, as in `process_jwt_verify`
+pub fn process_jwt_report(token: &str, keys: &JwtKeySet, leeway: u64) -> Result<JwtReport> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        return Err(RcliError::format(format!(
+            "Invalid token: expected 3 segments, got {}",
+            parts.len()
+        )));
+    }
+    let header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[0])?)?;
+    let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1])?)?;
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs() as i64;
+    let leeway = leeway as i64;
+
+    let mut checks = Vec::new();
+    let mut warnings = Vec::new();
+
+    let alg = header.get("alg").and_then(Value::as_str).unwrap_or("none");
+    if alg.eq_ignore_ascii_case("none") {
+        warnings.push("alg=none: token is unsigned and must never be trusted".to_string());
+    }
+    let accepted = alg.parse::<Algorithm>().is_ok_and(|alg| keys.accepts(alg));
+    checks.push(JwtCheck {
+        name: "alg",
+        passed: accepted,
+        detail: if accepted {
+            format!("{} is accepted by a configured key", alg)
+        } else {
+            format!("no configured key accepts {}", alg)
+        },
+    });
+    if let Some(kid) = header.get("kid").and_then(Value::as_str) {
+        if !keys.keys.iter().any(|k| k.kid.as_deref() == Some(kid)) {
+            warnings.push(format!(
+                "kid {} matches no configured key, tried them all",
+                kid
+            ));
+        }
+    }
+
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.validate_exp = false;
+    validation.required_spec_claims.clear();
+    let signature = keys.decode::<Value>(token, &validation);
+    let signature_valid = signature.is_ok();
+    if let Ok((_, index)) = &signature {
+        let key = &keys.keys[*index];
+        if let Some(len) = key.secret_len.filter(|len| *len < MIN_HS256_SECRET_LEN) {
+            warnings.push(format!(
+                "{} with a short secret ({} bytes, at least {} recommended)",
+                alg, len, MIN_HS256_SECRET_LEN
+            ));
+        }
+    }
+    checks.push(JwtCheck {
+        name: "signature",
+        passed: signature_valid,
+        detail: match signature {
+            Ok((_, index)) => format!("signature matches key {}", keys.keys[index].label(index)),
+            Err(e) => e.to_string(),
+        },
+    });
+
+    let exp = claims.get("exp").and_then(Value::as_i64);
+    checks.push(match exp {
+        Some(exp) => JwtCheck {
+            name: "exp",
+            passed: exp > now - leeway,
+            detail: format!("expires at {}", exp),
+        },
+        None => JwtCheck {
+            name: "exp",
+            passed: false,
+            detail: "missing exp claim".to_string(),
+        },
+    });
+    if let Some(nbf) = claims.get("nbf").and_then(Value::as_i64) {
+        checks.push(JwtCheck {
+            name: "nbf",
+            passed: nbf <= now + leeway,
+            detail: format!("not valid before {}", nbf),
+        });
+    }
+    if let Some(iat) = claims.get("iat").and_then(Value::as_i64) {
+        if iat > now + leeway {
+            warnings.push(format!(
+                "iat {} is in the future, check the issuer's clock",
+                iat
+            ));
+        }
+    }
+    for claim in ["sub", "company"] {
+        let present = claims.get(claim).is_some();
+        checks.push(JwtCheck {
+            name: claim,
+            passed: present,
+            detail: if present {
+                "present".to_string()
+            } else {
+                format!("missing {} claim", claim)
+            },
+        });
+    }
+
+    Ok(JwtReport {
+        header,
+        claims,
+        signature_valid,
+        checks,
+        expires_in: exp.map(|exp| exp - now),
+        warnings,
+        introspection: None,
+    })
+}
+
+/// Validate the token locally, then ask the authorization server (RFC 7662) whether it's
+/// still active, which catches revocations that offline checks can't see
+pub async fn process_jwt_introspect(
+    token: &str,
+    keys: &JwtKeySet,
+    endpoint: &str,
+    client_id: &str,
+    client_secret: &str,
+    leeway: u64,
+) -> Result<JwtReport> {
+    let mut report = process_jwt_report(token, keys, leeway)?;
+    let response = reqwest::Client::new()
+        .post(endpoint)
+        .basic_auth(client_id, Some(client_secret))
+        .form(&[("token", token), ("token_type_hint", "access_token")])
+        .send()
+        .await?
+        .error_for_status()?;
+    let remote: Value = response.json().await?;
+    let active = remote
+        .get("active")
+        .and_then(Value::as_bool)
+        .ok_or_else(|| RcliError::format("Invalid introspection response: missing active"))?;
+    report.checks.push(JwtCheck {
+        name: "active",
+        passed: active,
+        detail: if active {
+            "authorization server reports the token as active".to_string()
+        } else {
+            "authorization server reports the token as inactive (revoked or expired)".to_string()
+        },
+    });
+    for claim in ["sub", "exp"] {
+        if let (Some(local), Some(remote)) = (report.claims.get(claim), remote.get(claim)) {
+            if local != remote {
+                report.warnings.push(format!(
+                    "{} differs: token has {}, authorization server has {}",
+                    claim, local, remote
+                ));
+            }
+        }
+    }
+    report.introspection = Some(remote);
+    Ok(report)
+}
+
+impl JwtReport {
+    pub fn is_valid(&self) -> bool {
+        self.checks.iter().all(|c| c.passed)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Claims {
     sub: String,
     company: String,
     exp: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nbf: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    iat: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    jti: Option<String>,
+}
+
+/// Claims of the tokens `jwt issue` mints, a superset of `Claims`
+#[derive(Debug, Serialize, Deserialize)]
+struct PairClaims {
+    sub: String,
+    company: String,
+    exp: usize,
+    jti: String,
+    /// `access` or `refresh`
+    typ: String,
+    /// only set on refresh tokens, see `REFRESH_AUD`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aud: Option<String>,
+    /// on access tokens, the jti of the refresh token they were minted with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rti: Option<String>,
 }
 
 #[cfg(test)]
@@ -52,7 +556,183 @@ mod tests {
         let sub = "acme";
         let aud = "device1";
         let exp = Duration::new(60, 0).unwrap();
-        let token = process_jwt_sign(sub, aud, exp).unwrap();
-        assert!(process_jwt_verify(token.as_str()).unwrap());
+        let token = process_jwt_sign(sub, aud, exp, None, None).unwrap();
+        assert!(
+            process_jwt_verify(token.as_str(), &JwtKeySet::default(), JWT_DEFAULT_LEEWAY).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_process_jwt_sign_nbf_jti() -> anyhow::Result<()> {
+        let keys = JwtKeySet::default();
+        let exp = Duration::hours(1);
+        let token = process_jwt_sign("acme", "device1", exp, Some(Duration::minutes(10)), None)?;
+        assert!(matches!(
+            process_jwt_verify(&token, &keys, JWT_DEFAULT_LEEWAY),
+            Err(e) if e.is_verification_failure()
+        ));
+        let report = process_jwt_report(&token, &keys, JWT_DEFAULT_LEEWAY)?;
+        assert!(report.checks.iter().any(|c| c.name == "nbf" && !c.passed));
+        // enough leeway covers the wait
+        assert!(process_jwt_verify(&token, &keys, 601)?);
+
+        let token = process_jwt_sign("acme", "device1", exp, None, Some("req-42"))?;
+        let report = process_jwt_report(&token, &keys, JWT_DEFAULT_LEEWAY)?;
+        assert_eq!(report.claims["jti"], "req-42");
+        assert!(report.claims["iat"].as_i64().unwrap() <= report.claims["exp"].as_i64().unwrap());
+        assert!(report.is_valid());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_jwt_issue_refresh() -> anyhow::Result<()> {
+        let keys = JwtKeySet::default();
+        let tokens =
+            process_jwt_issue("acme", "device1", Duration::minutes(15), Duration::days(7))?;
+        assert_eq!(tokens.expires_in, 900);
+        let refresh_token = tokens.refresh_token.unwrap();
+        // access tokens still pass the plain `jwt verify`
+        assert!(process_jwt_verify(
+            &tokens.access_token,
+            &keys,
+            JWT_DEFAULT_LEEWAY
+        )?);
+
+        let validation = Validation::new(Algorithm::HS256);
+        let (access, _) = keys.decode::<PairClaims>(&tokens.access_token, &validation)?;
+        let mut refresh_validation = validation.clone();
+        refresh_validation.set_audience(&[REFRESH_AUD]);
+        let (refresh, _) = keys.decode::<PairClaims>(&refresh_token, &refresh_validation)?;
+        assert_eq!(
+            access.claims.rti.as_deref(),
+            Some(refresh.claims.jti.as_str())
+        );
+        assert_ne!(access.claims.jti, refresh.claims.jti);
+
+        // but a refresh token doesn't
+        let err = process_jwt_verify(&refresh_token, &keys, JWT_DEFAULT_LEEWAY).unwrap_err();
+        assert!(err.is_verification_failure());
+
+        let renewed = process_jwt_refresh(&refresh_token, Duration::minutes(5))?;
+        assert!(renewed.refresh_token.is_none());
+        let (renewed, _) = keys.decode::<PairClaims>(&renewed.access_token, &validation)?;
+        assert_eq!(renewed.claims.rti, access.claims.rti);
+        assert_ne!(renewed.claims.jti, access.claims.jti);
+
+        // an access token can't stand in for the refresh token
+        assert!(process_jwt_refresh(&tokens.access_token, Duration::minutes(5)).is_err());
+        let plain = process_jwt_sign("acme", "device1", Duration::minutes(5), None, None)?;
+        assert!(process_jwt_refresh(&plain, Duration::minutes(5)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_jwt_report() -> anyhow::Result<()> {
+        let exp = Duration::new(60, 0).unwrap();
+        let token = process_jwt_sign("acme", "device1", exp, None, None)?;
+        let report = process_jwt_report(&token, &JwtKeySet::default(), JWT_DEFAULT_LEEWAY)?;
+        assert!(report.signature_valid);
+        assert!(report.is_valid());
+        assert!(report.expires_in.unwrap() > 0);
+        assert_eq!(report.claims["sub"], "acme");
+        assert!(!report.warnings.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_jwt_introspect() -> anyhow::Result<()> {
+        use axum::{routing::post, Json, Router};
+        let app = Router::new().route(
+            "/introspect",
+            post(|| async { Json(serde_json::json!({ "active": false, "sub": "other" })) }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let token = process_jwt_sign("acme", "device1", Duration::new(60, 0).unwrap(), None, None)?;
+        let endpoint = format!("http://{}/introspect", addr);
+        let report = process_jwt_introspect(
+            &token,
+            &JwtKeySet::default(),
+            &endpoint,
+            "rcli",
+            "secret",
+            JWT_DEFAULT_LEEWAY,
+        )
+        .await?;
+        assert!(report.signature_valid);
+        assert!(!report.is_valid());
+        assert!(report.warnings.iter().any(|w| w.starts_with("sub differs")));
+        assert_eq!(report.introspection.unwrap()["active"], false);
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_jwt_report_alg_none() -> anyhow::Result<()> {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none","typ":"JWT"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(r#"{"sub":"acme","exp":1}"#);
+        let token = format!("{}.{}.", header, claims);
+        let report = process_jwt_report(&token, &JwtKeySet::default(), JWT_DEFAULT_LEEWAY)?;
+        assert!(!report.signature_valid);
+        assert!(!report.is_valid());
+        assert!(report.warnings[0].contains("alg=none"));
+        assert!(report.expires_in.unwrap() < 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jwt_key_rollover() -> anyhow::Result<()> {
+        let claims =
+            serde_json::json!({ "sub": "acme", "company": "device1", "exp": 4102444800u64 });
+        let sign = |kid: Option<&str>, secret: &str| {
+            let header = Header {
+                kid: kid.map(String::from),
+                ..Default::default()
+            };
+            encode(
+                &header,
+                &claims,
+                &EncodingKey::from_secret(secret.as_bytes()),
+            )
+        };
+
+        let dir = std::env::temp_dir().join("rcli-jwt-keys");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("2024.key"), "old-secret\n")?;
+        fs::write(dir.join("2025.key"), "new-secret\n")?;
+        let keys = JwtKeySet::load(&[], &[], Some(&dir))?;
+
+        // picked by kid, or found by trying every key when the kid is missing
+        assert!(process_jwt_verify(
+            &sign(Some("2024"), "old-secret")?,
+            &keys,
+            JWT_DEFAULT_LEEWAY
+        )?);
+        assert!(process_jwt_verify(
+            &sign(None, "new-secret")?,
+            &keys,
+            JWT_DEFAULT_LEEWAY
+        )?);
+        assert!(
+            process_jwt_verify(&sign(Some("2025"), "retired")?, &keys, JWT_DEFAULT_LEEWAY).is_err()
+        );
+
+        let report = process_jwt_report(
+            &sign(Some("2025"), "new-secret")?,
+            &keys,
+            JWT_DEFAULT_LEEWAY,
+        )?;
+        assert_eq!(report.checks[1].detail, "signature matches key kid 2025");
+
+        let keys = JwtKeySet::load(&["new-secret".parse()?], &[], None)?;
+        assert!(process_jwt_verify(
+            &sign(Some("2024"), "old-secret")?,
+            &keys,
+            JWT_DEFAULT_LEEWAY
+        )
+        .is_err());
+        Ok(())
     }
 }
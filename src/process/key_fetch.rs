@@ -0,0 +1,94 @@
+use std::{fs, path::PathBuf};
+
+use data_encoding::HEXLOWER;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::{RcliError, Result};
+
+const KEY_CACHE_DIR: &str = "rcli/keys";
+
+/// Fetch a public key published at an https URL (e.g. `/.well-known/rcli.pub`) and return
+/// the path of the locally cached copy, so it can be used like any other key file.
+///
+/// `pin` is the expected `sha256:<hex>` fingerprint of the key file; it's checked on every
+/// use, including cache hits, so a tampered cache is caught as well.
+pub async fn process_fetch_key(url: &str, pin: Option<&str>, refresh: bool) -> Result<PathBuf> {
+    if !url.starts_with("https://") {
+        return Err(RcliError::Unsupported(format!(
+            "Keys can only be fetched over https: {}",
+            url
+        )));
+    }
+    let path = cache_path(url)?;
+    let key = if path.exists() && !refresh {
+        fs::read(&path)?
+    } else {
+        let key = reqwest::get(url)
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?
+            .to_vec();
+        if let Some(pin) = pin {
+            verify_pin(&key, pin)?;
+        }
+        fs::create_dir_all(path.parent().expect("cache path has a parent"))?;
+        fs::write(&path, &key)?;
+        key
+    };
+    if let Some(pin) = pin {
+        verify_pin(&key, pin)?;
+    }
+    Ok(path)
+}
+
+pub fn key_fingerprint(key: &[u8]) -> String {
+    format!("sha256:{}", HEXLOWER.encode(&Sha256::digest(key)))
+}
+
+fn verify_pin(key: &[u8], pin: &str) -> Result<()> {
+    let expected = pin.trim().to_ascii_lowercase();
+    let expected = match expected.strip_prefix("sha256:") {
+        Some(_) => expected,
+        None => format!("sha256:{}", expected),
+    };
+    let actual = key_fingerprint(key);
+    if actual.as_bytes().ct_eq(expected.as_bytes()).into() {
+        Ok(())
+    } else {
+        Err(RcliError::invalid_key(format!(
+            "Key pin mismatch: expected {}, got {}",
+            expected, actual
+        )))
+    }
+}
+
+fn cache_path(url: &str) -> Result<PathBuf> {
+    let dir =
+        dirs::cache_dir().ok_or_else(|| RcliError::NotFound("Cache directory".to_string()))?;
+    let name = HEXLOWER.encode(&Sha256::digest(url.as_bytes()));
+    Ok(dir.join(KEY_CACHE_DIR).join(format!("{}.pub", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_pin() -> Result<()> {
+        let key = fs::read("fixtures/ed25519.pk")?;
+        let fingerprint = key_fingerprint(&key);
+        verify_pin(&key, &fingerprint)?;
+        verify_pin(&key, fingerprint.trim_start_matches("sha256:"))?;
+        verify_pin(&key, &fingerprint.to_uppercase())?;
+        assert!(verify_pin(b"other key", &fingerprint).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_key_requires_https() {
+        let result = process_fetch_key("http://example.com/.well-known/rcli.pub", None, false);
+        assert!(result.await.is_err());
+    }
+}
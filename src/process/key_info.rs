@@ -0,0 +1,107 @@
+use rsa::{pkcs8::DecodePublicKey, traits::PublicKeyParts};
+
+use super::text::public_key_der;
+use crate::{key_fingerprint, read_key, RcliError, Result, TextSignFormat};
+
+/// What `text key-info` could tell about a key file
+#[derive(Debug, Clone)]
+pub struct KeyInfo {
+    /// e.g. "ed25519 public key", "rsa-2048 private key" or "32-byte raw key or secret"
+    pub kind: String,
+    /// `sha256:<hex>` of the public key (SPKI DER), the same for both halves of a pair and
+    /// the `Key:` header of armored signatures. Not known for raw keys and secrets.
+    pub fingerprint: Option<String>,
+    /// `sha256:<hex>` of the file itself, what `text verify --key-pin` compares against
+    pub file_fingerprint: String,
+    /// The public key as SPKI DER, never set for secrets
+    pub public_key: Option<Vec<u8>>,
+}
+
+/// Identify a key generated by `text generate` (or OpenSSL/OpenSSH) without using it
+pub fn process_key_info(path: &str) -> Result<KeyInfo> {
+    let data = read_key(path)?;
+    let file_fingerprint = key_fingerprint(&data);
+    let text = std::str::from_utf8(&data).unwrap_or_default().trim();
+    let armored = text.starts_with("-----BEGIN ") || text.starts_with("ssh-ed25519 ");
+    // raw keys carry no type; `text generate` only writes raw .sk/.pk pairs for ed25519
+    let raw_pair = data.len() == 32 && (path.ends_with(".sk") || path.ends_with(".pk"));
+    if !armored && !raw_pair {
+        // a raw key looks just like a blake3 or hmac secret, so never echo it back
+        return Ok(KeyInfo {
+            kind: format!("{}-byte raw key or secret", data.len()),
+            fingerprint: None,
+            file_fingerprint,
+            public_key: None,
+        });
+    }
+    let private = if armored {
+        text.contains("PRIVATE KEY-----")
+    } else {
+        path.ends_with(".sk")
+    };
+    let half = if private { "private" } else { "public" };
+    for format in [
+        TextSignFormat::Ed25519,
+        TextSignFormat::EcdsaP256,
+        TextSignFormat::Rsa,
+    ] {
+        let Ok(Some(der)) = public_key_der(path, format, private) else {
+            continue;
+        };
+        let kind = match format {
+            TextSignFormat::Rsa => {
+                let bits = rsa::RsaPublicKey::from_public_key_der(&der)?.n().bits();
+                format!("rsa-{} {} key", bits, half)
+            }
+            _ => format!("{} {} key", format, half),
+        };
+        return Ok(KeyInfo {
+            kind,
+            fingerprint: Some(key_fingerprint(&der)),
+            file_fingerprint,
+            public_key: Some(der),
+        });
+    }
+    Err(RcliError::invalid_key(format!(
+        "{} is not an ed25519, ecdsa-p256 or rsa key",
+        path
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_generate_key, KeyFormat};
+
+    #[test]
+    fn test_key_info() -> Result<()> {
+        let dir = std::env::temp_dir();
+        for (format, kind) in [
+            (TextSignFormat::Ed25519, "ed25519"),
+            (TextSignFormat::EcdsaP256, "ecdsa-p256"),
+        ] {
+            let keys = process_generate_key(format, KeyFormat::Pem, None)?;
+            let sk = dir.join(format!("rcli-key-info-{}.sk", kind));
+            let pk = dir.join(format!("rcli-key-info-{}.pk", kind));
+            std::fs::write(&sk, &keys[0])?;
+            std::fs::write(&pk, &keys[1])?;
+            let private = process_key_info(sk.to_str().unwrap())?;
+            let public = process_key_info(pk.to_str().unwrap())?;
+            assert_eq!(private.kind, format!("{} private key", kind));
+            assert_eq!(public.kind, format!("{} public key", kind));
+            // both halves of a pair share the fingerprint, but not the file pin
+            assert_eq!(private.fingerprint, public.fingerprint);
+            assert_ne!(private.file_fingerprint, public.file_fingerprint);
+        }
+        let keys = process_generate_key(TextSignFormat::Ed25519, KeyFormat::Raw, None)?;
+        let pk = dir.join("rcli-key-info-raw.pk");
+        std::fs::write(&pk, &keys[1])?;
+        let info = process_key_info(pk.to_str().unwrap())?;
+        assert_eq!(info.kind, "ed25519 public key");
+
+        let info = process_key_info("fixtures/blake3.txt")?;
+        assert!(info.kind.ends_with("raw key or secret"));
+        assert!(info.public_key.is_none() && info.fingerprint.is_none());
+        Ok(())
+    }
+}
@@ -1,17 +1,115 @@
+mod api;
+mod archive;
 mod b64;
+mod cert;
+mod chain;
+mod compress;
 mod csv_convert;
+mod csv_json;
+mod csv_schema;
+mod csv_sort;
+mod dns;
 mod gen_pass;
+mod gen_rand;
+mod gen_uuid;
+mod hash;
+mod hex;
+mod http_access;
+mod http_admin;
+mod http_archive;
+mod http_bench;
+mod http_echo;
 mod http_serve;
+mod http_throttle;
+mod http_webdav;
+mod ip;
+mod json;
 mod jwt;
+mod key_fetch;
+mod key_info;
+mod otp;
+mod pass_meta;
+mod qr;
+mod share;
+mod template;
 mod text;
-pub use b64::{process_decode, process_encode};
-pub use csv_convert::process_csv;
-pub use gen_pass::process_genpass;
+mod text_age;
+mod text_armor;
+mod text_diff;
+mod text_jws;
+mod text_kdf;
+mod text_message;
+mod text_stream;
+mod text_tree;
+mod time;
+mod url_codec;
+mod vault;
+mod watch;
+pub use api::{process_api_request, process_api_serve, process_api_stdio};
+pub use archive::{process_archive_create, process_archive_extract, ArchiveSummary};
+pub use b64::{
+    process_decode, process_decode_stream, process_decode_stream_async, process_encode,
+    process_encode_stream, process_encode_stream_async, process_inspect, Base64Inspection,
+};
+pub use cert::{process_cert_generate, process_cert_inspect, CertInfo, GeneratedCert};
+pub use chain::{process_chain, ChainStep, ChainValue};
+pub use compress::{process_compress, process_decompress};
+pub use csv_convert::{
+    process_csv, process_csv_convert_dir, process_csv_join, CsvConvertResult, CsvDialect, CsvLookup,
+};
+pub use csv_json::process_csv_from_json;
+pub use csv_schema::{infer_csv_schema, process_csv_schema, ColumnSchema, ColumnType, CsvTypes};
+pub use csv_sort::{CsvSort, SortKey};
+pub use dns::{process_dns_lookup, DnsRecord};
+pub use gen_pass::{process_genpass, process_genpass_with, PasswordCharset};
+pub use gen_rand::{encode_rand_bytes, process_rand_bytes, process_rand_int, process_rand_token};
+pub use gen_uuid::{process_uuid, process_uuid_decode, UuidInfo};
+pub use hash::{process_hash, process_hash_async, process_hash_check, HashCheckResult};
 
-pub use http_serve::process_http_serve;
+pub use hex::{process_hex_dump, process_hex_revert};
+pub use http_bench::{process_http_bench, HttpBenchReport};
+pub use http_echo::process_http_echo;
+pub use http_serve::{process_http_serve, HttpServeConfig, TlsConfig};
+pub use http_throttle::ByteRate;
+pub use ip::{process_ip_interfaces, process_public_ip, InterfaceAddr};
+pub use json::{process_json_fmt, process_json_get};
+pub use key_fetch::{key_fingerprint, process_fetch_key};
+pub use key_info::{process_key_info, KeyInfo};
+pub use otp::{
+    decode_otp_secret, process_hotp, process_otp_new, process_otp_uri, process_totp,
+    process_totp_now, OtpParams,
+};
+pub use pass_meta::{password_policy_hash, PasswordMeta, PasswordRecord};
+pub use qr::process_qrcode;
+pub use share::{process_share_secret, SharedSecret, SHARE_DIR};
+pub use template::{process_template, RenderedTemplate};
 pub use text::{
-    process_generate_key, process_text_decrypt, process_text_encrypt, process_text_sign,
-    process_text_verify,
+    decode_signature, process_generate_key, process_text_decrypt, process_text_decrypt_password,
+    process_text_encrypt, process_text_encrypt_password, process_text_sign, process_text_verify,
+    Verification,
+};
+pub use text_age::{process_age_decrypt, process_age_encrypt};
+pub use text_armor::{process_text_sign_armored, process_text_verify_armored, ArmoredSignature};
+pub use text_diff::{process_text_diff, DiffHunk, DiffLine, DiffTag, FileDiff};
+pub use text_jws::{process_jws_sign, process_jws_verify};
+pub use text_kdf::{process_derive_key, PBKDF2_DEFAULT_ITERATIONS};
+pub use text_message::{process_text_encrypt_armored, EncryptedMessage};
+pub use text_stream::{
+    process_stream_decrypt, process_stream_decrypt_async, process_stream_encrypt,
+    process_stream_encrypt_async,
 };
+pub use text_tree::{
+    process_text_sign_tree, process_text_verify_tree, TreeManifest, TreeVerifyResult,
+};
+pub use time::{process_time_format, process_time_parse};
+pub use url_codec::{
+    process_url_decode, process_url_encode, process_url_parse, UrlParam, UrlParts,
+};
+pub use vault::{Vault, VaultEntry};
+pub use watch::process_watch;
 
-pub use jwt::{process_jwt_sign, process_jwt_verify};
+pub use jwt::{
+    process_jwt_introspect, process_jwt_issue, process_jwt_refresh, process_jwt_report,
+    process_jwt_sign, process_jwt_verify, JwtCheck, JwtKeySet, JwtReport, JwtTokens,
+    JWT_DEFAULT_LEEWAY,
+};
@@ -0,0 +1,127 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use chrono::{DateTime, Duration, Utc};
+use data_encoding::HEXLOWER;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{PasswordCharset, Result};
+
+/// Rotation bookkeeping for generated passwords, keyed by label. Only metadata is stored,
+/// never the password itself.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PasswordMeta {
+    entries: BTreeMap<String, PasswordRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordRecord {
+    pub label: String,
+    pub created_at: DateTime<Utc>,
+    /// Hash of the generation options, to tell when a password predates a policy change
+    pub policy: String,
+    pub rotate_days: u32,
+}
+
+impl PasswordRecord {
+    pub fn due_at(&self) -> DateTime<Utc> {
+        self.created_at + Duration::days(self.rotate_days as i64)
+    }
+}
+
+/// A short fingerprint of the options a password was generated with
+pub fn password_policy_hash(length: u8, charset: &PasswordCharset) -> String {
+    let mut policy = format!(
+        "length={};upper={};lowercase={};numbers={};symbols={}",
+        length, charset.upper, charset.lowercase, charset.numbers, charset.symbols
+    );
+    // only appended when set, so the passwords recorded before these options keep their hash
+    if charset.no_ambiguous {
+        policy.push_str(";no_ambiguous=true");
+    }
+    if let Some(custom) = &charset.custom {
+        policy.push_str(&format!(";charset={}", custom));
+    }
+    if !charset.exclude.is_empty() {
+        policy.push_str(&format!(";exclude={}", charset.exclude));
+    }
+    let mins = [
+        ("min_upper", charset.min_upper),
+        ("min_lowercase", charset.min_lowercase),
+        ("min_numbers", charset.min_numbers),
+        ("min_symbols", charset.min_symbols),
+    ];
+    for (name, min) in mins.into_iter().filter(|(_, min)| *min > 0) {
+        policy.push_str(&format!(";{}={}", name, min));
+    }
+    HEXLOWER.encode(&Sha256::digest(policy)[..8])
+}
+
+impl PasswordMeta {
+    /// Load the records at `path`; a missing file has none
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(PasswordMeta::default());
+        }
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Record a freshly generated password, restarting the clock of an existing label
+    pub fn record(&mut self, label: &str, policy: String, rotate_days: u32, now: DateTime<Utc>) {
+        let record = PasswordRecord {
+            label: label.to_string(),
+            created_at: now,
+            policy,
+            rotate_days,
+        };
+        self.entries.insert(label.to_string(), record);
+    }
+
+    /// Records past their rotation interval, oldest deadline first
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<&PasswordRecord> {
+        let mut due: Vec<_> = self
+            .entries
+            .values()
+            .filter(|r| r.due_at() <= now)
+            .collect();
+        due.sort_by_key(|r| r.due_at());
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_meta_due() -> Result<()> {
+        let path = std::env::temp_dir().join("rcli-passwords.meta");
+        let _ = fs::remove_file(&path);
+        let now = Utc::now();
+        let policy = password_policy_hash(16, &PasswordCharset::default());
+        let mut meta = PasswordMeta::load(&path)?;
+        meta.record("db", policy.clone(), 90, now - Duration::days(100));
+        meta.record("vpn", policy.clone(), 30, now - Duration::days(45));
+        meta.record("mail", policy, 90, now);
+        meta.save(&path)?;
+
+        let meta = PasswordMeta::load(&path)?;
+        let due: Vec<&str> = meta.due(now).iter().map(|r| r.label.as_str()).collect();
+        assert_eq!(due, ["vpn", "db"]);
+        assert_ne!(
+            password_policy_hash(16, &PasswordCharset::default()),
+            password_policy_hash(20, &PasswordCharset::default())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use qrcode::{
+    render::{svg, unicode::Dense1x2},
+    Color, QrCode,
+};
+
+use crate::{QrFormat, RcliError, Result};
+
+/// Blank modules around the code, the minimum the QR spec asks scanners to expect
+const QUIET_ZONE: usize = 4;
+
+/// Render `data` as a QR code: a PNG or SVG image, or block characters for a terminal.
+/// `scale` is the size of a module in pixels for PNG output.
+pub fn process_qrcode(data: &[u8], format: QrFormat, scale: u32) -> Result<Vec<u8>> {
+    let code = QrCode::new(data)
+        .map_err(|e| RcliError::Unsupported(format!("Can't encode as a QR code: {}", e)))?;
+    let rendered = match format {
+        // drawn light on dark, which scans on the usual dark terminal background
+        QrFormat::Terminal => code
+            .render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build()
+            .into_bytes(),
+        QrFormat::Svg => code
+            .render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .build()
+            .into_bytes(),
+        QrFormat::Png => encode_png(&code, scale.max(1) as usize)?,
+    };
+    Ok(rendered)
+}
+
+fn encode_png(code: &QrCode, scale: usize) -> Result<Vec<u8>> {
+    let modules = code.width();
+    let size = (modules + 2 * QUIET_ZONE) * scale;
+    let colors = code.to_colors();
+    let mut pixels = vec![0xffu8; size * size];
+    for (i, color) in colors.iter().enumerate() {
+        if *color == Color::Light {
+            continue;
+        }
+        let (x, y) = (i % modules + QUIET_ZONE, i / modules + QUIET_ZONE);
+        for row in y * scale..(y + 1) * scale {
+            pixels[row * size + x * scale..row * size + (x + 1) * scale].fill(0);
+        }
+    }
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_qrcode() -> Result<()> {
+        let png = process_qrcode(b"hello", QrFormat::Png, 2)?;
+        assert!(png.starts_with(b"\x89PNG"));
+        // version 1 is 21 modules wide, plus the quiet zone on both sides
+        let decoder = png::Decoder::new(png.as_slice());
+        let reader = decoder
+            .read_info()
+            .map_err(|e| RcliError::format(e.to_string()))?;
+        assert_eq!(reader.info().width, (21 + 8) * 2);
+
+        let svg = String::from_utf8(process_qrcode(b"hello", QrFormat::Svg, 8)?)?;
+        assert!(svg.contains("<svg"));
+        let text = String::from_utf8(process_qrcode(b"hello", QrFormat::Terminal, 8)?)?;
+        assert!(text.lines().count() > 10);
+
+        assert!(process_qrcode(&[b'x'; 8000], QrFormat::Png, 8).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use std::{fs, path::Path};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use data_encoding::HEXLOWER;
+use rand::RngCore;
+
+use super::text::{ChaCha20Poly1305, TextEncryptor};
+use crate::{secure_rng, Result};
+
+/// Directory (relative to the served root) holding one-time shares; files in it are
+/// removed by `http serve` after the first successful download.
+pub const SHARE_DIR: &str = ".rcli-share";
+
+#[derive(Debug)]
+pub struct SharedSecret {
+    /// url path of the ciphertext relative to the served root
+    pub path: String,
+    /// url-safe base64 encryption key, meant to travel in the url fragment
+    pub key: String,
+}
+
+/// Encrypt the secret with a fresh random key and drop the ciphertext into the served directory
+pub fn process_share_secret(secret: &[u8], dir: impl AsRef<Path>) -> Result<SharedSecret> {
+    let mut key = [0u8; 32];
+    let mut rng = secure_rng()?;
+    rng.fill_bytes(&mut key);
+    let mut id = [0u8; 16];
+    rng.fill_bytes(&mut id);
+
+    let encrypted = ChaCha20Poly1305::new(key).encrypt(&mut &secret[..])?;
+    let share_dir = dir.as_ref().join(SHARE_DIR);
+    fs::create_dir_all(&share_dir)?;
+    let name = format!("{}.enc", HEXLOWER.encode(&id));
+    // same encoding as `rcli text encrypt` so `rcli text decrypt` can open it
+    fs::write(share_dir.join(&name), URL_SAFE_NO_PAD.encode(encrypted))?;
+
+    Ok(SharedSecret {
+        path: format!("{}/{}", SHARE_DIR, name),
+        key: URL_SAFE_NO_PAD.encode(key),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::text::TextDecryptor;
+
+    #[test]
+    fn test_process_share_secret() -> Result<()> {
+        let dir = std::env::temp_dir().join("rcli-share-test");
+        let shared = process_share_secret(b"s3cret", &dir)?;
+        let encrypted = URL_SAFE_NO_PAD.decode(fs::read(dir.join(&shared.path))?)?;
+        let key = URL_SAFE_NO_PAD.decode(&shared.key)?;
+        let decrypted = ChaCha20Poly1305::try_new(&key)?.decrypt(&mut &encrypted[..])?;
+        assert_eq!(decrypted, b"s3cret");
+        Ok(())
+    }
+}
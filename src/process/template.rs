@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use serde_json::Value;
+use tera::{Context, Tera};
+
+use super::csv_convert::record_to_json;
+use crate::{get_reader, CsvDialect, Result, TemplateDataFormat};
+
+const EACH_TEMPLATE: &str = "__each";
+
+/// A template rendered against one data item, and the file `--each` named for it
+#[derive(Debug, PartialEq)]
+pub struct RenderedTemplate {
+    pub path: Option<String>,
+    pub text: String,
+}
+
+/// Render the Tera template at `template` once with JSON `data`, or once per row when the
+/// data is CSV (a mail merge). Object keys and columns are top-level variables, the whole
+/// item is also `data`, and CSV rows get their 0-based `index`. With `each`, a template
+/// for a file name, every rendering is given a path from its own item.
+pub fn process_template(
+    template: &str,
+    data: Option<&str>,
+    format: TemplateDataFormat,
+    each: Option<&str>,
+) -> Result<Vec<RenderedTemplate>> {
+    let mut source = String::new();
+    get_reader(template)?.read_to_string(&mut source)?;
+    // named after the file so .html templates are autoescaped
+    let name = Path::new(template)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "template".to_string());
+    let mut tera = Tera::default();
+    tera.add_raw_template(&name, &source)?;
+    if let Some(each) = each {
+        tera.add_raw_template(EACH_TEMPLATE, each)?;
+    }
+    let items = match data {
+        Some(data) => load_data(data, format)?,
+        None => vec![(Value::Object(Default::default()), None)],
+    };
+    items
+        .iter()
+        .map(|(item, index)| {
+            let context = context(item, *index)?;
+            Ok(RenderedTemplate {
+                path: each
+                    .map(|_| tera.render(EACH_TEMPLATE, &context))
+                    .transpose()?,
+                text: tera.render(&name, &context)?,
+            })
+        })
+        .collect()
+}
+
+fn load_data(path: &str, format: TemplateDataFormat) -> Result<Vec<(Value, Option<usize>)>> {
+    let reader = get_reader(path)?;
+    match format {
+        TemplateDataFormat::Json => Ok(vec![(serde_json::from_reader(reader)?, None)]),
+        TemplateDataFormat::Csv | TemplateDataFormat::Tsv => {
+            let dialect = CsvDialect {
+                delimiter: if format == TemplateDataFormat::Tsv {
+                    b'\t'
+                } else {
+                    b','
+                },
+                ..Default::default()
+            };
+            let mut reader = dialect.builder().from_reader(reader);
+            let headers = reader.headers()?.clone();
+            reader
+                .records()
+                .enumerate()
+                .map(|(i, record)| Ok((record_to_json(&headers, &record?, None)?, Some(i))))
+                .collect()
+        }
+    }
+}
+
+fn context(item: &Value, index: Option<usize>) -> Result<Context> {
+    let mut context = match item {
+        Value::Object(_) => Context::from_value(item.clone())?,
+        _ => Context::new(),
+    };
+    context.insert("data", item);
+    if let Some(index) = index {
+        context.insert("index", &index);
+    }
+    Ok(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_process_template_json() -> Result<()> {
+        let template = write(
+            "rcli-template.html",
+            "<h1>{{ title }}</h1>{% for tag in data.tags %}<i>{{ tag }}</i>{% endfor %}",
+        );
+        let data = write(
+            "rcli-template.json",
+            r#"{"title": "Tom & Jerry", "tags": ["a", "b"]}"#,
+        );
+        let rendered = process_template(&template, Some(&data), TemplateDataFormat::Json, None)?;
+        assert_eq!(
+            rendered,
+            vec![RenderedTemplate {
+                path: None,
+                // .html templates are autoescaped
+                text: "<h1>Tom &amp; Jerry</h1><i>a</i><i>b</i>".to_string(),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_template_csv_rows() -> Result<()> {
+        let template = write("rcli-template.txt", "Dear {{ Name }}, you are #{{ index }}");
+        let rendered = process_template(
+            &template,
+            Some("assets/juventus.csv"),
+            TemplateDataFormat::Csv,
+            Some("out/{{ data[\"Kit Number\"] }}.txt"),
+        )?;
+        assert_eq!(rendered.len(), 27);
+        assert_eq!(rendered[0].text, "Dear Wojciech Szczesny, you are #0");
+        assert_eq!(rendered[0].path.as_deref(), Some("out/1.txt"));
+        // unknown variables are errors rather than blanks
+        let template = write("rcli-template-bad.txt", "{{ nope }}");
+        assert!(process_template(&template, None, TemplateDataFormat::Json, None).is_err());
+        Ok(())
+    }
+}
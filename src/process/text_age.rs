@@ -0,0 +1,93 @@
+use std::{
+    io::{BufReader, Read, Write},
+    path::Path,
+};
+
+use age::armor::{ArmoredReader, ArmoredWriter, Format};
+
+use crate::{RcliError, Result};
+
+/// Encrypt the reader to one or more `age1...` X25519 recipients, producing a file any
+/// age implementation can open. Returns plaintext bytes read.
+pub fn process_age_encrypt(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    recipients: &[String],
+    armor: bool,
+) -> Result<u64> {
+    let recipients = recipients
+        .iter()
+        .map(|r| {
+            r.parse::<age::x25519::Recipient>()
+                .map_err(|e| RcliError::invalid_key(format!("Invalid age recipient {}: {}", r, e)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let encryptor =
+        age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))?;
+    let format = if armor {
+        Format::AsciiArmor
+    } else {
+        Format::Binary
+    };
+    let mut armored = ArmoredWriter::wrap_output(writer, format)?;
+    let mut stream = encryptor.wrap_output(&mut armored)?;
+    let total = std::io::copy(reader, &mut stream)?;
+    stream.finish()?;
+    armored.finish()?.flush()?;
+    Ok(total)
+}
+
+/// Decrypt a binary or armored age file with the identities in an `age-keygen` style
+/// identity file. Returns plaintext bytes written.
+pub fn process_age_decrypt(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    identity: impl AsRef<Path>,
+) -> Result<u64> {
+    let identity = identity.as_ref();
+    let identities = age::IdentityFile::from_file(identity.to_string_lossy().to_string())
+        .map_err(|e| {
+            RcliError::invalid_key(format!(
+                "Cannot read identity {}: {}",
+                identity.display(),
+                e
+            ))
+        })?
+        .into_identities()?;
+    let decryptor = age::Decryptor::new(ArmoredReader::new(BufReader::new(reader)))?;
+    let mut stream = decryptor.decrypt(identities.iter().map(|i| i.as_ref()))?;
+    let total = std::io::copy(&mut stream, writer)?;
+    writer.flush()?;
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::secrecy::ExposeSecret;
+
+    #[test]
+    fn test_age_encrypt_decrypt() -> Result<()> {
+        let key = age::x25519::Identity::generate();
+        let path = std::env::temp_dir().join("rcli-age-identity.txt");
+        std::fs::write(&path, key.to_string().expose_secret())?;
+        let recipients = vec![key.to_public().to_string()];
+        let data = b"hello age";
+        for armor in [false, true] {
+            let mut encrypted = Vec::new();
+            process_age_encrypt(&mut &data[..], &mut encrypted, &recipients, armor)?;
+            assert_eq!(
+                encrypted.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----"),
+                armor
+            );
+            let mut decrypted = Vec::new();
+            process_age_decrypt(&mut &encrypted[..], &mut decrypted, &path)?;
+            assert_eq!(decrypted, data);
+        }
+        let other = age::x25519::Identity::generate().to_public().to_string();
+        let mut encrypted = Vec::new();
+        process_age_encrypt(&mut &data[..], &mut encrypted, &[other], false)?;
+        assert!(process_age_decrypt(&mut &encrypted[..], &mut Vec::new(), &path).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use super::text::{get_canonical_reader, public_key_der, sign_reader, verify_any};
+use crate::{
+    key_fingerprint, Canonicalize, Input, RcliError, Result, TextSignFormat, Verification,
+};
+
+const ARMOR_BEGIN: &str = "-----BEGIN RCLI SIGNATURE-----";
+const ARMOR_END: &str = "-----END RCLI SIGNATURE-----";
+const ARMOR_WIDTH: usize = 64;
+
+/// A detached signature along with what is needed to check it, in an OpenPGP-like
+/// ASCII armor that survives being pasted into emails and tickets
+#[derive(Debug, Clone)]
+pub struct ArmoredSignature {
+    pub format: TextSignFormat,
+    /// `sha256:<hex>` of the public key, never set for shared secrets
+    pub key: Option<String>,
+    pub created: DateTime<Utc>,
+    pub canonicalize: Vec<Canonicalize>,
+    pub signature: Vec<u8>,
+}
+
+impl ArmoredSignature {
+    pub fn is_armored(text: &[u8]) -> bool {
+        text.trim_ascii_start().starts_with(ARMOR_BEGIN.as_bytes())
+    }
+
+    pub fn encode(&self) -> String {
+        let mut lines = vec![ARMOR_BEGIN.to_string(), format!("Format: {}", self.format)];
+        if let Some(key) = &self.key {
+            lines.push(format!("Key: {}", key));
+        }
+        lines.push(format!(
+            "Created: {}",
+            self.created.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ));
+        if !self.canonicalize.is_empty() {
+            let steps: Vec<String> = self.canonicalize.iter().map(|c| c.to_string()).collect();
+            lines.push(format!("Canonicalize: {}", steps.join(",")));
+        }
+        lines.push(String::new());
+        let body = STANDARD.encode(&self.signature);
+        for chunk in body.as_bytes().chunks(ARMOR_WIDTH) {
+            lines.push(String::from_utf8_lossy(chunk).to_string());
+        }
+        lines.push(ARMOR_END.to_string());
+        lines.join("\n")
+    }
+
+    pub fn decode(text: &str) -> Result<Self> {
+        let invalid = |msg: &str| RcliError::format(format!("Invalid armored signature: {}", msg));
+        let mut lines = text.trim().lines().map(str::trim);
+        if lines.next() != Some(ARMOR_BEGIN) {
+            return Err(invalid("missing BEGIN line"));
+        }
+        let (mut format, mut key, mut created, mut canonicalize) = (None, None, None, Vec::new());
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line
+                .split_once(": ")
+                .ok_or_else(|| invalid("malformed header"))?;
+            match name {
+                "Format" => format = Some(value.parse().map_err(|_| invalid(line))?),
+                "Key" => key = Some(value.to_string()),
+                "Created" => created = Some(DateTime::parse_from_rfc3339(value)?.to_utc()),
+                "Canonicalize" => {
+                    canonicalize = value
+                        .split(',')
+                        .map(|step| step.parse().map_err(|_| invalid(line)))
+                        .collect::<Result<_>>()?
+                }
+                // unknown headers are informational
+                _ => {}
+            }
+        }
+        let mut body = String::new();
+        let mut ended = false;
+        for line in lines {
+            if line == ARMOR_END {
+                ended = true;
+                break;
+            }
+            body.push_str(line);
+        }
+        if !ended {
+            return Err(invalid("missing END line"));
+        }
+        Ok(Self {
+            format: format.ok_or_else(|| invalid("missing Format header"))?,
+            key,
+            created: created.ok_or_else(|| invalid("missing Created header"))?,
+            canonicalize,
+            signature: STANDARD.decode(body)?,
+        })
+    }
+}
+
+/// Sign `input` and wrap the signature in an armored block
+pub fn process_text_sign_armored(
+    input: impl Into<Input>,
+    key: &str,
+    format: TextSignFormat,
+    canonicalize: &[Canonicalize],
+) -> Result<String> {
+    let mut reader = get_canonical_reader(input, canonicalize)?;
+    let signature = sign_reader(&mut reader, key, format)?;
+    let armored = ArmoredSignature {
+        format,
+        key: public_key_der(key, format, true)?.map(|der| key_fingerprint(&der)),
+        created: Utc::now(),
+        canonicalize: canonicalize.to_vec(),
+        signature,
+    };
+    Ok(armored.encode())
+}
+
+/// Verify `input` against an armored signature, using the canonicalization it names.
+/// The index of the first of `keys` the signature verifies with, or why it verifies with none.
+/// Keys other than the one named in the armor are skipped, and an error if that leaves none.
+///
+/// The armor's format is untrusted: it must match `format` when given, and a shared-secret
+/// format, whose armor names no key, is only accepted when `format` asks for it.
+pub fn process_text_verify_armored(
+    input: impl Into<Input>,
+    keys: &[impl AsRef<str>],
+    armored: &ArmoredSignature,
+    format: Option<TextSignFormat>,
+) -> Result<std::result::Result<usize, Verification>> {
+    match format {
+        Some(format) if format != armored.format => {
+            return Err(RcliError::format(format!(
+                "Signature is {}, not {}",
+                armored.format, format
+            )));
+        }
+        None if armored.key.is_none() => {
+            return Err(RcliError::format(format!(
+                "A {} signature has no key to check, its format must be given explicitly",
+                armored.format
+            )));
+        }
+        _ => {}
+    }
+    let mut candidates = Vec::new();
+    let mut others = Vec::new();
+    for (i, key) in keys.iter().enumerate() {
+        let Some(expected) = &armored.key else {
+            candidates.push(i);
+            continue;
+        };
+        let fingerprint =
+            public_key_der(key.as_ref(), armored.format, false)?.map(|der| key_fingerprint(&der));
+        match fingerprint {
+            Some(fingerprint) if &fingerprint == expected => candidates.push(i),
+            fingerprint => others.push(fingerprint.unwrap_or_default()),
+        }
+    }
+    if candidates.is_empty() {
+        return Err(RcliError::invalid_key(format!(
+            "Signed with key {}, not {}",
+            armored.key.as_deref().unwrap_or_default(),
+            others.join(", ")
+        )));
+    }
+    let mut reader = get_canonical_reader(input, &armored.canonicalize)?;
+    let keys: Vec<&str> = candidates.iter().map(|&i| keys[i].as_ref()).collect();
+    let verified = verify_any(&mut reader, &keys, armored.format, &armored.signature)?;
+    Ok(verified.map(|i| candidates[i]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_generate_key, KeyFormat};
+
+    #[test]
+    fn test_armored_sign_verify() -> Result<()> {
+        let dir = std::env::temp_dir();
+        let input = dir.join("rcli-armor.txt");
+        std::fs::write(&input, "hello\r\n")?;
+        let input = input.to_str().unwrap();
+        let mut keys = Vec::new();
+        for name in ["a", "b"] {
+            let pair = process_generate_key(TextSignFormat::Ed25519, KeyFormat::Pem, None)?;
+            let (sk, pk) = (
+                dir.join(format!("rcli-armor-{}.sk", name)),
+                dir.join(format!("rcli-armor-{}.pk", name)),
+            );
+            std::fs::write(&sk, &pair[0])?;
+            std::fs::write(&pk, &pair[1])?;
+            keys.push((sk, pk));
+        }
+        let (sk, pk) = (keys[0].0.to_str().unwrap(), keys[0].1.to_str().unwrap());
+
+        let text =
+            process_text_sign_armored(input, sk, TextSignFormat::Ed25519, &[Canonicalize::Lf])?;
+        assert!(ArmoredSignature::is_armored(text.as_bytes()));
+        assert!(text.contains("Format: ed25519\nKey: sha256:"));
+        let armored = ArmoredSignature::decode(&text)?;
+        assert_eq!(armored.canonicalize, [Canonicalize::Lf]);
+        assert_eq!(
+            process_text_verify_armored(input, &[pk], &armored, None)?,
+            Ok(0)
+        );
+        let other = keys[1].1.to_str().unwrap();
+        assert!(process_text_verify_armored(input, &[other], &armored, None).is_err());
+        // during a rotation the key named in the armor is picked out of several
+        assert_eq!(
+            process_text_verify_armored(input, &[other, pk], &armored, None)?,
+            Ok(1)
+        );
+
+        // shared secrets are armored without a fingerprint
+        let text =
+            process_text_sign_armored(input, "fixtures/blake3.txt", TextSignFormat::Blake3, &[])?;
+        let armored = ArmoredSignature::decode(&text)?;
+        assert!(armored.key.is_none());
+        let blake3 = Some(TextSignFormat::Blake3);
+        assert_eq!(
+            process_text_verify_armored(input, &["fixtures/blake3.txt"], &armored, blake3)?,
+            Ok(0)
+        );
+        assert_eq!(
+            process_text_verify_armored(
+                "fixtures/b64.txt",
+                &["fixtures/blake3.txt"],
+                &armored,
+                blake3
+            )?,
+            Err(Verification::Mismatch)
+        );
+        // the armor can't downgrade a public-key verification to a MAC
+        assert!(
+            process_text_verify_armored(input, &["fixtures/blake3.txt"], &armored, None).is_err()
+        );
+        let ed25519 = Some(TextSignFormat::Ed25519);
+        assert!(process_text_verify_armored(input, &[pk], &armored, ed25519).is_err());
+        // nor is a public key accepted as the shared secret
+        assert!(process_text_sign_armored(input, pk, TextSignFormat::Blake3, &[]).is_err());
+        assert!(process_text_verify_armored(input, &[pk], &armored, blake3).is_err());
+        assert!(ArmoredSignature::decode(&text.replace(ARMOR_END, "")).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use colored::Colorize;
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+
+use crate::{get_reader, Result};
+
+/// The differences between two texts, as the hunks of a unified diff
+#[derive(Debug, Serialize)]
+pub struct FileDiff {
+    pub old: String,
+    pub new: String,
+    pub hunks: Vec<DiffHunk>,
+}
+
+/// A run of changes with its context lines; starts are 1-based like in the `@@` header
+#[derive(Debug, Serialize)]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffLine {
+    pub tag: DiffTag,
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub text: String,
+    #[serde(skip)]
+    missing_newline: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffTag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Diff the files `old` and `new` line by line, keeping `context` unchanged lines around
+/// each change
+pub fn process_text_diff(old: &str, new: &str, context: usize) -> Result<FileDiff> {
+    let read = |path: &str| -> Result<String> {
+        let mut text = String::new();
+        get_reader(path)?.read_to_string(&mut text)?;
+        Ok(text)
+    };
+    Ok(FileDiff::new(old, &read(old)?, new, &read(new)?, context))
+}
+
+impl FileDiff {
+    pub fn new(old: &str, old_text: &str, new: &str, new_text: &str, context: usize) -> Self {
+        let diff = TextDiff::from_lines(old_text, new_text);
+        let hunks = diff
+            .grouped_ops(context)
+            .iter()
+            .map(|ops| {
+                let (first, last) = (&ops[0], &ops[ops.len() - 1]);
+                let old_range = first.old_range().start..last.old_range().end;
+                let new_range = first.new_range().start..last.new_range().end;
+                let lines = ops
+                    .iter()
+                    .flat_map(|op| diff.iter_changes(op))
+                    .map(|change| DiffLine {
+                        tag: match change.tag() {
+                            ChangeTag::Equal => DiffTag::Equal,
+                            ChangeTag::Delete => DiffTag::Delete,
+                            ChangeTag::Insert => DiffTag::Insert,
+                        },
+                        old_line: change.old_index().map(|i| i + 1),
+                        new_line: change.new_index().map(|i| i + 1),
+                        text: change.value().trim_end_matches('\n').to_string(),
+                        missing_newline: change.missing_newline(),
+                    })
+                    .collect();
+                DiffHunk {
+                    old_start: hunk_start(old_range.start, old_range.len()),
+                    old_lines: old_range.len(),
+                    new_start: hunk_start(new_range.start, new_range.len()),
+                    new_lines: new_range.len(),
+                    lines,
+                }
+            })
+            .collect();
+        Self {
+            old: old.to_string(),
+            new: new.to_string(),
+            hunks,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    /// The diff in unified format, with removals in red and additions in green if `color`
+    pub fn to_unified(&self, color: bool) -> String {
+        let paint = |line: String, tag: Option<DiffTag>| match (color, tag) {
+            (false, _) => line,
+            (true, None) => line.cyan().to_string(),
+            (true, Some(DiffTag::Delete)) => line.red().to_string(),
+            (true, Some(DiffTag::Insert)) => line.green().to_string(),
+            (true, Some(DiffTag::Equal)) => line,
+        };
+        let header = |line: String| if color { line.bold().to_string() } else { line };
+        let mut lines = vec![
+            header(format!("--- {}", self.old)),
+            header(format!("+++ {}", self.new)),
+        ];
+        for hunk in &self.hunks {
+            lines.push(paint(
+                format!(
+                    "@@ -{} +{} @@",
+                    hunk_range(hunk.old_start, hunk.old_lines),
+                    hunk_range(hunk.new_start, hunk.new_lines)
+                ),
+                None,
+            ));
+            for line in &hunk.lines {
+                let sign = match line.tag {
+                    DiffTag::Equal => ' ',
+                    DiffTag::Delete => '-',
+                    DiffTag::Insert => '+',
+                };
+                lines.push(paint(format!("{}{}", sign, line.text), Some(line.tag)));
+                if line.missing_newline {
+                    lines.push("\\ No newline at end of file".to_string());
+                }
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// Empty ranges start at the line before them, as in diff(1)
+fn hunk_start(start: usize, len: usize) -> usize {
+    if len == 0 {
+        start
+    } else {
+        start + 1
+    }
+}
+
+fn hunk_range(start: usize, len: usize) -> String {
+    if len == 1 {
+        start.to_string()
+    } else {
+        format!("{},{}", start, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_diff_unified() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni";
+        let diff = FileDiff::new("old.txt", old, "new.txt", new, 1);
+        assert_eq!(diff.hunks.len(), 2);
+        assert_eq!(
+            diff.to_unified(false),
+            [
+                "--- old.txt",
+                "+++ new.txt",
+                "@@ -1,3 +1,3 @@",
+                " a",
+                "-b",
+                "+B",
+                " c",
+                "@@ -8 +8,2 @@",
+                " h",
+                "+i",
+                "\\ No newline at end of file",
+            ]
+            .join("\n")
+        );
+        let hunk = &diff.hunks[0];
+        assert_eq!(hunk.lines[1].tag, DiffTag::Delete);
+        assert_eq!(
+            (hunk.lines[1].old_line, hunk.lines[1].new_line),
+            (Some(2), None)
+        );
+        assert_eq!(
+            (hunk.lines[2].old_line, hunk.lines[2].new_line),
+            (None, Some(2))
+        );
+    }
+
+    #[test]
+    fn test_file_diff_identical_and_empty() {
+        assert!(FileDiff::new("a", "same\n", "b", "same\n", 3).is_empty());
+        let diff = FileDiff::new("a", "", "b", "x\n", 3);
+        let hunk = &diff.hunks[0];
+        assert_eq!(
+            (
+                hunk.old_start,
+                hunk.old_lines,
+                hunk.new_start,
+                hunk.new_lines
+            ),
+            (0, 0, 1, 1)
+        );
+        assert!(diff.to_unified(false).contains("@@ -0,0 +1 @@"));
+    }
+}
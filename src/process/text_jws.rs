@@ -0,0 +1,242 @@
+use std::io::Read;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde_json::{json, Value};
+
+use super::text::{sign_reader, verify_reader};
+use crate::{get_reader, Input, JwsSerialization, RcliError, Result, TextSignFormat};
+
+/// RFC 7518 `alg` for each signing format; keyed BLAKE3 and Ed25519ph have no JOSE equivalent
+fn jws_alg(format: TextSignFormat) -> Result<&'static str> {
+    match format {
+        TextSignFormat::HmacSha256 => Ok("HS256"),
+        TextSignFormat::Rsa => Ok("RS256"),
+        TextSignFormat::EcdsaP256 => Ok("ES256"),
+        TextSignFormat::Ed25519 => Ok("EdDSA"),
+        TextSignFormat::Blake3 | TextSignFormat::Ed25519ph => Err(RcliError::Unsupported(format!(
+            "{} can't be used for JWS",
+            format
+        ))),
+    }
+}
+
+/// Sign the payload read from `input` as a JWS. A detached JWS leaves the payload out,
+/// the receiver supplies it separately (RFC 7515 appendix F).
+pub fn process_jws_sign(
+    input: impl Into<Input>,
+    key: &str,
+    format: TextSignFormat,
+    serialization: JwsSerialization,
+    detached: bool,
+    kid: Option<&str>,
+) -> Result<String> {
+    let mut payload = Vec::new();
+    get_reader(input)?.read_to_end(&mut payload)?;
+    let mut header = json!({ "alg": jws_alg(format)? });
+    if let Some(kid) = kid {
+        header["kid"] = json!(kid);
+    }
+    let protected = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?);
+    let payload = URL_SAFE_NO_PAD.encode(&payload);
+    let signing_input = format!("{}.{}", protected, payload);
+    let signature = sign_reader(&mut signing_input.as_bytes(), key, format)?;
+    let signature = URL_SAFE_NO_PAD.encode(to_jose_signature(format, signature)?);
+    let payload = if detached { "" } else { payload.as_str() };
+    Ok(match serialization {
+        JwsSerialization::Compact => format!("{}.{}.{}", protected, payload, signature),
+        JwsSerialization::Json => {
+            let mut jws = json!({ "protected": protected, "signature": signature });
+            if !detached {
+                jws["payload"] = json!(payload);
+            }
+            serde_json::to_string(&jws)?
+        }
+    })
+}
+
+/// Verify a compact or JSON serialized JWS, flattened or general. `payload` is the input
+/// holding the content of a detached JWS.
+pub fn process_jws_verify(
+    input: impl Into<Input>,
+    key: &str,
+    format: TextSignFormat,
+    payload: Option<&str>,
+) -> Result<bool> {
+    let mut jws = String::new();
+    get_reader(input)?.read_to_string(&mut jws)?;
+    let jws = jws.trim();
+    let detached = match payload {
+        Some(payload) => {
+            let mut buf = Vec::new();
+            get_reader(payload)?.read_to_end(&mut buf)?;
+            Some(URL_SAFE_NO_PAD.encode(buf))
+        }
+        None => None,
+    };
+
+    // (protected, payload, signature) for every signature in the JWS
+    let mut parts = Vec::new();
+    if jws.starts_with('{') {
+        let value: Value = serde_json::from_str(jws)?;
+        let embedded = value.get("payload").and_then(Value::as_str).unwrap_or("");
+        let signatures = match value.get("signatures").and_then(Value::as_array) {
+            Some(signatures) => signatures.clone(),
+            None => vec![value.clone()],
+        };
+        for signature in signatures {
+            let field = |name: &str| {
+                signature
+                    .get(name)
+                    .and_then(Value::as_str)
+                    .map(String::from)
+                    .ok_or_else(|| RcliError::format(format!("Invalid JWS: missing {}", name)))
+            };
+            parts.push((
+                field("protected")?,
+                embedded.to_string(),
+                field("signature")?,
+            ));
+        }
+    } else {
+        let segments: Vec<&str> = jws.split('.').collect();
+        if segments.len() != 3 {
+            return Err(RcliError::format(format!(
+                "Invalid JWS: expected 3 segments, got {}",
+                segments.len()
+            )));
+        }
+        parts.push((
+            segments[0].to_string(),
+            segments[1].to_string(),
+            segments[2].to_string(),
+        ));
+    }
+
+    let alg = jws_alg(format)?;
+    for (protected, embedded, signature) in parts {
+        let payload = match (&detached, embedded.is_empty()) {
+            (Some(detached), true) => detached.clone(),
+            (Some(_), false) => {
+                return Err(RcliError::Unsupported(
+                    "JWS already carries a payload, drop --payload".to_string(),
+                ))
+            }
+            (None, true) => {
+                return Err(RcliError::Unsupported(
+                    "JWS has a detached payload, pass it with --payload".to_string(),
+                ))
+            }
+            (None, false) => embedded,
+        };
+        let header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(&protected)?)?;
+        // the algorithm comes from the key type, never from the token itself
+        if header.get("alg").and_then(Value::as_str) != Some(alg) {
+            continue;
+        }
+        let signature = from_jose_signature(format, URL_SAFE_NO_PAD.decode(&signature)?)?;
+        let signing_input = format!("{}.{}", protected, payload);
+        if verify_reader(&mut signing_input.as_bytes(), key, format, &signature)?.is_valid() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// JWS carries ECDSA signatures as fixed size r || s rather than the DER used by `text sign`
+fn to_jose_signature(format: TextSignFormat, signature: Vec<u8>) -> Result<Vec<u8>> {
+    match format {
+        TextSignFormat::EcdsaP256 => Ok(p256::ecdsa::Signature::from_der(&signature)?
+            .to_bytes()
+            .to_vec()),
+        _ => Ok(signature),
+    }
+}
+
+fn from_jose_signature(format: TextSignFormat, signature: Vec<u8>) -> Result<Vec<u8>> {
+    match format {
+        TextSignFormat::EcdsaP256 => Ok(p256::ecdsa::Signature::from_slice(&signature)?
+            .to_der()
+            .as_bytes()
+            .to_vec()),
+        _ => Ok(signature),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_generate_key, KeyFormat};
+
+    #[test]
+    fn test_jws_sign_verify() -> Result<()> {
+        let dir = std::env::temp_dir();
+        let payload = dir.join("rcli-jws-payload.json");
+        std::fs::write(&payload, r#"{"event":"push"}"#)?;
+        let payload = payload.to_str().unwrap();
+        for format in [TextSignFormat::Ed25519, TextSignFormat::EcdsaP256] {
+            let keys = process_generate_key(format, KeyFormat::Pem, None)?;
+            let sk = dir.join(format!("rcli-jws-{}.sk", format));
+            let pk = dir.join(format!("rcli-jws-{}.pk", format));
+            std::fs::write(&sk, &keys[0])?;
+            std::fs::write(&pk, &keys[1])?;
+            let (sk, pk) = (sk.to_str().unwrap(), pk.to_str().unwrap());
+
+            for serialization in [JwsSerialization::Compact, JwsSerialization::Json] {
+                let jws = dir.join(format!("rcli-jws-{}.jws", format));
+                let signed = process_jws_sign(payload, sk, format, serialization, false, None)?;
+                std::fs::write(&jws, &signed)?;
+                let jws = jws.to_str().unwrap();
+                assert!(process_jws_verify(jws, pk, format, None)?);
+
+                let signed = process_jws_sign(payload, sk, format, serialization, true, None)?;
+                std::fs::write(jws, &signed)?;
+                assert!(process_jws_verify(jws, pk, format, Some(payload))?);
+                assert!(!process_jws_verify(
+                    jws,
+                    pk,
+                    format,
+                    Some("fixtures/b64.txt")
+                )?);
+                assert!(process_jws_verify(jws, pk, format, None).is_err());
+            }
+        }
+        assert!(process_jws_sign(
+            payload,
+            "fixtures/blake3.txt",
+            TextSignFormat::Blake3,
+            JwsSerialization::Compact,
+            false,
+            None
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_jws_verify_rfc7515_example() -> Result<()> {
+        // RFC 7515 appendix A.1
+        let dir = std::env::temp_dir();
+        let key = dir.join("rcli-jws-rfc7515.key");
+        std::fs::write(
+            &key,
+            URL_SAFE_NO_PAD.decode(
+                "AyM1SysPpbyDfgZld3umj1qzKObwVMkoqQ-EstJQLr_T-1qS0gZH75aKtMN3Yj0iPS4hcgUuTwjAzZr1Z9CAow",
+            )?,
+        )?;
+        let jws = dir.join("rcli-jws-rfc7515.jws");
+        std::fs::write(
+            &jws,
+            "eyJ0eXAiOiJKV1QiLA0KICJhbGciOiJIUzI1NiJ9\
+             .eyJpc3MiOiJqb2UiLA0KICJleHAiOjEzMDA4MTkzODAsDQogImh0dHA6Ly9leGFtcGxlLmNvbS9pc19yb290Ijp0cnVlfQ\
+             .dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk",
+        )?;
+        let (key, jws) = (key.to_str().unwrap(), jws.to_str().unwrap());
+        assert!(process_jws_verify(
+            jws,
+            key,
+            TextSignFormat::HmacSha256,
+            None
+        )?);
+        Ok(())
+    }
+}
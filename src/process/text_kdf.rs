@@ -0,0 +1,76 @@
+use hkdf::Hkdf;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::{KdfAlgorithm, RcliError, Result};
+
+// OWASP's recommendation for PBKDF2-HMAC-SHA256
+pub const PBKDF2_DEFAULT_ITERATIONS: u32 = 600_000;
+
+/// Derive `len` bytes of key material from `secret`. Argon2id (default parameters) and
+/// PBKDF2-HMAC-SHA256 stretch a passphrase and need a salt; HKDF-SHA256 expands a secret
+/// that is already random, with an optional salt and `info` to separate uses.
+pub fn process_derive_key(
+    secret: &[u8],
+    algorithm: KdfAlgorithm,
+    salt: &[u8],
+    info: &[u8],
+    len: usize,
+    iterations: u32,
+) -> Result<Zeroizing<Vec<u8>>> {
+    if algorithm != KdfAlgorithm::Hkdf && salt.is_empty() {
+        return Err(RcliError::invalid_key(format!(
+            "{} needs a salt",
+            algorithm
+        )));
+    }
+    let mut key = Zeroizing::new(vec![0u8; len]);
+    match algorithm {
+        KdfAlgorithm::Argon2id => argon2::Argon2::default()
+            .hash_password_into(secret, salt, &mut key)
+            .map_err(|e| RcliError::crypto(format!("Error deriving key: {}", e)))?,
+        KdfAlgorithm::Pbkdf2 => {
+            pbkdf2::pbkdf2_hmac::<Sha256>(secret, salt, iterations, &mut key);
+        }
+        KdfAlgorithm::Hkdf => Hkdf::<Sha256>::new(Some(salt), secret)
+            .expand(info, &mut key)
+            .map_err(|_| {
+                RcliError::crypto(format!("HKDF-SHA256 derives at most {} bytes", 255 * 32))
+            })?,
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_derive_key_hkdf() -> Result<()> {
+        // RFC 5869, test case 1
+        let ikm = [0x0bu8; 22];
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        let okm = process_derive_key(&ikm, KdfAlgorithm::Hkdf, &salt, &info, 42, 0)?;
+        assert_eq!(
+            data_encoding::HEXLOWER.encode(&okm[..]),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_derive_key_passphrase() -> Result<()> {
+        let derive = |algorithm, salt: &[u8]| {
+            process_derive_key(b"passphrase", algorithm, salt, b"", 32, 1000)
+        };
+        for algorithm in [KdfAlgorithm::Argon2id, KdfAlgorithm::Pbkdf2] {
+            let key = derive(algorithm, b"saltsalt")?;
+            assert_eq!(key.len(), 32);
+            assert_eq!(key, derive(algorithm, b"saltsalt")?);
+            assert_ne!(key, derive(algorithm, b"pepperpepper")?);
+            assert!(derive(algorithm, b"").is_err());
+        }
+        Ok(())
+    }
+}
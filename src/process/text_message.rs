@@ -0,0 +1,196 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+use super::text::{aead_cipher, aead_cipher_id, load_decryptor, load_encryptor};
+use crate::{get_reader, Input, RcliError, Result, TextCipher};
+
+const MESSAGE_BEGIN: &str = "-----BEGIN RCLI ENCRYPTED MESSAGE-----";
+const MESSAGE_END: &str = "-----END RCLI ENCRYPTED MESSAGE-----";
+const MESSAGE_WIDTH: usize = 64;
+
+// message header: magic | version | cipher | nonce len | nonce, then the ciphertext
+const MESSAGE_MAGIC: &[u8] = b"RCLM";
+const MESSAGE_VERSION: u8 = 1;
+
+/// A ciphertext that says how to decrypt it, so new ciphers can be added without
+/// breaking old messages
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptedMessage {
+    pub cipher: TextCipher,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl EncryptedMessage {
+    /// Encrypt `data` with the key file at `key`
+    pub fn seal(data: &[u8], key: &str, cipher: TextCipher) -> Result<Self> {
+        let nonce_len = nonce_len(cipher)?;
+        // the AEAD encryptors put the nonce in front of the ciphertext
+        let mut sealed = load_encryptor(key, cipher)?.encrypt(&mut &data[..])?;
+        let ciphertext = sealed.split_off(nonce_len);
+        Ok(Self {
+            cipher,
+            nonce: sealed,
+            ciphertext,
+        })
+    }
+
+    pub fn open(&self, key: &str) -> Result<Vec<u8>> {
+        let mut sealed = self.nonce.clone();
+        sealed.extend_from_slice(&self.ciphertext);
+        load_decryptor(key, self.cipher)?.decrypt(&mut sealed.as_slice())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(MESSAGE_MAGIC.len() + 3 + self.nonce.len());
+        buf.extend_from_slice(MESSAGE_MAGIC);
+        buf.extend_from_slice(&[
+            MESSAGE_VERSION,
+            aead_cipher_id(self.cipher)?,
+            self.nonce.len() as u8,
+        ]);
+        buf.extend_from_slice(&self.nonce);
+        buf.extend_from_slice(&self.ciphertext);
+        Ok(buf)
+    }
+
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        let header_len = MESSAGE_MAGIC.len() + 3;
+        if buf.len() < header_len || !buf.starts_with(MESSAGE_MAGIC) {
+            return Err(RcliError::format(
+                "Invalid data: not an rcli encrypted message",
+            ));
+        }
+        let rest = &buf[MESSAGE_MAGIC.len()..];
+        if rest[0] != MESSAGE_VERSION {
+            return Err(RcliError::Unsupported(format!(
+                "Unsupported message version: {}",
+                rest[0]
+            )));
+        }
+        let cipher = aead_cipher(rest[1])?;
+        let nonce_len = rest[2] as usize;
+        if nonce_len != self::nonce_len(cipher)? || rest.len() < 3 + nonce_len {
+            return Err(RcliError::format("Invalid data: bad nonce"));
+        }
+        let (nonce, ciphertext) = rest[3..].split_at(nonce_len);
+        Ok(Self {
+            cipher,
+            nonce: nonce.to_vec(),
+            ciphertext: ciphertext.to_vec(),
+        })
+    }
+
+    pub fn is_armored(text: &[u8]) -> bool {
+        text.trim_ascii_start()
+            .starts_with(MESSAGE_BEGIN.as_bytes())
+    }
+
+    pub fn encode(&self) -> Result<String> {
+        let mut lines = vec![
+            MESSAGE_BEGIN.to_string(),
+            format!("Cipher: {}", self.cipher),
+            String::new(),
+        ];
+        let body = STANDARD.encode(self.to_bytes()?);
+        for chunk in body.as_bytes().chunks(MESSAGE_WIDTH) {
+            lines.push(String::from_utf8_lossy(chunk).to_string());
+        }
+        lines.push(MESSAGE_END.to_string());
+        Ok(lines.join("\n"))
+    }
+
+    /// Parse an armored message; the headers are informational, the binary header
+    /// inside the body is what counts
+    pub fn decode(text: &str) -> Result<Self> {
+        let invalid = |msg: &str| RcliError::format(format!("Invalid encrypted message: {}", msg));
+        let mut lines = text.trim().lines().map(str::trim);
+        if lines.next() != Some(MESSAGE_BEGIN) {
+            return Err(invalid("missing BEGIN line"));
+        }
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+        }
+        let mut body = String::new();
+        let mut ended = false;
+        for line in lines {
+            if line == MESSAGE_END {
+                ended = true;
+                break;
+            }
+            body.push_str(line);
+        }
+        if !ended {
+            return Err(invalid("missing END line"));
+        }
+        Self::parse(&STANDARD.decode(body)?)
+    }
+}
+
+fn nonce_len(cipher: TextCipher) -> Result<usize> {
+    match cipher {
+        TextCipher::ChaCha20Poly1305 | TextCipher::Aes256Gcm => Ok(12),
+        TextCipher::XChaCha20Poly1305 => Ok(24),
+        TextCipher::OpenSslAes256Cbc => Err(RcliError::Unsupported(
+            "Armored messages require an AEAD cipher".to_string(),
+        )),
+    }
+}
+
+/// Encrypt the input into an armored message
+pub fn process_text_encrypt_armored(
+    input: impl Into<Input>,
+    key: &str,
+    cipher: TextCipher,
+) -> Result<String> {
+    let mut reader = get_reader(input)?;
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    EncryptedMessage::seal(&buf, key, cipher)?.encode()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_text_decrypt;
+
+    const KEY: &str = "fixtures/chacha20poly1305.txt";
+
+    #[test]
+    fn test_encrypted_message_round_trip() -> Result<()> {
+        for cipher in [
+            TextCipher::ChaCha20Poly1305,
+            TextCipher::XChaCha20Poly1305,
+            TextCipher::Aes256Gcm,
+        ] {
+            let message = EncryptedMessage::seal(b"hello world", KEY, cipher)?;
+            let armored = message.encode()?;
+            assert!(EncryptedMessage::is_armored(armored.as_bytes()));
+            assert!(armored.contains(&format!("Cipher: {}", cipher)));
+            let decoded = EncryptedMessage::decode(&armored)?;
+            assert_eq!(decoded, message);
+            assert_eq!(decoded.open(KEY)?, b"hello world");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_text_decrypt_armored() -> Result<()> {
+        let armored = process_text_encrypt_armored("fixtures/b64.txt", KEY, TextCipher::Aes256Gcm)?;
+        let path = std::env::temp_dir().join("rcli-armored-message.txt");
+        std::fs::write(&path, &armored)?;
+        // the cipher comes from the message, not the one passed in
+        let decrypted =
+            process_text_decrypt(path.to_str().unwrap(), &[KEY], TextCipher::ChaCha20Poly1305)?;
+        assert_eq!(decrypted, std::fs::read_to_string("fixtures/b64.txt")?);
+
+        let mut bytes = EncryptedMessage::decode(&armored)?.to_bytes()?;
+        bytes[MESSAGE_MAGIC.len()] = 9;
+        assert!(matches!(
+            EncryptedMessage::parse(&bytes),
+            Err(RcliError::Unsupported(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+};
+
+use chacha20poly1305::{
+    aead::stream::{DecryptorBE32, EncryptorBE32},
+    KeyInit, XChaCha20Poly1305,
+};
+use rand::RngCore;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use zeroize::Zeroizing;
+
+use super::text::key32;
+use crate::{read_key, secure_rng, timer, RcliError, Result};
+
+// file layout: magic | version | 19 bytes stream nonce | chunk* | last chunk
+const STREAM_MAGIC: &[u8] = b"RCLS";
+const STREAM_VERSION: u8 = 1;
+// XChaCha20 nonce (24 bytes) minus the 5 bytes STREAM counter and last-block flag
+const STREAM_NONCE_LEN: usize = 19;
+const STREAM_CHUNK: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = STREAM_MAGIC.len() + 1 + STREAM_NONCE_LEN;
+
+type StreamDecryptor = DecryptorBE32<XChaCha20Poly1305>;
+
+/// Encrypt the reader into the writer with XChaCha20-Poly1305 in the STREAM construction,
+/// so arbitrarily large inputs are processed in fixed-size chunks. Returns plaintext bytes read.
+pub fn process_stream_encrypt(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    key: impl AsRef<Path>,
+) -> Result<u64> {
+    let timer = timer("chacha20 stream encrypt");
+    let (mut encryptor, header) = stream_encryptor(key)?;
+    writer.write_all(&header)?;
+
+    let mut reader = BufReader::new(reader);
+    let mut buf = vec![0u8; STREAM_CHUNK];
+    let mut total = 0u64;
+    loop {
+        let n = read_full(&mut reader, &mut buf)?;
+        total += n as u64;
+        timer.add_bytes(n as u64);
+        // a chunk is the last one when it's short or nothing follows it
+        if n < STREAM_CHUNK || reader.fill_buf()?.is_empty() {
+            writer.write_all(&encrypt_last(encryptor, &buf[..n])?)?;
+            break;
+        }
+        writer.write_all(&encrypt_next(&mut encryptor, &buf[..n])?)?;
+    }
+    writer.flush()?;
+    Ok(total)
+}
+
+/// [`process_stream_encrypt`] on async IO, which leaves the runtime free between chunks
+pub async fn process_stream_encrypt_async(
+    reader: &mut (dyn AsyncRead + Unpin + Send),
+    writer: &mut (dyn AsyncWrite + Unpin + Send),
+    key: impl AsRef<Path>,
+) -> Result<u64> {
+    let timer = timer("chacha20 stream encrypt");
+    let (mut encryptor, header) = stream_encryptor(key)?;
+    writer.write_all(&header).await?;
+
+    let mut reader = tokio::io::BufReader::new(reader);
+    let mut buf = vec![0u8; STREAM_CHUNK];
+    let mut total = 0u64;
+    loop {
+        let n = read_full_async(&mut reader, &mut buf).await?;
+        total += n as u64;
+        timer.add_bytes(n as u64);
+        if n < STREAM_CHUNK || reader.fill_buf().await?.is_empty() {
+            writer
+                .write_all(&encrypt_last(encryptor, &buf[..n])?)
+                .await?;
+            break;
+        }
+        writer
+            .write_all(&encrypt_next(&mut encryptor, &buf[..n])?)
+            .await?;
+    }
+    writer.flush().await?;
+    Ok(total)
+}
+
+/// Decrypt a stream produced by `process_stream_encrypt`, authenticating every chunk before
+/// writing it out. Fails on the first tampered, reordered or truncated chunk. The first
+/// chunk picks which of `keys` is used: the first one it authenticates with.
+pub fn process_stream_decrypt(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    keys: &[impl AsRef<Path>],
+) -> Result<u64> {
+    let timer = timer("chacha20 stream decrypt");
+    let mut reader = BufReader::new(reader);
+    let mut header = [0u8; HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| RcliError::format("Invalid data: missing stream header"))?;
+
+    let mut buf = vec![0u8; STREAM_CHUNK + TAG_LEN];
+    let mut decryptor = None;
+    let mut total = 0u64;
+    loop {
+        let n = read_full(&mut reader, &mut buf)?;
+        timer.add_bytes(n as u64);
+        let last = n < STREAM_CHUNK + TAG_LEN || reader.fill_buf()?.is_empty();
+        let (next, decrypted) = match decryptor {
+            Some(decryptor) => decrypt_chunk(decryptor, &buf[..n], last, total)?,
+            None => decrypt_first_chunk(keys, &header, &buf[..n], last)?,
+        };
+        writer.write_all(&decrypted)?;
+        total += decrypted.len() as u64;
+        if last {
+            break;
+        }
+        decryptor = next;
+    }
+    writer.flush()?;
+    Ok(total)
+}
+
+/// [`process_stream_decrypt`] on async IO
+pub async fn process_stream_decrypt_async(
+    reader: &mut (dyn AsyncRead + Unpin + Send),
+    writer: &mut (dyn AsyncWrite + Unpin + Send),
+    keys: &[impl AsRef<Path>],
+) -> Result<u64> {
+    let timer = timer("chacha20 stream decrypt");
+    let mut reader = tokio::io::BufReader::new(reader);
+    let mut header = [0u8; HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .await
+        .map_err(|_| RcliError::format("Invalid data: missing stream header"))?;
+
+    let mut buf = vec![0u8; STREAM_CHUNK + TAG_LEN];
+    let mut decryptor = None;
+    let mut total = 0u64;
+    loop {
+        let n = read_full_async(&mut reader, &mut buf).await?;
+        timer.add_bytes(n as u64);
+        let last = n < STREAM_CHUNK + TAG_LEN || reader.fill_buf().await?.is_empty();
+        let (next, decrypted) = match decryptor {
+            Some(decryptor) => decrypt_chunk(decryptor, &buf[..n], last, total)?,
+            None => decrypt_first_chunk(keys, &header, &buf[..n], last)?,
+        };
+        writer.write_all(&decrypted).await?;
+        total += decrypted.len() as u64;
+        if last {
+            break;
+        }
+        decryptor = next;
+    }
+    writer.flush().await?;
+    Ok(total)
+}
+
+/// A fresh encryptor and the header that goes in front of its chunks
+fn stream_encryptor(key: impl AsRef<Path>) -> Result<(EncryptorBE32<XChaCha20Poly1305>, Vec<u8>)> {
+    let key = Zeroizing::new(key32(&read_key(key)?)?);
+    let mut nonce = [0u8; STREAM_NONCE_LEN];
+    secure_rng()?.fill_bytes(&mut nonce);
+    let encryptor =
+        EncryptorBE32::from_aead(XChaCha20Poly1305::new((&*key).into()), (&nonce).into());
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(STREAM_MAGIC);
+    header.push(STREAM_VERSION);
+    header.extend_from_slice(&nonce);
+    Ok((encryptor, header))
+}
+
+fn stream_decryptor(key: impl AsRef<Path>, header: &[u8; HEADER_LEN]) -> Result<StreamDecryptor> {
+    let key = Zeroizing::new(key32(&read_key(key)?)?);
+    if &header[..4] != STREAM_MAGIC {
+        return Err(RcliError::format(
+            "Invalid data: not an rcli encrypted file",
+        ));
+    }
+    if header[4] != STREAM_VERSION {
+        return Err(RcliError::Unsupported(format!(
+            "Unsupported stream version: {}",
+            header[4]
+        )));
+    }
+    let nonce = &header[5..];
+    Ok(DecryptorBE32::from_aead(
+        XChaCha20Poly1305::new((&*key).into()),
+        nonce.into(),
+    ))
+}
+
+fn encrypt_next(encryptor: &mut EncryptorBE32<XChaCha20Poly1305>, chunk: &[u8]) -> Result<Vec<u8>> {
+    encryptor
+        .encrypt_next(chunk)
+        .map_err(|e| RcliError::crypto(format!("Error encrypting data: {}", e)))
+}
+
+fn encrypt_last(encryptor: EncryptorBE32<XChaCha20Poly1305>, chunk: &[u8]) -> Result<Vec<u8>> {
+    encryptor
+        .encrypt_last(chunk)
+        .map_err(|e| RcliError::crypto(format!("Error encrypting data: {}", e)))
+}
+
+/// `offset` is the plaintext offset of the chunk, for the error message
+fn decrypt_next(decryptor: &mut StreamDecryptor, chunk: &[u8], offset: u64) -> Result<Vec<u8>> {
+    decryptor.decrypt_next(chunk).map_err(|_| {
+        RcliError::crypto(format!(
+            "Error decrypting data: chunk at plaintext offset {} failed authentication",
+            offset
+        ))
+    })
+}
+
+fn decrypt_last(decryptor: StreamDecryptor, chunk: &[u8]) -> Result<Vec<u8>> {
+    decryptor
+        .decrypt_last(chunk)
+        .map_err(|_| RcliError::crypto("Error decrypting data: last chunk failed authentication"))
+}
+
+/// Decrypt a chunk, handing the decryptor back unless it was the last one
+fn decrypt_chunk(
+    mut decryptor: StreamDecryptor,
+    chunk: &[u8],
+    last: bool,
+    offset: u64,
+) -> Result<(Option<StreamDecryptor>, Vec<u8>)> {
+    if last {
+        return Ok((None, decrypt_last(decryptor, chunk)?));
+    }
+    let decrypted = decrypt_next(&mut decryptor, chunk, offset)?;
+    Ok((Some(decryptor), decrypted))
+}
+
+/// Try every key on the first chunk and go on with the first it authenticates with
+fn decrypt_first_chunk(
+    keys: &[impl AsRef<Path>],
+    header: &[u8; HEADER_LEN],
+    chunk: &[u8],
+    last: bool,
+) -> Result<(Option<StreamDecryptor>, Vec<u8>)> {
+    let mut error = RcliError::invalid_key("No key given");
+    for key in keys {
+        match decrypt_chunk(stream_decryptor(key, header)?, chunk, last, 0) {
+            Ok(result) => return Ok(result),
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+/// Fill the buffer unless EOF is reached first, returning the number of bytes read
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..])? {
+            0 => break,
+            read => n += read,
+        }
+    }
+    Ok(n)
+}
+
+async fn read_full_async(reader: &mut (impl AsyncRead + Unpin), buf: &mut [u8]) -> Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..]).await? {
+            0 => break,
+            read => n += read,
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "fixtures/chacha20poly1305.txt";
+
+    #[test]
+    fn test_stream_encrypt_decrypt() -> Result<()> {
+        for len in [0, 1, STREAM_CHUNK, STREAM_CHUNK * 2 + 17] {
+            let data: Vec<u8> = (0..len).map(|i| (i % 253) as u8).collect();
+            let mut encrypted = Vec::new();
+            assert_eq!(
+                process_stream_encrypt(&mut &data[..], &mut encrypted, KEY)?,
+                len as u64
+            );
+            let mut decrypted = Vec::new();
+            process_stream_decrypt(&mut &encrypted[..], &mut decrypted, &[KEY])?;
+            assert_eq!(decrypted, data);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_encrypt_decrypt_async() -> Result<()> {
+        let data: Vec<u8> = (0..STREAM_CHUNK * 2 + 9).map(|i| (i % 251) as u8).collect();
+        let mut encrypted = Vec::new();
+        process_stream_encrypt_async(&mut &data[..], &mut encrypted, KEY).await?;
+        // both directions interoperate with the blocking implementation
+        let mut decrypted = Vec::new();
+        process_stream_decrypt(&mut &encrypted[..], &mut decrypted, &[KEY])?;
+        assert_eq!(decrypted, data);
+        let mut encrypted = Vec::new();
+        process_stream_encrypt(&mut &data[..], &mut encrypted, KEY)?;
+        let mut decrypted = Vec::new();
+        process_stream_decrypt_async(&mut &encrypted[..], &mut decrypted, &[KEY]).await?;
+        assert_eq!(decrypted, data);
+
+        encrypted[HEADER_LEN] ^= 1;
+        let mut out = Vec::new();
+        assert!(
+            process_stream_decrypt_async(&mut &encrypted[..], &mut out, &[KEY])
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_decrypt_detects_tampering() -> Result<()> {
+        let data = vec![7u8; STREAM_CHUNK * 2];
+        let mut encrypted = Vec::new();
+        process_stream_encrypt(&mut &data[..], &mut encrypted, KEY)?;
+
+        let mut tampered = encrypted.clone();
+        tampered[100] ^= 1;
+        let mut out = Vec::new();
+        assert!(process_stream_decrypt(&mut &tampered[..], &mut out, &[KEY]).is_err());
+        // fail fast: nothing from the bad chunk was written
+        assert!(out.is_empty());
+
+        // dropping the last chunk must not go unnoticed
+        let truncated = &encrypted[..encrypted.len() - (STREAM_CHUNK + TAG_LEN)];
+        let mut out = Vec::new();
+        assert!(process_stream_decrypt(&mut &truncated[..], &mut out, &[KEY]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_decrypt_tries_every_key() -> Result<()> {
+        let data = vec![3u8; STREAM_CHUNK + 5];
+        let mut encrypted = Vec::new();
+        process_stream_encrypt(&mut &data[..], &mut encrypted, KEY)?;
+        let old = "fixtures/blake3.txt";
+        let mut decrypted = Vec::new();
+        process_stream_decrypt(&mut &encrypted[..], &mut decrypted, &[old, KEY])?;
+        assert_eq!(decrypted, data);
+        let mut out = Vec::new();
+        assert!(process_stream_decrypt(&mut &encrypted[..], &mut out, &[old]).is_err());
+        Ok(())
+    }
+}
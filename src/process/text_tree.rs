@@ -0,0 +1,233 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    hash::hash_reader,
+    text::{public_key_der, sign_reader, verify_reader},
+};
+use crate::{key_fingerprint, HashAlgorithm, RcliError, Result, TextSignFormat};
+
+const MANIFEST_VERSION: u32 = 1;
+
+/// Digest of every file under a directory, signed with ed25519 over its JSON encoding
+/// without the `signature` field
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TreeManifest {
+    pub version: u32,
+    pub algorithm: String,
+    pub created: DateTime<Utc>,
+    /// `sha256:<hex>` of the public key
+    pub key: Option<String>,
+    /// hex digest by path relative to the directory, `/` separated
+    pub files: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct TreeVerifyResult {
+    pub signature_valid: bool,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+impl TreeVerifyResult {
+    pub fn is_valid(&self) -> bool {
+        self.signature_valid
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+impl TreeManifest {
+    fn signed_bytes(&self) -> Result<Vec<u8>> {
+        let unsigned = TreeManifest {
+            version: self.version,
+            algorithm: self.algorithm.clone(),
+            created: self.created,
+            key: self.key.clone(),
+            files: self.files.clone(),
+            signature: None,
+        };
+        Ok(serde_json::to_vec(&unsigned)?)
+    }
+}
+
+/// Hash every file under `dir` and sign the result with an ed25519 private key.
+/// `exclude` is left out, typically the manifest itself when written inside `dir`.
+pub fn process_text_sign_tree(
+    dir: &Path,
+    key: &str,
+    exclude: Option<&Path>,
+) -> Result<TreeManifest> {
+    let algorithm = HashAlgorithm::Sha256;
+    let mut manifest = TreeManifest {
+        version: MANIFEST_VERSION,
+        algorithm: algorithm.to_string(),
+        created: Utc::now(),
+        key: public_key_der(key, TextSignFormat::Ed25519, true)?.map(|der| key_fingerprint(&der)),
+        files: hash_tree(dir, algorithm, exclude)?,
+        signature: None,
+    };
+    let signature = sign_reader(
+        &mut manifest.signed_bytes()?.as_slice(),
+        key,
+        TextSignFormat::Ed25519,
+    )?;
+    manifest.signature = Some(STANDARD.encode(signature));
+    Ok(manifest)
+}
+
+/// Check the manifest signature against an ed25519 public key, then compare `dir` with
+/// the files it lists
+pub fn process_text_verify_tree(
+    dir: &Path,
+    key: &str,
+    manifest: &TreeManifest,
+    exclude: Option<&Path>,
+) -> Result<TreeVerifyResult> {
+    if manifest.version != MANIFEST_VERSION {
+        return Err(RcliError::Unsupported(format!(
+            "Manifest version {} is not supported",
+            manifest.version
+        )));
+    }
+    if let Some(expected) = &manifest.key {
+        let actual =
+            public_key_der(key, TextSignFormat::Ed25519, false)?.map(|der| key_fingerprint(&der));
+        if actual.as_ref() != Some(expected) {
+            return Err(RcliError::invalid_key(format!(
+                "Signed with key {}, not {}",
+                expected,
+                actual.unwrap_or_default()
+            )));
+        }
+    }
+    let signature = manifest
+        .signature
+        .as_deref()
+        .ok_or_else(|| RcliError::format("Manifest is not signed"))?;
+    let signature_valid = verify_reader(
+        &mut manifest.signed_bytes()?.as_slice(),
+        key,
+        TextSignFormat::Ed25519,
+        &STANDARD.decode(signature)?,
+    )?
+    .is_valid();
+    let algorithm: HashAlgorithm = manifest
+        .algorithm
+        .parse()
+        .map_err(|_| RcliError::format(format!("Unknown algorithm: {}", manifest.algorithm)))?;
+
+    let actual = hash_tree(dir, algorithm, exclude)?;
+    let mut result = TreeVerifyResult {
+        signature_valid,
+        ..Default::default()
+    };
+    for (path, digest) in &actual {
+        match manifest.files.get(path) {
+            None => result.added.push(path.clone()),
+            Some(expected) if expected != digest => result.modified.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    result.removed = manifest
+        .files
+        .keys()
+        .filter(|path| !actual.contains_key(*path))
+        .cloned()
+        .collect();
+    Ok(result)
+}
+
+fn hash_tree(
+    dir: &Path,
+    algorithm: HashAlgorithm,
+    exclude: Option<&Path>,
+) -> Result<BTreeMap<String, String>> {
+    let exclude = exclude.and_then(|p| fs::canonicalize(p).ok());
+    let mut files = BTreeMap::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            if exclude.is_some() && fs::canonicalize(&path).ok() == exclude {
+                continue;
+            }
+            let digest = hash_reader(&mut File::open(&path)?, algorithm)?;
+            files.insert(relative_name(dir, &path), digest);
+        }
+    }
+    Ok(files)
+}
+
+/// `/` separated so manifests compare equal across platforms
+fn relative_name(dir: &Path, path: &Path) -> String {
+    let rel: PathBuf = path.strip_prefix(dir).unwrap_or(path).to_path_buf();
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_generate_key, KeyFormat};
+
+    #[test]
+    fn test_sign_verify_tree() -> Result<()> {
+        let root = std::env::temp_dir().join("rcli-sign-tree");
+        let _ = fs::remove_dir_all(&root);
+        let dist = root.join("dist");
+        fs::create_dir_all(dist.join("assets"))?;
+        fs::write(dist.join("app"), "binary")?;
+        fs::write(dist.join("assets/style.css"), "body{}")?;
+        fs::write(dist.join("README"), "docs")?;
+        let keys = process_generate_key(TextSignFormat::Ed25519, KeyFormat::Raw, None)?;
+        let (sk, pk) = (root.join("ed25519.sk"), root.join("ed25519.pk"));
+        fs::write(&sk, &keys[0])?;
+        fs::write(&pk, &keys[1])?;
+        let (sk, pk) = (sk.to_str().unwrap(), pk.to_str().unwrap());
+
+        let manifest_path = dist.join("manifest.json");
+        fs::write(&manifest_path, "")?;
+        let manifest = process_text_sign_tree(&dist, sk, Some(&manifest_path))?;
+        assert_eq!(
+            manifest.files.keys().collect::<Vec<_>>(),
+            ["README", "app", "assets/style.css"]
+        );
+        // survives a round trip through the JSON file
+        let manifest: TreeManifest = serde_json::from_str(&serde_json::to_string(&manifest)?)?;
+        let result = process_text_verify_tree(&dist, pk, &manifest, Some(&manifest_path))?;
+        assert!(result.is_valid());
+
+        fs::write(dist.join("app"), "tampered")?;
+        fs::remove_file(dist.join("README"))?;
+        fs::write(dist.join("assets/extra.js"), "")?;
+        let result = process_text_verify_tree(&dist, pk, &manifest, Some(&manifest_path))?;
+        assert!(result.signature_valid && !result.is_valid());
+        assert_eq!(result.modified, ["app"]);
+        assert_eq!(result.removed, ["README"]);
+        assert_eq!(result.added, ["assets/extra.js"]);
+
+        let mut forged = manifest;
+        forged.files.insert("app".to_string(), "00".to_string());
+        let result = process_text_verify_tree(&dist, pk, &forged, Some(&manifest_path))?;
+        assert!(!result.signature_valid);
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::{RcliError, Result, TimeFormat};
+
+// epochs above these magnitudes can't be seconds (year 5138+), so they are taken as
+// milliseconds, microseconds or nanoseconds respectively
+const MAX_SECS: i64 = 100_000_000_000;
+const MAX_MILLIS: i64 = MAX_SECS * 1000;
+const MAX_MICROS: i64 = MAX_MILLIS * 1000;
+
+/// Parse an epoch number (unit guessed from its magnitude), RFC 3339, RFC 2822 or a
+/// plain `YYYY-MM-DD[ HH:MM:SS]` taken as UTC
+pub fn process_time_parse(value: &str) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    let parsed = if let Ok(n) = value.parse::<i64>() {
+        match n.abs() {
+            n if n < MAX_SECS => Utc.timestamp_opt(n, 0).single(),
+            n if n < MAX_MILLIS => Utc.timestamp_millis_opt(n).single(),
+            n if n < MAX_MICROS => Some(Utc.timestamp_nanos(n * 1000)),
+            _ => Some(Utc.timestamp_nanos(n)),
+        }
+    } else if let Ok(secs) = value.parse::<f64>() {
+        DateTime::from_timestamp_micros((secs * 1_000_000.0).round() as i64)
+    } else if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        Some(t.with_timezone(&Utc))
+    } else if let Ok(t) = DateTime::parse_from_rfc2822(value) {
+        Some(t.with_timezone(&Utc))
+    } else if let Ok(t) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        Some(t.and_utc())
+    } else if let Ok(d) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        d.and_hms_opt(0, 0, 0).map(|t| t.and_utc())
+    } else {
+        None
+    };
+    parsed.ok_or_else(|| RcliError::format(format!("Unrecognized timestamp: {}", value)))
+}
+
+pub fn process_time_format(time: DateTime<Utc>, format: TimeFormat, tz: Option<Tz>) -> String {
+    let time = time.with_timezone(&tz.unwrap_or(Tz::UTC));
+    match format {
+        TimeFormat::Unix => time.timestamp().to_string(),
+        TimeFormat::UnixMs => time.timestamp_millis().to_string(),
+        TimeFormat::Iso => time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        TimeFormat::Rfc2822 => time.to_rfc2822(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_time_parse() -> Result<()> {
+        let expected = Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap();
+        for value in [
+            "1714550400",
+            "1714550400000",
+            "1714550400000000",
+            "1714550400.0",
+            "2024-05-01T16:00:00+08:00",
+            "Wed, 01 May 2024 08:00:00 +0000",
+            "2024-05-01 08:00:00",
+        ] {
+            assert_eq!(process_time_parse(value)?, expected, "{}", value);
+        }
+        assert!(process_time_parse("yesterday").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_time_format() {
+        let time = Utc.timestamp_millis_opt(1714550400123).unwrap();
+        let tz = Some(chrono_tz::Asia::Shanghai);
+        assert_eq!(
+            process_time_format(time, TimeFormat::Iso, tz),
+            "2024-05-01T16:00:00.123+08:00"
+        );
+        assert_eq!(
+            process_time_format(time, TimeFormat::Iso, None),
+            "2024-05-01T08:00:00.123Z"
+        );
+        assert_eq!(
+            process_time_format(time, TimeFormat::UnixMs, tz),
+            "1714550400123"
+        );
+        assert_eq!(
+            process_time_format(time, TimeFormat::Rfc2822, None),
+            "Wed, 1 May 2024 08:00:00 +0000"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use std::io::Read;
+
+use percent_encoding::{percent_decode, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Serialize;
+use url::{form_urlencoded, Url};
+
+use crate::{get_reader, Input, RcliError, Result};
+
+/// Everything but the RFC 3986 unreserved characters, so the result fits in any URL part
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// The parts of a URL, as `url parse` prints them
+#[derive(Debug, PartialEq, Serialize)]
+pub struct UrlParts {
+    pub scheme: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub host: Option<String>,
+    /// the explicit port, or the scheme's default one (443 for https)
+    pub port: Option<u16>,
+    pub path: String,
+    pub query: Option<String>,
+    /// the query split into decoded pairs, in order; names may repeat
+    pub params: Vec<UrlParam>,
+    pub fragment: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct UrlParam {
+    pub name: String,
+    pub value: String,
+}
+
+/// Percent-encode the input as a URL component. With `form`, spaces become `+` as in
+/// `application/x-www-form-urlencoded` query strings.
+pub fn process_url_encode(input: impl Into<Input>, form: bool) -> Result<String> {
+    let data = read_input(input)?;
+    if form {
+        return Ok(form_urlencoded::byte_serialize(&data).collect());
+    }
+    Ok(match std::str::from_utf8(&data) {
+        Ok(text) => utf8_percent_encode(text, COMPONENT).to_string(),
+        Err(_) => percent_encoding::percent_encode(&data, COMPONENT).to_string(),
+    })
+}
+
+/// Undo percent-encoding. With `form`, `+` is read as a space first. The result may be
+/// binary, since `%ff` is valid anywhere.
+pub fn process_url_decode(input: impl Into<Input>, form: bool) -> Result<Vec<u8>> {
+    let mut data = read_input(input)?;
+    if form {
+        data.iter_mut()
+            .filter(|b| **b == b'+')
+            .for_each(|b| *b = b' ');
+    }
+    Ok(percent_decode(&data).collect())
+}
+
+pub fn process_url_parse(url: &str) -> Result<UrlParts> {
+    let url = Url::parse(url.trim())
+        .map_err(|e| RcliError::format(format!("Invalid URL {}: {}", url, e)))?;
+    let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+    Ok(UrlParts {
+        scheme: url.scheme().to_string(),
+        username: non_empty(url.username()),
+        password: url.password().map(str::to_string),
+        host: url.host_str().map(str::to_string),
+        port: url.port_or_known_default(),
+        path: url.path().to_string(),
+        query: url.query().map(str::to_string),
+        params: url
+            .query_pairs()
+            .map(|(name, value)| UrlParam {
+                name: name.into_owned(),
+                value: value.into_owned(),
+            })
+            .collect(),
+        fragment: url.fragment().map(str::to_string),
+    })
+}
+
+/// The whole input, without the newline `echo` leaves at its end
+fn read_input(input: impl Into<Input>) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    get_reader(input)?.read_to_end(&mut data)?;
+    if data.ends_with(b"\n") {
+        data.pop();
+        if data.ends_with(b"\r") {
+            data.pop();
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!("rcli-url-{}", name));
+        std::fs::write(&path, text).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_url_encode_decode() -> Result<()> {
+        let encoded = process_url_encode(&input("text", "a b&c=d/é~\n"), false)?;
+        assert_eq!(encoded, "a%20b%26c%3Dd%2F%C3%A9~");
+        assert_eq!(
+            process_url_encode(&input("form", "a b+c\n"), true)?,
+            "a+b%2Bc"
+        );
+        assert_eq!(
+            process_url_decode(&input("encoded", &encoded), false)?,
+            "a b&c=d/é~".as_bytes()
+        );
+        assert_eq!(
+            process_url_decode(&input("form-encoded", "a+b%2Bc"), true)?,
+            b"a b+c"
+        );
+        // without form decoding + stays a plus
+        assert_eq!(
+            process_url_decode(&input("binary", "a+b%ff"), false)?,
+            b"a+b\xff"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_url_parse() -> Result<()> {
+        let parts = process_url_parse("https://user:pw@example.com/a%20b/c?q=rust+cli&q=2&x#top")?;
+        assert_eq!(parts.scheme, "https");
+        assert_eq!(parts.username.as_deref(), Some("user"));
+        assert_eq!(parts.password.as_deref(), Some("pw"));
+        assert_eq!(parts.host.as_deref(), Some("example.com"));
+        assert_eq!(parts.port, Some(443));
+        assert_eq!(parts.path, "/a%20b/c");
+        assert_eq!(parts.query.as_deref(), Some("q=rust+cli&q=2&x"));
+        let params: Vec<_> = parts
+            .params
+            .iter()
+            .map(|p| (p.name.as_str(), p.value.as_str()))
+            .collect();
+        assert_eq!(params, [("q", "rust cli"), ("q", "2"), ("x", "")]);
+        assert_eq!(parts.fragment.as_deref(), Some("top"));
+
+        let parts = process_url_parse("http://localhost:8080")?;
+        assert_eq!((parts.port, parts.path.as_str()), (Some(8080), "/"));
+        assert!(process_url_parse("not a url").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::text::{password_open, password_seal};
+use crate::{write_key_file, RcliError, Result, Secret, TextCipher};
+
+/// name → secret entries, stored as JSON inside an Argon2id + ChaCha20-Poly1305 envelope
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Vault {
+    entries: BTreeMap<String, VaultEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultEntry {
+    pub secret: Secret,
+    pub created: String,
+    pub updated: String,
+}
+
+impl Vault {
+    /// Open the vault at `path`; a missing file is an empty vault
+    pub fn open(path: impl AsRef<Path>, password: &str) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Vault::default());
+        }
+        let data = fs::read(path)?;
+        let plain = password_open(&data, password).map_err(|_| {
+            RcliError::crypto(format!(
+                "Cannot open vault {}: wrong password?",
+                path.display()
+            ))
+        })?;
+        Ok(serde_json::from_slice(&plain)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, password: &str) -> Result<()> {
+        let path = path.as_ref();
+        let plain = serde_json::to_vec(self)?;
+        let data = password_seal(&plain, password, TextCipher::ChaCha20Poly1305)?;
+        // write then rename so an interrupted save never corrupts the vault; the file is
+        // owner-only like any other secret key file
+        let tmp = path.with_extension("tmp");
+        write_key_file(&tmp, &data, true, true)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    pub fn add(&mut self, name: &str, secret: Secret, force: bool) -> Result<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        match self.entries.get_mut(name) {
+            Some(_) if !force => Err(RcliError::AlreadyExists(format!("Entry {}", name))),
+            Some(entry) => {
+                entry.secret = secret;
+                entry.updated = now;
+                Ok(())
+            }
+            None => {
+                let entry = VaultEntry {
+                    secret,
+                    created: now.clone(),
+                    updated: now,
+                };
+                self.entries.insert(name.to_string(), entry);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<&VaultEntry> {
+        self.entries
+            .get(name)
+            .ok_or_else(|| RcliError::NotFound(format!("Entry {}", name)))
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<VaultEntry> {
+        self.entries
+            .remove(name)
+            .ok_or_else(|| RcliError::NotFound(format!("Entry {}", name)))
+    }
+
+    pub fn list(&self) -> impl Iterator<Item = (&String, &VaultEntry)> {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vault_roundtrip() -> Result<()> {
+        let path = std::env::temp_dir().join("rcli-test.vault");
+        let _ = fs::remove_file(&path);
+        let mut vault = Vault::open(&path, "master")?;
+        vault.add("github", "s3cret".to_string().into(), false)?;
+        assert!(vault
+            .add("github", "other".to_string().into(), false)
+            .is_err());
+        vault.save(&path, "master")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+        }
+
+        let mut vault = Vault::open(&path, "master")?;
+        assert_eq!(vault.get("github")?.secret.expose_str(), "s3cret");
+        assert!(Vault::open(&path, "wrong").is_err());
+        vault.remove("github")?;
+        assert!(vault.get("github").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use tracing::debug;
+
+use crate::{RcliError, Result};
+
+/// Editors save in bursts (truncate, write, rename), so changes this close together
+/// trigger a single re-run
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// Run `step` once, then again each time one of `paths` changes, until interrupted.
+/// `step` deals with its own failures, so saving a half-edited file doesn't end the
+/// session.
+pub fn process_watch(paths: &[&Path], step: impl FnMut()) -> Result<()> {
+    watch(paths, None, step)
+}
+
+fn watch(paths: &[&Path], runs: Option<usize>, mut step: impl FnMut()) -> Result<()> {
+    let targets = paths
+        .iter()
+        .map(|path| {
+            path.canonicalize()
+                .map_err(|_| RcliError::FileNotFound(path.to_path_buf()))
+        })
+        .collect::<Result<HashSet<PathBuf>>>()?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    // watch the directories rather than the files, a file replaced by a rename would
+    // otherwise stop being watched after the first save
+    let dirs = targets
+        .iter()
+        .filter_map(|path| path.parent())
+        .collect::<HashSet<_>>();
+    for dir in dirs {
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    }
+
+    let mut run = 0;
+    loop {
+        step();
+        run += 1;
+        if runs.is_some_and(|runs| run >= runs) {
+            return Ok(());
+        }
+        wait_for_change(&rx, &targets)?;
+    }
+}
+
+fn wait_for_change(
+    rx: &mpsc::Receiver<notify::Result<notify::Event>>,
+    targets: &HashSet<PathBuf>,
+) -> Result<()> {
+    loop {
+        let event = rx
+            .recv()
+            .map_err(|_| std::io::Error::other("file watcher stopped"))??;
+        if !matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) || !event.paths.iter().any(|path| targets.contains(path))
+        {
+            continue;
+        }
+        debug!(paths = ?event.paths, kind = ?event.kind, "input changed");
+        // let the save finish, then drop whatever else it produced
+        std::thread::sleep(SETTLE);
+        while rx.try_recv().is_ok() {}
+        // removed and not recreated yet, wait for it to come back
+        if targets.iter().all(|path| path.exists()) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, fs};
+
+    #[test]
+    fn test_watch_reruns_on_change() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join("rcli-watch");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let input = dir.join("input.csv");
+        fs::write(&input, "a,b\n1,2\n")?;
+        let other = dir.join("other.txt");
+
+        let writer = {
+            let (input, other) = (input.clone(), other.clone());
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(300));
+                // files next to the input don't count
+                fs::write(other, "ignored").unwrap();
+                std::thread::sleep(Duration::from_millis(300));
+                fs::write(input, "a,b\n3,4\n").unwrap();
+            })
+        };
+        let seen = Cell::new(Vec::new());
+        watch(&[&input], Some(2), || {
+            let mut runs = seen.take();
+            runs.push(fs::read_to_string(&input).unwrap());
+            seen.set(runs);
+        })?;
+        writer.join().unwrap();
+        assert_eq!(seen.take(), ["a,b\n1,2\n", "a,b\n3,4\n"]);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
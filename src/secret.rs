@@ -0,0 +1,71 @@
+use std::{convert::Infallible, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// A key, token or password that must never end up in Debug output, logs or error
+/// messages. The value is only reachable through `expose`, which makes every use greppable.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret<T = String>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl Secret {
+    pub fn expose_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+// lets clap parse secret-bearing options directly
+impl FromStr for Secret {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_redacted() -> anyhow::Result<()> {
+        let secret: Secret = "hunter2".parse()?;
+        assert_eq!(format!("{:?}", secret), "Secret([REDACTED])");
+        assert_eq!(format!("{:?}", Some(&secret)), "Some(Secret([REDACTED]))");
+        assert_eq!(secret.to_string(), REDACTED);
+        assert_eq!(secret.expose_str(), "hunter2");
+        // serialized as the bare value so stored files keep their format
+        assert_eq!(serde_json::to_string(&secret)?, "\"hunter2\"");
+        Ok(())
+    }
+}
@@ -1,11 +1,473 @@
-use anyhow::Result;
-use std::{fs::File, io::Read};
+use crate::{RcliError, Result};
+use data_encoding::HEXLOWER_PERMISSIVE;
+use rand::{rngs::OsRng, CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::debug;
+use zeroize::Zeroizing;
 
-pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
-    let reader: Box<dyn Read> = if input == "-" {
-        Box::new(std::io::stdin())
-    } else {
-        Box::new(File::open(input)?)
+/// Set by the hidden `--seed` flag; every rng handed out afterwards is derived from it
+static RNG_SEED: Mutex<Option<SeedState>> = Mutex::new(None);
+
+struct SeedState {
+    rng: ChaCha20Rng,
+    insecure: bool,
+}
+
+/// OS randomness, or a deterministic stream derived from `--seed` for reproducible tests and docs
+pub enum RcliRng {
+    Os(OsRng),
+    Seeded(Box<ChaCha20Rng>),
+}
+
+/// A 256-bit ChaCha20 seed given in hex; shorter seeds are zero padded
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RngSeed([u8; 32]);
+
+impl RngSeed {
+    /// The same stream every time, for keys and passwords that have to be reproducible
+    pub fn rng(&self) -> RcliRng {
+        RcliRng::Seeded(Box::new(ChaCha20Rng::from_seed(self.0)))
+    }
+}
+
+impl FromStr for RngSeed {
+    type Err = RcliError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        let hex = if hex.len() % 2 == 1 {
+            format!("0{}", hex)
+        } else {
+            hex.to_string()
+        };
+        let bytes = HEXLOWER_PERMISSIVE
+            .decode(hex.as_bytes())
+            .map_err(|_| RcliError::format(format!("Invalid seed, expected hex: {}", s)))?;
+        if bytes.is_empty() || bytes.len() > 32 {
+            return Err(RcliError::format(
+                "Invalid seed, expected 1 to 64 hex digits",
+            ));
+        }
+        let mut seed = [0u8; 32];
+        seed[..bytes.len()].copy_from_slice(&bytes);
+        Ok(Self(seed))
+    }
+}
+
+// a seed is as sensitive as the keys derived from it
+impl fmt::Debug for RngSeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RngSeed(..)")
+    }
+}
+
+impl RngCore for RcliRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            RcliRng::Os(rng) => rng.next_u32(),
+            RcliRng::Seeded(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            RcliRng::Os(rng) => rng.next_u64(),
+            RcliRng::Seeded(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            RcliRng::Os(rng) => rng.fill_bytes(dest),
+            RcliRng::Seeded(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            RcliRng::Os(rng) => rng.try_fill_bytes(dest),
+            RcliRng::Seeded(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+// seeded output is only as secret as the seed, callers of `secure_rng` opt in via `--insecure-seed`
+impl CryptoRng for RcliRng {}
+
+pub fn set_rng_seed(seed: RngSeed, insecure: bool) {
+    let state = SeedState {
+        rng: ChaCha20Rng::from_seed(seed.0),
+        insecure,
+    };
+    *RNG_SEED.lock().expect("rng seed lock poisoned") = Some(state);
+}
+
+/// Randomness for output that isn't secret (sample data, shuffles), honoring `--seed`
+pub fn rng() -> RcliRng {
+    next_rng(&mut RNG_SEED.lock().expect("rng seed lock poisoned"), false)
+        .expect("seed is always allowed for non-secret output")
+}
+
+/// Randomness for keys, passwords, nonces and salts; refuses `--seed` without `--insecure-seed`
+pub fn secure_rng() -> Result<RcliRng> {
+    next_rng(&mut RNG_SEED.lock().expect("rng seed lock poisoned"), true)
+}
+
+fn next_rng(state: &mut Option<SeedState>, secret: bool) -> Result<RcliRng> {
+    match state {
+        None => Ok(RcliRng::Os(OsRng)),
+        Some(state) if secret && !state.insecure => Err(RcliError::Unsupported(
+            "--seed makes secrets predictable, pass --insecure-seed as well to use it here"
+                .to_string(),
+        )),
+        // each caller gets its own child stream so salts and nonces never repeat within a run,
+        // seeded with a full 256 bits of the parent
+        Some(state) => {
+            let mut seed = <ChaCha20Rng as SeedableRng>::Seed::default();
+            state.rng.fill_bytes(&mut seed);
+            Ok(RcliRng::Seeded(Box::new(ChaCha20Rng::from_seed(seed))))
+        }
+    }
+}
+
+/// Where a command's input is read from: a file, stdin (`-`) or the literal text given
+/// with `--text`, so one-offs don't need `echo` or a temp file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    File(PathBuf),
+    Stdin,
+    Text(String),
+}
+
+impl Input {
+    /// The `-i` value, or the `--text` one when given; clap keeps the two exclusive
+    pub fn new(input: &str, text: Option<&str>) -> Self {
+        match text {
+            Some(text) => Input::Text(text.to_string()),
+            None => Input::from(input),
+        }
+    }
+
+    /// The file read, for commands that need to watch or list it
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Input::File(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Input {
+    fn from(input: &str) -> Self {
+        match input {
+            "-" => Input::Stdin,
+            path => Input::File(path.into()),
+        }
+    }
+}
+
+impl From<&String> for Input {
+    fn from(input: &String) -> Self {
+        Input::from(input.as_str())
+    }
+}
+
+impl From<&Path> for Input {
+    fn from(path: &Path) -> Self {
+        Input::File(path.into())
+    }
+}
+
+impl From<&PathBuf> for Input {
+    fn from(path: &PathBuf) -> Self {
+        Input::File(path.clone())
+    }
+}
+
+impl From<&Input> for Input {
+    fn from(input: &Input) -> Self {
+        input.clone()
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::File(path) => write!(f, "{}", path.display()),
+            Input::Stdin => write!(f, "-"),
+            Input::Text(_) => write!(f, "--text"),
+        }
+    }
+}
+
+pub fn get_reader(input: impl Into<Input>) -> Result<Box<dyn Read>> {
+    let input = input.into();
+    debug!(%input, "opening input");
+    let reader: Box<dyn Read> = match input {
+        Input::File(path) => Box::new(File::open(&path).map_err(|e| file_error(&path, e))?),
+        Input::Stdin => Box::new(std::io::stdin()),
+        Input::Text(text) => Box::new(Cursor::new(text.into_bytes())),
     };
     Ok(reader)
 }
+
+/// Async counterpart of [`get_reader`], so large inputs don't block the runtime
+pub async fn get_async_reader(
+    input: impl Into<Input>,
+) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+    let input = input.into();
+    debug!(%input, "opening input");
+    let reader: Box<dyn AsyncRead + Unpin + Send> = match input {
+        Input::File(path) => Box::new(
+            tokio::fs::File::open(&path)
+                .await
+                .map_err(|e| file_error(&path, e))?,
+        ),
+        Input::Stdin => Box::new(tokio::io::stdin()),
+        Input::Text(text) => Box::new(Cursor::new(text.into_bytes())),
+    };
+    Ok(reader)
+}
+
+/// Read a whole file, reporting a missing one as `RcliError::FileNotFound`
+pub fn read_file(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).map_err(|e| file_error(path, e))?;
+    debug!(path = %path.display(), bytes = data.len(), "read file");
+    Ok(data)
+}
+
+/// Where a key is read from: a file, stdin (`-`) or an environment variable (`env:NAME`),
+/// so secrets never have to be written to disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    File(PathBuf),
+    Stdin,
+    Env(String),
+}
+
+impl KeySource {
+    pub fn parse(key: impl AsRef<Path>) -> Self {
+        let key = key.as_ref();
+        match key.to_str() {
+            Some("-") => KeySource::Stdin,
+            Some(s) if s.starts_with("env:") => KeySource::Env(s["env:".len()..].to_string()),
+            _ => KeySource::File(key.to_path_buf()),
+        }
+    }
+
+    /// The key bytes, wiped from memory when dropped
+    pub fn read(&self) -> Result<Zeroizing<Vec<u8>>> {
+        // only where the key comes from is logged, never its bytes
+        debug!(source = ?self, "reading key");
+        match self {
+            KeySource::File(path) => read_file(path).map(Zeroizing::new),
+            KeySource::Stdin => {
+                let mut key = Zeroizing::new(Vec::new());
+                std::io::stdin().read_to_end(&mut key)?;
+                Ok(key)
+            }
+            KeySource::Env(name) => std::env::var_os(name)
+                .map(|value| Zeroizing::new(value.into_encoded_bytes()))
+                .ok_or_else(|| RcliError::NotFound(format!("Environment variable {}", name))),
+        }
+    }
+}
+
+/// Read a key given as a file path, `-` or `env:NAME`, see [`KeySource`]
+pub fn read_key(key: impl AsRef<Path>) -> Result<Zeroizing<Vec<u8>>> {
+    KeySource::parse(key).read()
+}
+
+fn file_error(path: &Path, e: std::io::Error) -> RcliError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => RcliError::FileNotFound(path.into()),
+        _ => e.into(),
+    }
+}
+
+pub fn get_writer(output: &str) -> Result<Box<dyn Write>> {
+    debug!(output, "opening output");
+    let writer: Box<dyn Write> = if output == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(File::create(output)?)
+    };
+    Ok(writer)
+}
+
+/// Async counterpart of [`get_writer`]
+pub async fn get_async_writer(output: &str) -> Result<Box<dyn AsyncWrite + Unpin + Send>> {
+    debug!(output, "opening output");
+    let writer: Box<dyn AsyncWrite + Unpin + Send> = if output == "-" {
+        Box::new(tokio::io::stdout())
+    } else {
+        Box::new(tokio::fs::File::create(output).await?)
+    };
+    Ok(writer)
+}
+
+/// Write a key file, readable only by the owner when `secret` is set (Unix).
+/// Existing files are only replaced with `force`.
+pub fn write_key_file(path: &Path, data: &[u8], secret: bool, force: bool) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    if secret {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => RcliError::AlreadyExists(path.display().to_string()),
+        _ => e.into(),
+    })?;
+    // `mode` only applies to newly created files, so tighten overwritten ones too
+    #[cfg(unix)]
+    if secret {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(data)?;
+    debug!(path = %path.display(), secret, "wrote key file");
+    Ok(())
+}
+
+/// Render bytes in the canonical `xxd` layout: offset, 16 hex bytes in pairs, ascii column
+pub fn hexdump(data: &[u8], offset: usize) -> String {
+    let mut out = String::new();
+    for (i, line) in data.chunks(16).enumerate() {
+        out.push_str(&format!("{:08x}: ", offset + i * 16));
+        for j in 0..16 {
+            match line.get(j) {
+                Some(b) => out.push_str(&format!("{:02x}", b)),
+                None => out.push_str("  "),
+            }
+            if j % 2 == 1 {
+                out.push(' ');
+            }
+        }
+        out.push(' ');
+        for b in line {
+            out.push(if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_key_file() -> Result<()> {
+        let path = std::env::temp_dir().join("rcli-write-key.sk");
+        let _ = std::fs::remove_file(&path);
+        write_key_file(&path, b"secret", true, false)?;
+        assert!(write_key_file(&path, b"other", true, false).is_err());
+        write_key_file(&path, b"other", true, true)?;
+        assert_eq!(std::fs::read(&path)?, b"other");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_input() -> Result<()> {
+        assert_eq!(Input::new("-", None), Input::Stdin);
+        assert_eq!(Input::new("a.txt", None), Input::File("a.txt".into()));
+        let input = Input::new("-", Some("hello"));
+        assert_eq!(input, Input::Text("hello".to_string()));
+        assert_eq!(input.path(), None);
+        let mut text = String::new();
+        get_reader(&input)?.read_to_string(&mut text)?;
+        assert_eq!(text, "hello");
+        assert!(matches!(
+            get_reader("missing.txt"),
+            Err(RcliError::FileNotFound(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_source() -> Result<()> {
+        assert_eq!(KeySource::parse("-"), KeySource::Stdin);
+        assert_eq!(
+            KeySource::parse("env:RCLI_TEST_KEY"),
+            KeySource::Env("RCLI_TEST_KEY".to_string())
+        );
+        assert_eq!(
+            KeySource::parse("fixtures/blake3.txt"),
+            KeySource::File(PathBuf::from("fixtures/blake3.txt"))
+        );
+        std::env::set_var("RCLI_TEST_KEY", "secret");
+        assert_eq!(read_key("env:RCLI_TEST_KEY")?.as_slice(), b"secret");
+        assert!(read_key("env:RCLI_TEST_KEY_MISSING").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let dump = hexdump(b"Hello, World!\n", 0);
+        assert_eq!(
+            dump,
+            "00000000: 4865 6c6c 6f2c 2057 6f72 6c64 210a       Hello, World!.\n"
+        );
+    }
+
+    #[test]
+    fn test_rng_seed_from_str() -> Result<()> {
+        let seed: RngSeed = "0x0102".parse()?;
+        assert_eq!(&seed.0[..3], [1, 2, 0]);
+        // odd lengths are padded on the left, like the number they spell
+        assert_eq!("abc".parse::<RngSeed>()?.0[..2], [0x0a, 0xbc]);
+        assert_eq!(
+            seed.rng().next_u64(),
+            "0102".parse::<RngSeed>()?.rng().next_u64()
+        );
+        assert!("xyz".parse::<RngSeed>().is_err());
+        assert!("".parse::<RngSeed>().is_err());
+        assert!("00".repeat(33).parse::<RngSeed>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_rng_seeded() -> Result<()> {
+        let seeded = |insecure| {
+            Some(SeedState {
+                rng: ChaCha20Rng::seed_from_u64(42),
+                insecure,
+            })
+        };
+        let (mut a, mut b) = (seeded(false), seeded(false));
+        let first = next_rng(&mut a, false)?.next_u64();
+        assert_eq!(first, next_rng(&mut b, false)?.next_u64());
+        assert_ne!(first, next_rng(&mut a, false)?.next_u64());
+        assert!(next_rng(&mut a, true).is_err());
+        assert!(next_rng(&mut seeded(true), true).is_ok());
+        assert!(matches!(next_rng(&mut None, true)?, RcliRng::Os(_)));
+        Ok(())
+    }
+}